@@ -9,8 +9,13 @@ try {
 let crepe = null;
 let isEditing = false;
 let currentPath = null;
+// Version of the note the editor was loaded from, sent back on save
+let baseVersion = null;
 
 const editToggle = document.getElementById('edit-toggle');
+const editSource = document.getElementById('edit-source');
+const renameButton = document.getElementById('rename-note');
+const deleteButton = document.getElementById('delete-note');
 const main = document.querySelector('main');
 const editorContainer = document.getElementById('milkdown-editor');
 
@@ -32,13 +37,102 @@ function updateEditButton() {
             editToggle.style.display = 'none';
         }
     }
+    if (editSource) {
+        const editPath = getEditPath();
+        editSource.style.display = editPath && !isEditing ? 'block' : 'none';
+        editSource.href = editPath ? `${paraBase()}/edit${editPath}` : '#';
+    }
+    if (renameButton) {
+        renameButton.style.display = getEditPath() && !isEditing ? 'block' : 'none';
+    }
+    if (deleteButton) {
+        deleteButton.style.display = getEditPath() && !isEditing ? 'block' : 'none';
+    }
 }
 
-// Fetch raw markdown content
+// Fetch raw markdown content along with its version
 async function fetchRawContent(path) {
-    const response = await fetch(`/raw${path}`);
+    const response = await fetch(`${paraBase()}/raw${path}`);
     if (!response.ok) throw new Error('Failed to fetch content');
-    return response.text();
+    const version = (response.headers.get('ETag') || '').replace(/"/g, '') || null;
+    return { content: await response.text(), version };
+}
+
+// Line diff of two texts as [op, line] pairs, op being ' ', '-' or '+'
+function diffLines(a, b) {
+    const x = a.split('\n');
+    const y = b.split('\n');
+    const lcs = Array.from({ length: x.length + 1 }, () => new Array(y.length + 1).fill(0));
+    for (let i = x.length - 1; i >= 0; i--) {
+        for (let j = y.length - 1; j >= 0; j--) {
+            lcs[i][j] = x[i] === y[j] ? lcs[i + 1][j + 1] + 1 : Math.max(lcs[i + 1][j], lcs[i][j + 1]);
+        }
+    }
+    const out = [];
+    let i = 0, j = 0;
+    while (i < x.length && j < y.length) {
+        if (x[i] === y[j]) {
+            out.push([' ', x[i++]]);
+            j++;
+        } else if (lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(['-', x[i++]]);
+        } else {
+            out.push(['+', y[j++]]);
+        }
+    }
+    while (i < x.length) out.push(['-', x[i++]]);
+    while (j < y.length) out.push(['+', y[j++]]);
+    return out;
+}
+
+// Show what changed on disk against the editor's copy and let the user pick.
+// Resolves to true once the note has been saved.
+function showConflict(mine, theirs, version) {
+    return new Promise((resolve) => {
+        document.querySelector('.merge-view')?.remove();
+        const view = document.createElement('div');
+        view.className = 'merge-view';
+
+        const heading = document.createElement('p');
+        heading.textContent = 'This note changed on disk while you were editing. Lines marked - are only in the file, + only in your edit.';
+
+        const diff = document.createElement('pre');
+        diff.className = 'merge-diff';
+        for (const [op, line] of diffLines(theirs, mine)) {
+            const row = document.createElement('div');
+            row.className = op === '-' ? 'theirs' : op === '+' ? 'mine' : '';
+            row.textContent = `${op} ${line}`;
+            diff.appendChild(row);
+        }
+
+        const actions = document.createElement('div');
+        actions.className = 'merge-actions';
+        const button = (label, onClick) => {
+            const b = document.createElement('button');
+            b.textContent = label;
+            b.addEventListener('click', onClick);
+            actions.appendChild(b);
+        };
+        button('Keep mine', async () => {
+            view.remove();
+            baseVersion = version;
+            resolve(await saveContent());
+        });
+        button('Use file', () => {
+            view.remove();
+            isEditing = false;
+            location.reload();
+        });
+        button('Keep editing', () => {
+            // Merge by hand; the next save replaces the version shown here
+            view.remove();
+            baseVersion = version;
+            resolve(false);
+        });
+
+        view.append(heading, diff, actions);
+        document.body.appendChild(view);
+    });
 }
 
 // Clean up markdown output to match original formatting
@@ -57,17 +151,21 @@ async function saveContent() {
 
     const rawContent = crepe.getMarkdown();
     const content = cleanMarkdown(rawContent);
-    const response = await fetch('/save', {
+    const response = await fetch(paraBase() + '/save', {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ path: currentPath, content })
+        body: JSON.stringify({ path: currentPath, content, version: baseVersion })
     });
 
     const result = await response.json();
+    if (result.conflict !== undefined) {
+        return showConflict(content, result.conflict, result.version);
+    }
     if (!result.success) {
         alert('Save failed: ' + (result.error || 'Unknown error'));
         return false;
     }
+    baseVersion = result.version || null;
     return true;
 }
 
@@ -87,7 +185,8 @@ async function enterEditMode() {
     }
 
     try {
-        const content = await fetchRawContent(currentPath);
+        const { content, version } = await fetchRawContent(currentPath);
+        baseVersion = version;
 
         // Clear previous editor
         if (crepe) {
@@ -150,10 +249,50 @@ async function toggleEditMode() {
     }
 }
 
+// Rename or move the current note; links to it are updated on the server
+async function renameNote() {
+    const path = getEditPath();
+    if (!path) return;
+    const to = prompt('Move to:', path.slice(1));
+    if (!to || to === path.slice(1)) return;
+
+    const response = await fetch(paraBase() + '/rename', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ from: path, to })
+    });
+    const result = await response.json();
+    if (!result.success) {
+        alert('Rename failed: ' + (result.error || 'Unknown error'));
+        return;
+    }
+    const target = to.endsWith('.md') ? to : `${to}.md`;
+    location.href = paraBase() + '/' + target.replace(/^\/+/, '').split('/').map(encodeURIComponent).join('/');
+}
+
+// Move the current note to the trash and go to its folder
+async function deleteNote() {
+    const path = getEditPath();
+    if (!path || !confirm(`Move ${path} to the trash?`)) return;
+
+    const response = await fetch(paraBase() + path.split('/').map(encodeURIComponent).join('/'), { method: 'DELETE' });
+    if (!response.ok) {
+        alert('Delete failed: ' + response.statusText);
+        return;
+    }
+    location.href = paraBase() + path.slice(0, path.lastIndexOf('/') + 1);
+}
+
 // Event listeners
 if (editToggle) {
     editToggle.addEventListener('click', toggleEditMode);
 }
+if (renameButton) {
+    renameButton.addEventListener('click', renameNote);
+}
+if (deleteButton) {
+    deleteButton.addEventListener('click', deleteNote);
+}
 
 // Keyboard shortcut: Cmd/Ctrl+S to save
 document.addEventListener('keydown', async (e) => {
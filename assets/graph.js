@@ -0,0 +1,197 @@
+// Force-directed view of the vault's link graph, drawn into `.link-graph`
+// elements from the JSON at their `data-src`.
+(function() {
+    const SVG_NS = 'http://www.w3.org/2000/svg';
+    const REPULSION = 400;
+    const SPRING = 0.02;
+    const LINK_LENGTH = 40;
+    const GRAVITY = 0.01;
+    const DAMPING = 0.85;
+    const COOLING = 0.99;
+    const MIN_ALPHA = 0.005;
+
+    function el(name, attrs) {
+        const node = document.createElementNS(SVG_NS, name);
+        for (const key in attrs) node.setAttribute(key, attrs[key]);
+        return node;
+    }
+
+    function radius(node) {
+        return 3 + Math.sqrt(node.degree) * 1.5;
+    }
+
+    function draw(container, graph) {
+        const width = container.clientWidth || 800;
+        const height = container.clientHeight || 600;
+        const svg = el('svg', { viewBox: `${-width / 2} ${-height / 2} ${width} ${height}` });
+        const view = el('g', {});
+        const edgeLayer = el('g', { class: 'edges' });
+        const nodeLayer = el('g', { class: 'nodes' });
+        view.append(edgeLayer, nodeLayer);
+        svg.appendChild(view);
+        container.replaceChildren(svg);
+
+        // Start on a circle so the layout is the same on every load
+        const byId = new Map();
+        graph.nodes.forEach((node, i) => {
+            const angle = i * 2.399963;
+            const r = 10 * Math.sqrt(i + 1);
+            node.x = r * Math.cos(angle);
+            node.y = r * Math.sin(angle);
+            node.vx = 0;
+            node.vy = 0;
+            byId.set(node.id, node);
+        });
+        const edges = graph.edges
+            .map(e => ({ source: byId.get(e.source), target: byId.get(e.target) }))
+            .filter(e => e.source && e.target);
+
+        edges.forEach(edge => {
+            edge.line = el('line', {});
+            edgeLayer.appendChild(edge.line);
+        });
+        graph.nodes.forEach(node => {
+            const group = el('g', {
+                class: `node ${node.category || 'other'}${node.degree === 0 ? ' orphan' : ''}`,
+            });
+            const title = el('title', {});
+            title.textContent = node.id;
+            const label = el('text', { x: radius(node) + 2, y: 3 });
+            label.textContent = node.name;
+            group.append(el('circle', { r: radius(node) }), title, label);
+            node.group = group;
+            nodeLayer.appendChild(group);
+        });
+
+        let alpha = 1;
+        let dragging = null;
+        let running = false;
+
+        function tick() {
+            const nodes = graph.nodes;
+            for (let i = 0; i < nodes.length; i++) {
+                const a = nodes[i];
+                for (let j = i + 1; j < nodes.length; j++) {
+                    const b = nodes[j];
+                    let dx = a.x - b.x;
+                    let dy = a.y - b.y;
+                    let d2 = dx * dx + dy * dy;
+                    if (d2 === 0) { dx = Math.random() - 0.5; dy = Math.random() - 0.5; d2 = 0.5; }
+                    const f = REPULSION * alpha / d2;
+                    a.vx += dx * f; a.vy += dy * f;
+                    b.vx -= dx * f; b.vy -= dy * f;
+                }
+                a.vx -= a.x * GRAVITY * alpha;
+                a.vy -= a.y * GRAVITY * alpha;
+            }
+            edges.forEach(({ source, target }) => {
+                const dx = target.x - source.x;
+                const dy = target.y - source.y;
+                const d = Math.sqrt(dx * dx + dy * dy) || 1;
+                const f = (d - LINK_LENGTH) * SPRING * alpha / d;
+                source.vx += dx * f; source.vy += dy * f;
+                target.vx -= dx * f; target.vy -= dy * f;
+            });
+            nodes.forEach(node => {
+                if (node === dragging) { node.vx = node.vy = 0; return; }
+                node.vx *= DAMPING;
+                node.vy *= DAMPING;
+                node.x += node.vx;
+                node.y += node.vy;
+            });
+        }
+
+        function render() {
+            edges.forEach(({ line, source, target }) => {
+                line.setAttribute('x1', source.x);
+                line.setAttribute('y1', source.y);
+                line.setAttribute('x2', target.x);
+                line.setAttribute('y2', target.y);
+            });
+            graph.nodes.forEach(node => {
+                node.group.setAttribute('transform', `translate(${node.x},${node.y})`);
+            });
+        }
+
+        function frame() {
+            if (!svg.isConnected || alpha < MIN_ALPHA) {
+                running = false;
+                return;
+            }
+            tick();
+            render();
+            alpha *= COOLING;
+            requestAnimationFrame(frame);
+        }
+
+        function reheat() {
+            alpha = Math.max(alpha, 0.3);
+            if (!running) {
+                running = true;
+                requestAnimationFrame(frame);
+            }
+        }
+
+        // Pan by dragging the background, zoom with the wheel
+        let scale = 1, panX = 0, panY = 0, panning = null;
+        function transform() {
+            view.setAttribute('transform', `translate(${panX},${panY}) scale(${scale})`);
+        }
+        function toGraph(event) {
+            const box = svg.getBoundingClientRect();
+            const x = (event.clientX - box.left) * width / box.width - width / 2;
+            const y = (event.clientY - box.top) * height / box.height - height / 2;
+            return { x: (x - panX) / scale, y: (y - panY) / scale };
+        }
+        svg.addEventListener('wheel', e => {
+            e.preventDefault();
+            scale = Math.min(8, Math.max(0.1, scale * (e.deltaY < 0 ? 1.1 : 1 / 1.1)));
+            transform();
+        }, { passive: false });
+        svg.addEventListener('pointerdown', e => {
+            const group = e.target.closest('.node');
+            const node = group && graph.nodes.find(n => n.group === group);
+            if (node) {
+                dragging = node;
+                dragging.moved = false;
+            } else {
+                panning = { x: e.clientX - panX, y: e.clientY - panY };
+            }
+            svg.setPointerCapture(e.pointerId);
+        });
+        svg.addEventListener('pointermove', e => {
+            if (dragging) {
+                Object.assign(dragging, toGraph(e));
+                dragging.moved = true;
+                reheat();
+            } else if (panning) {
+                panX = e.clientX - panning.x;
+                panY = e.clientY - panning.y;
+                transform();
+            }
+        });
+        svg.addEventListener('pointerup', () => {
+            // A click without a drag opens the note
+            if (dragging && !dragging.moved) {
+                const href = paraBase() + '/' + dragging.id.split('/').map(encodeURIComponent).join('/');
+                htmx.ajax('GET', href, { target: 'main' }).then(() => {
+                    history.pushState({ htmx: true }, '', href);
+                });
+            }
+            dragging = null;
+            panning = null;
+        });
+
+        render();
+        reheat();
+        alpha = 1;
+    }
+
+    window.renderGraph = function(root) {
+        root.querySelectorAll('.link-graph[data-src]').forEach(container => {
+            fetch(container.dataset.src)
+                .then(r => r.json())
+                .then(graph => draw(container, graph));
+        });
+    };
+})();
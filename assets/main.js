@@ -1,3 +1,10 @@
+// Where this vault is served from: '' normally, `/name` when vaults are
+// mounted side by side, behind `--base-path` if one is set. Prefix it to
+// the server's own routes.
+function paraBase() {
+    return document.documentElement.dataset.base || '';
+}
+
 // Keyboard shortcuts
 const isMac = navigator.platform.toUpperCase().indexOf('MAC') >= 0;
 const searchInput = document.querySelector('.search-form input[name="q"]');
@@ -19,6 +26,76 @@ document.addEventListener('keydown', (e) => {
     }
 });
 
+// Quick switcher: Cmd+P (Mac) or Ctrl+P (Windows/Linux) to jump to a note
+(function() {
+    const dialog = document.querySelector('.quick-switcher');
+    if (!dialog) return;
+    const input = dialog.querySelector('input');
+    const list = dialog.querySelector('ul');
+    let pending = null;
+
+    function items() {
+        return [...list.querySelectorAll('li')];
+    }
+
+    function select(index) {
+        const all = items();
+        if (!all.length) return;
+        const current = all.findIndex(li => li.classList.contains('selected'));
+        all.forEach(li => li.classList.remove('selected'));
+        const next = all[(index(current) + all.length) % all.length];
+        next.classList.add('selected');
+        next.scrollIntoView({ block: 'nearest' });
+    }
+
+    async function update() {
+        const query = input.value;
+        if (pending) pending.abort();
+        pending = new AbortController();
+        try {
+            const res = await fetch(`${paraBase()}/quickswitch?q=${encodeURIComponent(query)}`, { signal: pending.signal });
+            list.innerHTML = await res.text();
+        } catch (err) {
+            if (err.name !== 'AbortError') console.error('Quick switcher failed:', err);
+        }
+    }
+
+    document.addEventListener('keydown', (e) => {
+        if ((e.metaKey || e.ctrlKey) && e.key === 'p') {
+            e.preventDefault();
+            input.value = '';
+            list.innerHTML = '';
+            dialog.showModal();
+            input.focus();
+        }
+    });
+
+    input.addEventListener('input', update);
+
+    input.addEventListener('keydown', (e) => {
+        if (e.key === 'ArrowDown') {
+            e.preventDefault();
+            select(i => i + 1);
+        } else if (e.key === 'ArrowUp') {
+            e.preventDefault();
+            select(i => i - 1);
+        } else if (e.key === 'Enter') {
+            e.preventDefault();
+            const link = list.querySelector('li.selected a');
+            if (link) link.click();
+        }
+    });
+
+    list.addEventListener('click', (e) => {
+        if (e.target.closest('a')) dialog.close();
+    });
+
+    // Close when clicking the backdrop
+    dialog.addEventListener('click', (e) => {
+        if (e.target === dialog) dialog.close();
+    });
+})();
+
 // Sidebar resize
 const handle = document.querySelector('.resize-handle');
 const sidebar = document.querySelector('.sidebar');
@@ -72,7 +149,7 @@ document.querySelectorAll('.file-tree li.dir').forEach(li => {
 
 // Copy buttons for code blocks
 function addCopyButtons(container) {
-    container.querySelectorAll('pre:not(.has-copy-btn)').forEach(pre => {
+    container.querySelectorAll('pre:not(.has-copy-btn):not(.line-numbers)').forEach(pre => {
         pre.classList.add('has-copy-btn');
         const button = document.createElement('button');
         button.className = 'copy-button';
@@ -91,10 +168,37 @@ function addCopyButtons(container) {
 
 addCopyButtons(document);
 
-// Mermaid diagrams
-mermaid.initialize({ startOnLoad: false, theme: 'neutral' });
+// Sort tables by a column when its header is clicked; again to reverse
+function makeSortable(root) {
+    root.querySelectorAll('table.sortable').forEach(table => {
+        const tbody = table.tBodies[0];
+        table.querySelectorAll('thead th').forEach((th, column) => {
+            th.addEventListener('click', () => {
+                const ascending = th.dataset.sort !== 'asc';
+                table.querySelectorAll('thead th').forEach(other => delete other.dataset.sort);
+                th.dataset.sort = ascending ? 'asc' : 'desc';
+                const rows = Array.from(tbody.rows).sort((a, b) => {
+                    const x = a.cells[column], y = b.cells[column];
+                    const order = x.classList.contains('num') && y.classList.contains('num')
+                        ? parseFloat(x.textContent.replace(/,/g, '')) - parseFloat(y.textContent.replace(/,/g, ''))
+                        : x.textContent.localeCompare(y.textContent, undefined, { numeric: true });
+                    return ascending ? order : -order;
+                });
+                tbody.append(...rows);
+            });
+        });
+    });
+}
+
+makeSortable(document);
+
+// Mermaid diagrams, unless the server draws them
+if (typeof mermaid !== 'undefined') {
+    mermaid.initialize({ startOnLoad: false, theme: 'neutral' });
+}
 
 async function renderMermaid(container) {
+    if (typeof mermaid === 'undefined') return;
     const nodes = [];
     container.querySelectorAll('pre > code.language-mermaid').forEach(code => {
         const pre = code.parentElement;
@@ -111,33 +215,108 @@ async function renderMermaid(container) {
 
 renderMermaid(document);
 
+// Scroll to the first search match in a note opened from search results
+function scrollToSearchHit(root) {
+    const hit = root.querySelector('mark.search-hit');
+    if (hit) {
+        hit.scrollIntoView({ block: 'center' });
+    }
+}
+
+scrollToSearchHit(document);
+renderGraph(document);
+
 // Re-add copy buttons and render mermaid after htmx swaps content
 document.body.addEventListener('htmx:afterSwap', (e) => {
     addCopyButtons(e.detail.target);
     renderMermaid(e.detail.target);
+    renderGraph(e.detail.target);
+    makeSortable(e.detail.target);
+    scrollToSearchHit(e.detail.target);
+});
+
+// Tick tasks off straight from the rendered note, or from /tasks, where
+// each checkbox names its note
+document.addEventListener('change', async (e) => {
+    const box = e.target;
+    if (!box.matches?.('input.task-toggle')) return;
+    const path = box.dataset.path || document.querySelector('main')?.dataset.editPath;
+    if (!path) return;
+    box.disabled = true;
+    try {
+        const response = await fetch(paraBase() + '/toggle-task', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ path, line: Number(box.dataset.line), version: box.dataset.version })
+        });
+        const result = await response.json();
+        if (!result.success) {
+            box.checked = !box.checked;
+            alert('Updating the task failed: ' + (result.error || 'Unknown error'));
+            return;
+        }
+        box.checked = result.done;
+        document.querySelectorAll('input.task-toggle').forEach(other => {
+            if ((other.dataset.path || path) === path) other.dataset.version = result.version;
+        });
+    } catch (err) {
+        box.checked = !box.checked;
+        alert('Updating the task failed: ' + err);
+    } finally {
+        box.disabled = false;
+    }
 });
 
+// Archive a finished project from its page
+document.addEventListener('click', async (e) => {
+    const button = e.target.closest?.('button.archive-project');
+    if (!button) return;
+    const path = button.dataset.path;
+    if (!confirm(`Move ${path} to the archive?`)) return;
+    button.disabled = true;
+    const response = await fetch(paraBase() + '/archive-project', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ path })
+    });
+    const result = await response.json();
+    if (!result.success) {
+        button.disabled = false;
+        alert('Archiving failed: ' + (result.error || 'Unknown error'));
+        return;
+    }
+    const href = paraBase() + '/' + result.path.split('/').map(encodeURIComponent).join('/');
+    location.href = result.path.endsWith('.md') ? href : href + '/';
+});
+
+// Drop link card icons that fail to load
+document.addEventListener('error', (e) => {
+    if (e.target.classList?.contains('link-card-icon')) e.target.remove();
+}, true);
+
 // Highlight current file in tree and update path display
 function highlightCurrentFile() {
     const currentPath = decodeURIComponent(location.pathname);
+    const base = paraBase();
 
     // Update breadcrumb in navbar
     const pathDisplay = document.querySelector('.current-path');
     if (pathDisplay) {
         pathDisplay.innerHTML = '';
-        const parts = currentPath.split('/').filter(p => p);
+        const inBase = base && currentPath.startsWith(base + '/');
+        const parts = (inBase ? currentPath.slice(base.length) : currentPath).split('/').filter(p => p);
 
         // Add root link
         const rootLink = document.createElement('a');
-        rootLink.href = '/';
+        rootLink.href = base + '/';
         rootLink.textContent = 'Notes';
-        rootLink.setAttribute('hx-get', '/');
+        rootLink.setAttribute('hx-get', base + '/');
         rootLink.setAttribute('hx-target', 'main');
         rootLink.setAttribute('hx-push-url', 'true');
         pathDisplay.appendChild(rootLink);
 
         // Add each path segment
-        let href = '';
+        let href = inBase ? base : '';
         parts.forEach((part, i) => {
             const sep = document.createElement('span');
             sep.textContent = ' / ';
@@ -199,13 +378,100 @@ highlightCurrentFile();
 // Re-highlight after htmx navigation
 document.body.addEventListener('htmx:afterSettle', highlightCurrentFile);
 
+// Plain-text editor at /edit/{path}
+document.addEventListener('submit', async (e) => {
+    const form = e.target.closest('form.raw-editor');
+    if (!form) return;
+    e.preventDefault();
+
+    const status = form.querySelector('.raw-editor-status');
+    const put = (version) => fetch(paraBase() + form.dataset.path, {
+        method: 'PUT',
+        headers: { 'Content-Type': 'text/markdown; charset=utf-8', 'If-Match': `"${version}"` },
+        body: form.elements.content.value
+    });
+
+    status.textContent = 'Saving...';
+    let response = await put(form.dataset.version);
+    let result = await response.json();
+    if (response.status === 412 && confirm('This note changed on disk since you opened it. Overwrite those changes?')) {
+        response = await put(result.version);
+        result = await response.json();
+    }
+    if (!result.success) {
+        status.textContent = 'Save failed: ' + (result.error || 'Unknown error');
+        return;
+    }
+    location.href = paraBase() + form.dataset.path;
+});
+
+// Pasting an image into the plain-text editor uploads it and inserts the embed
+document.addEventListener('paste', async (e) => {
+    const textarea = e.target.closest('form.raw-editor textarea');
+    if (!textarea) return;
+    const image = Array.from(e.clipboardData?.files || []).find(f => f.type.startsWith('image/'));
+    if (!image) return;
+    e.preventDefault();
+
+    const form = textarea.form;
+    const note = decodeURIComponent(form.dataset.path);
+    const status = form.querySelector('.raw-editor-status');
+    status.textContent = 'Uploading image...';
+    const response = await fetch(`${paraBase()}/paste-image?note=${encodeURIComponent(note)}`, {
+        method: 'POST',
+        headers: { 'Content-Type': image.type },
+        body: image
+    });
+    const result = await response.json();
+    if (!result.success) {
+        status.textContent = 'Upload failed: ' + (result.error || 'Unknown error');
+        return;
+    }
+    status.textContent = '';
+    textarea.setRangeText(result.markdown, textarea.selectionStart, textarea.selectionEnd, 'end');
+});
+
+// Open the plain-text editor at the line in the URL, like /edit/note.md#L12
+(function() {
+    const textarea = document.querySelector('form.raw-editor textarea');
+    const match = location.hash.match(/^#L(\d+)$/);
+    if (!textarea || !match) return;
+    const lines = textarea.value.split('\n');
+    const line = Math.max(1, Math.min(Number(match[1]), lines.length));
+    const start = lines.slice(0, line - 1).reduce((sum, text) => sum + text.length + 1, 0);
+    textarea.focus();
+    textarea.setSelectionRange(start, start + lines[line - 1].length);
+    const lineHeight = parseFloat(getComputedStyle(textarea).lineHeight) || 20;
+    textarea.scrollTop = Math.max(0, (line - 3) * lineHeight);
+})();
+
+// Ctrl/Cmd+S saves the plain-text editor
+document.addEventListener('keydown', (e) => {
+    const form = document.querySelector('form.raw-editor');
+    if (form && (e.metaKey || e.ctrlKey) && e.key === 's') {
+        e.preventDefault();
+        form.requestSubmit();
+    }
+});
+
+// Vault switcher
+(function() {
+    const select = document.querySelector('.vault-switcher');
+    if (!select) return;
+    select.addEventListener('change', () => {
+        location.href = paraBase() + '/switch-vault/' + encodeURIComponent(select.value);
+    });
+})();
+
 // Live reload
 (function() {
+    // Pages from `para export --format html` have no server to listen to
+    if (document.documentElement.hasAttribute('data-static')) return;
     let reconnectDelay = 1000;
 
     function connect() {
         const protocol = location.protocol === 'https:' ? 'wss:' : 'ws:';
-        const ws = new WebSocket(`${protocol}//${location.host}/ws`);
+        const ws = new WebSocket(`${protocol}//${location.host}${paraBase()}/ws`);
 
         ws.onopen = () => {
             console.log('[para] Live reload connected');
@@ -216,8 +482,14 @@ document.body.addEventListener('htmx:afterSettle', highlightCurrentFile);
             try {
                 const data = JSON.parse(e.data);
                 if (data.type === 'reload') {
+                    // Hold the reload while a move banner waits for an answer
+                    if (document.querySelector('.move-banner')) return;
+                    // Don't throw away an open edit; saving will catch the conflict
+                    if (document.querySelector('main.editing, form.raw-editor')) return;
                     console.log('[para] Reloading:', data.path);
                     location.reload();
+                } else if (data.type === 'moved') {
+                    showMoveBanner(data);
                 }
             } catch (err) {
                 console.error('[para] Parse error:', err);
@@ -232,6 +504,39 @@ document.body.addEventListener('htmx:afterSettle', highlightCurrentFile);
         ws.onerror = () => ws.close();
     }
 
+    // Offer to update links after a note was moved outside para
+    function showMoveBanner(data) {
+        document.querySelector('.move-banner')?.remove();
+        const banner = document.createElement('div');
+        banner.className = 'move-banner';
+        const text = document.createElement('span');
+        const notes = data.links === 1 ? '1 note links' : `${data.links} notes link`;
+        text.textContent = `${data.from} moved to ${data.to}. ${notes} to the old location.`;
+        const update = document.createElement('button');
+        update.textContent = 'Update links';
+        update.addEventListener('click', async () => {
+            const response = await fetch(paraBase() + '/relink', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ from: data.from, to: data.to })
+            });
+            const result = await response.json();
+            if (!result.success) {
+                alert('Updating links failed: ' + (result.error || 'Unknown error'));
+            }
+            banner.remove();
+            location.reload();
+        });
+        const dismiss = document.createElement('button');
+        dismiss.textContent = 'Dismiss';
+        dismiss.addEventListener('click', () => {
+            banner.remove();
+            location.reload();
+        });
+        banner.append(text, update, dismiss);
+        document.body.appendChild(banner);
+    }
+
     connect();
 })();
 
@@ -325,7 +630,9 @@ document.body.addEventListener('htmx:afterSettle', highlightCurrentFile);
     // Clear input after search is submitted
     const searchForm = document.querySelector('.search-form');
     if (searchForm) {
-        searchForm.addEventListener('htmx:afterRequest', () => {
+        searchForm.addEventListener('htmx:afterRequest', (e) => {
+            // Suggestion requests come from the input and bubble up here
+            if (e.detail.elt !== searchForm) return;
             searchInput.value = '';
             filterTree('');
         });
@@ -333,7 +640,48 @@ document.body.addEventListener('htmx:afterSettle', highlightCurrentFile);
             setTimeout(() => {
                 searchInput.value = '';
                 filterTree('');
+                clearSuggestions();
             }, 0);
         });
     }
+
+    // Suggestions dropdown, filled by htmx as the user types
+    const suggestions = document.querySelector('.search-suggestions');
+
+    function clearSuggestions() {
+        if (suggestions) suggestions.innerHTML = '';
+    }
+
+    if (suggestions) {
+        suggestions.addEventListener('click', (e) => {
+            if (e.target.closest('a')) {
+                searchInput.value = '';
+                filterTree('');
+                clearSuggestions();
+            }
+        });
+
+        searchForm.addEventListener('keydown', (e) => {
+            const links = [...suggestions.querySelectorAll('a')];
+            const current = links.indexOf(document.activeElement);
+            if (e.key === 'Escape') {
+                clearSuggestions();
+                searchInput.focus();
+            } else if (e.key === 'ArrowDown' && links.length) {
+                e.preventDefault();
+                links[Math.min(current + 1, links.length - 1)].focus();
+            } else if (e.key === 'ArrowUp' && current >= 0) {
+                e.preventDefault();
+                if (current === 0) searchInput.focus();
+                else links[current - 1].focus();
+            }
+        });
+
+        // Close when focus leaves the search box and its suggestions
+        searchForm.addEventListener('focusout', () => {
+            setTimeout(() => {
+                if (!searchForm.contains(document.activeElement)) clearSuggestions();
+            }, 100);
+        });
+    }
 })();
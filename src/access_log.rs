@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::fs::File;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A file `para serve --access-log` appends a line to for every request, in
+/// the Combined Log Format web servers use, so tools like fail2ban and
+/// GoAccess can read it.
+pub struct AccessLog {
+    file: Mutex<File>,
+}
+
+/// What one line of the log records.
+pub struct Entry<'a> {
+    pub client: Option<IpAddr>,
+    /// The signed-in user, if any.
+    pub user: Option<&'a str>,
+    pub time: DateTime<Local>,
+    /// `GET /path?query HTTP/1.1`.
+    pub request: &'a str,
+    pub status: u16,
+    /// Body size, when known before it's sent.
+    pub bytes: Option<u64>,
+    pub referer: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+}
+
+impl AccessLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open access log {}", path.display()))?;
+        Ok(AccessLog { file: Mutex::new(file) })
+    }
+
+    pub fn write(&self, entry: &Entry) {
+        let line = format!(
+            "{} - {} [{}] \"{}\" {} {} \"{}\" \"{}\"\n",
+            entry.client.map_or("-".to_string(), |ip| ip.to_string()),
+            entry.user.map_or("-".to_string(), field),
+            entry.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            quoted(entry.request),
+            entry.status,
+            entry.bytes.map_or("-".to_string(), |bytes| bytes.to_string()),
+            entry.referer.map_or("-".to_string(), quoted),
+            entry.user_agent.map_or("-".to_string(), quoted),
+        );
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            tracing::error!("Failed to write access log: {e}");
+        }
+    }
+}
+
+/// A value for inside `"..."`, with quotes and control characters escaped so
+/// a request can't forge a line of its own.
+fn quoted(value: &str) -> String {
+    value.escape_default().to_string()
+}
+
+/// A bare value, which mustn't contain spaces.
+fn field(value: &str) -> String {
+    quoted(value).replace(' ', "\\x20")
+}
@@ -0,0 +1,81 @@
+use chrono::{DateTime, Local, NaiveDate};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::journal;
+use crate::vault;
+
+#[derive(Default, Clone, Copy)]
+pub struct Day {
+    /// A daily note exists for this day.
+    pub journaled: bool,
+    /// Notes last modified on this day.
+    pub edits: usize,
+}
+
+pub struct Activity {
+    pub days: BTreeMap<NaiveDate, Day>,
+}
+
+impl Activity {
+    /// Daily notes and note modification times across the vault.
+    pub fn collect(notes_dir: &Path, config: &Config) -> Activity {
+        let mut days: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+        for path in vault::files(notes_dir, config) {
+            if path.extension().is_none_or(|e| e != "md") {
+                continue;
+            }
+            let relative = vault::relative(notes_dir, &path);
+            if let Some(date) = journal::date_of(&config.journal, &relative) {
+                days.entry(date).or_default().journaled = true;
+            }
+            if let Ok(modified) = path.metadata().and_then(|m| m.modified()) {
+                let date = DateTime::<Local>::from(modified).date_naive();
+                days.entry(date).or_default().edits += 1;
+            }
+        }
+        Activity { days }
+    }
+
+    pub fn has_journal(&self) -> bool {
+        self.days.values().any(|d| d.journaled)
+    }
+
+    pub fn day(&self, date: NaiveDate) -> Day {
+        self.days.get(&date).copied().unwrap_or_default()
+    }
+
+    /// Consecutive journaled days ending today, or yesterday if today's
+    /// note isn't written yet.
+    pub fn current_streak(&self, today: NaiveDate) -> usize {
+        let mut day = today;
+        if !self.day(day).journaled {
+            day = day.pred_opt().unwrap_or(day);
+        }
+        let mut streak = 0;
+        while self.day(day).journaled {
+            streak += 1;
+            let Some(previous) = day.pred_opt() else {
+                break;
+            };
+            day = previous;
+        }
+        streak
+    }
+
+    pub fn longest_streak(&self) -> usize {
+        let mut longest = 0;
+        let mut run = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for &date in self.days.iter().filter(|(_, d)| d.journaled).map(|(date, _)| date) {
+            run = match previous {
+                Some(p) if p.succ_opt() == Some(date) => run + 1,
+                _ => 1,
+            };
+            longest = longest.max(run);
+            previous = Some(date);
+        }
+        longest
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use serde_yaml_ng::Value;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::frontmatter;
+use crate::links;
+use crate::para::{self, Category};
+use crate::vault;
+
+pub const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// A project (folder or single note) that lives under the archive category.
+pub struct ArchivedItem {
+    pub name: String,
+    /// Path relative to the notes root, with a trailing `/` for folders.
+    pub path: String,
+    pub year: Option<u32>,
+    pub month: Option<u32>,
+}
+
+/// Collect archived items, dated by an `archived:` frontmatter key on the
+/// note (or the folder's README.md) or, failing that, by `YYYY/` and `YYYY/MM/`
+/// folders inside the archive. Sorted newest first with undated items last.
+pub fn collect(notes_dir: &Path) -> Vec<ArchivedItem> {
+    let Some(archive_dir) = para::category_dir(notes_dir, Category::Archive) else {
+        return Vec::new();
+    };
+
+    let mut items = Vec::new();
+    collect_dir(notes_dir, &archive_dir, None, None, &mut items);
+
+    items.sort_by(|a, b| {
+        let key = |i: &ArchivedItem| (i.year.is_none(), std::cmp::Reverse((i.year, i.month)));
+        key(a).cmp(&key(b)).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    items
+}
+
+fn collect_dir(
+    notes_dir: &Path,
+    dir: &Path,
+    year: Option<u32>,
+    month: Option<u32>,
+    items: &mut Vec<ArchivedItem>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name.starts_with('_') {
+            continue;
+        }
+        let path = entry.path();
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+
+        // Date folders group their contents rather than being projects themselves
+        if is_dir && year.is_none() && is_year(&name) {
+            collect_dir(notes_dir, &path, name.parse().ok(), None, items);
+            continue;
+        }
+        if is_dir && year.is_some() && month.is_none() && is_month(&name) {
+            collect_dir(notes_dir, &path, year, name.parse().ok(), items);
+            continue;
+        }
+
+        let note = if is_dir {
+            path.join("README.md")
+        } else if name.ends_with(".md") {
+            path.clone()
+        } else {
+            continue;
+        };
+
+        let (year, month) = frontmatter::read(&note)
+            .get_str("archived")
+            .and_then(|date| parse_year_month(&date))
+            .map(|(y, m)| (Some(y), Some(m)))
+            .unwrap_or((year, month));
+
+        let relative = path.strip_prefix(notes_dir).unwrap_or(&path).to_string_lossy();
+        items.push(ArchivedItem {
+            name: if is_dir {
+                name
+            } else {
+                name.trim_end_matches(".md").to_string()
+            },
+            path: if is_dir {
+                format!("{relative}/")
+            } else {
+                relative.to_string()
+            },
+            year,
+            month,
+        });
+    }
+}
+
+/// Where archiving a project moves it.
+pub struct Archiving {
+    /// The project, relative to the notes root.
+    pub from: String,
+    /// Its place in the archive, under a folder for the year.
+    pub to: String,
+    /// The note that gets the `archived:` date once moved: the project
+    /// itself, or the folder's README.md.
+    pub dated: String,
+}
+
+/// Whether `relative` is a project: a folder or note directly under the
+/// projects category.
+pub fn is_project(relative: &str) -> bool {
+    let relative = relative.trim_matches('/');
+    Category::of(relative) == Some(Category::Projects)
+        && relative
+            .split_once('/')
+            .is_some_and(|(_, name)| !name.contains('/') && !name.starts_with(['.', '_']) && name != "README.md")
+}
+
+/// Work out where the project at `project` (a folder or note directly
+/// under the projects category) goes when archived on `date`.
+pub fn plan(notes_dir: &Path, project: &str, date: NaiveDate) -> Result<Archiving> {
+    let from = project.trim_matches('/').to_string();
+    let source = notes_dir.join(&from);
+    if !source.exists() {
+        bail!("{from} doesn't exist");
+    }
+    if !is_project(&from) {
+        bail!("{from} isn't a project");
+    }
+    let name = from.rsplit('/').next().unwrap_or(&from);
+
+    let archive_dir = para::category_dir(notes_dir, Category::Archive)
+        .map(|dir| vault::relative(notes_dir, &dir))
+        .unwrap_or_else(|| "4 Archive".to_string());
+    let to = format!("{archive_dir}/{}/{name}", date.format("%Y"));
+    if notes_dir.join(&to).exists() {
+        bail!("{to} already exists");
+    }
+    let dated = if source.is_dir() {
+        format!("{to}/README.md")
+    } else {
+        to.clone()
+    };
+    Ok(Archiving { from, to, dated })
+}
+
+/// Move a project into the archive, date it, and point links at its new
+/// place, in the notes `config`'s viewer can write. Returns the notes whose
+/// links were updated.
+pub fn apply(notes_dir: &Path, config: &Config, archiving: &Archiving, date: NaiveDate) -> Result<Vec<String>> {
+    let target = notes_dir.join(&archiving.to);
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::rename(notes_dir.join(&archiving.from), &target)
+        .with_context(|| format!("failed to move {} to {}", archiving.from, archiving.to))?;
+
+    let dated = notes_dir.join(&archiving.dated);
+    let content = std::fs::read_to_string(&dated).unwrap_or_else(|_| {
+        // A folder without a README gets one to hold the date
+        let name = archiving.to.rsplit('/').next().unwrap_or(&archiving.to);
+        format!("# {name}\n")
+    });
+    let (mut meta, body) = frontmatter::parse(&content);
+    meta.set("archived", Value::String(date.format("%Y-%m-%d").to_string()));
+    std::fs::write(&dated, format!("{}{body}", meta.to_block()))
+        .with_context(|| format!("failed to write {}", archiving.dated))?;
+
+    let moves = links::expand_move(notes_dir, config, &archiving.from, &archiving.to);
+    links::rewrite(notes_dir, config, &moves, true)
+}
+
+/// Archive a project from the command line, or with `apply` unset, print
+/// what would change.
+pub fn run(notes_dir: &Path, config: &Config, project: &str, apply: bool) -> Result<()> {
+    let today = chrono::Local::now().date_naive();
+    let archiving = plan(notes_dir, project, today)?;
+    if !apply {
+        // Links as they'd be rewritten, reckoned before anything moves
+        let moves = planned_moves(notes_dir, config, &archiving);
+        let relinked = links::rewrite(notes_dir, config, &moves, false)?;
+        println!("move {} -> {}", archiving.from, archiving.to);
+        println!("date {} archived: {}", archiving.dated, today.format("%Y-%m-%d"));
+        for path in &relinked {
+            println!("relink {path}");
+        }
+        println!("(dry run; pass --apply to write)");
+        return Ok(());
+    }
+
+    let relinked = self::apply(notes_dir, config, &archiving, today)?;
+    println!(
+        "Archived {} as {}, updated links in {} note(s)",
+        archiving.from,
+        archiving.to,
+        relinked.len()
+    );
+    Ok(())
+}
+
+/// The `(old, new)` paths of every note the move will carry.
+fn planned_moves(notes_dir: &Path, config: &Config, archiving: &Archiving) -> Vec<(String, String)> {
+    if !notes_dir.join(&archiving.from).is_dir() {
+        return vec![(archiving.from.clone(), archiving.to.clone())];
+    }
+    let prefix = format!("{}/", archiving.from);
+    vault::files(notes_dir, config)
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .filter_map(|p| {
+            let old = vault::relative(notes_dir, p);
+            let suffix = old.strip_prefix(&prefix)?;
+            Some((old.clone(), format!("{}/{suffix}", archiving.to)))
+        })
+        .collect()
+}
+
+fn is_year(name: &str) -> bool {
+    name.len() == 4 && name.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_month(name: &str) -> bool {
+    name.len() == 2 && name.parse::<u32>().is_ok_and(|m| (1..=12).contains(&m))
+}
+
+/// Parse the year and month from a `YYYY-MM...` date.
+fn parse_year_month(date: &str) -> Option<(u32, u32)> {
+    let year = date.get(0..4)?.parse().ok()?;
+    let month: u32 = date.get(5..7)?.parse().ok()?;
+    (1..=12).contains(&month).then_some((year, month))
+}
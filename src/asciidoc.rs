@@ -0,0 +1,39 @@
+use asciidork_core::{JobAttr, JobSettings, SafeMode};
+use asciidork_parser::parser::SourceFile;
+use asciidork_parser::prelude::{Bump, Parser};
+
+use crate::render::Renderer;
+use crate::serve::html_escape;
+
+/// AsciiDoc (`.adoc`) documents, converted the way Asciidoctor would.
+pub struct AsciiDoc;
+
+impl Renderer for AsciiDoc {
+    fn render(&self, _name: &str, content: &str) -> String {
+        to_html(content).unwrap_or_else(|| {
+            format!(
+                "<p>This file couldn't be read as AsciiDoc.</p>\n<pre>{}</pre>\n",
+                html_escape(content)
+            )
+        })
+    }
+}
+
+/// Convert a document to an HTML fragment, with its title as an `<h1>`.
+/// Includes are refused, and cross references point at other `.adoc` files
+/// in the vault rather than their converted `.html`.
+fn to_html(content: &str) -> Option<String> {
+    let mut settings = JobSettings::embedded();
+    settings.strict = false;
+    settings.safe_mode = SafeMode::Secure;
+    for (name, value) in [("relfilesuffix", ".adoc"), ("outfilesuffix", ".adoc")] {
+        settings.job_attrs.insert_unchecked(name, JobAttr::readonly(value.to_string()));
+    }
+    settings.job_attrs.insert_unchecked("showtitle", JobAttr::readonly(true));
+
+    let bump = Bump::new();
+    let mut parser = Parser::from_str(content, SourceFile::Tmp, &bump);
+    parser.apply_job_settings(settings);
+    let parsed = parser.parse().ok()?;
+    asciidork_dr_html_backend::convert(parsed.document).ok()
+}
@@ -0,0 +1,97 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::config::Config;
+use crate::vault;
+
+/// Targets of markdown links/images and wikilinks/embeds.
+static REFERENCE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\]\(\s*<?([^)>\s]+)|!?\[\[([^\]|#]+)").unwrap()
+});
+
+pub struct Attachment {
+    /// Path relative to the notes root.
+    pub path: String,
+    pub size: u64,
+    /// Notes (relative paths) that link to or embed this file.
+    pub referenced_by: Vec<String>,
+}
+
+/// Collect every non-markdown file in the vault along with the notes that
+/// reference it. References are matched by file name, so a link to
+/// `../img/a.png` and an embed `![[a.png]]` both count.
+pub fn collect(notes_dir: &Path, config: &Config) -> Vec<Attachment> {
+    let files = vault::files(notes_dir, config);
+
+    let mut references: HashMap<String, Vec<String>> = HashMap::new();
+    for note in files.iter().filter(|p| p.extension().is_some_and(|e| e == "md")) {
+        let Ok(content) = std::fs::read_to_string(note) else {
+            continue;
+        };
+        let note_path = vault::relative(notes_dir, note);
+        for caps in REFERENCE_RE.captures_iter(&content) {
+            let Some(target) = caps.get(1).or_else(|| caps.get(2)) else {
+                continue;
+            };
+            let target = percent_decode(target.as_str().trim());
+            let name = target.rsplit('/').next().unwrap_or(&target).to_lowercase();
+            let notes = references.entry(name).or_default();
+            if !notes.contains(&note_path) {
+                notes.push(note_path.clone());
+            }
+        }
+    }
+
+    files
+        .iter()
+        .filter(|p| p.extension().is_none_or(|e| e != "md"))
+        .map(|path| {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            Attachment {
+                path: vault::relative(notes_dir, path),
+                size: path.metadata().map(|m| m.len()).unwrap_or(0),
+                referenced_by: references.get(&name).cloned().unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = bytes.get(i + 1..i + 3)
+            && let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(hex), 16)
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Format a byte count for display, e.g. `1.4 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
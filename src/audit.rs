@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A change made through the server.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Create,
+    Edit,
+    /// Renamed or moved to another folder.
+    Move,
+    /// Links to a moved note were pointed at its new location.
+    Relink,
+    /// An attachment was uploaded.
+    Upload,
+    /// Moved into the trash.
+    Delete,
+    /// Brought back from the trash.
+    Restore,
+    /// Removed from the trash for good.
+    Purge,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Edit => "edit",
+            Action::Move => "move",
+            Action::Relink => "relink",
+            Action::Upload => "upload",
+            Action::Delete => "delete",
+            Action::Restore => "restore",
+            Action::Purge => "purge",
+        }
+    }
+}
+
+/// One line of the audit log.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    /// RFC 3339 timestamp in local time.
+    pub time: String,
+    /// Signed-in user, or `None` when the server has no accounts.
+    pub user: Option<String>,
+    pub action: Action,
+    /// Path relative to the notes root.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+fn log_path(notes_dir: &Path) -> PathBuf {
+    notes_dir.join(".para").join("audit.log")
+}
+
+/// Append an entry stamped with the current time. The log is JSON lines and
+/// is only ever appended to.
+pub fn record(
+    notes_dir: &Path,
+    user: Option<&str>,
+    action: Action,
+    path: &str,
+    detail: Option<String>,
+) -> Result<()> {
+    let entry = Entry {
+        time: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        user: user.map(str::to_string),
+        action,
+        path: path.to_string(),
+        detail,
+    };
+    let path = log_path(notes_dir);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    Ok(())
+}
+
+/// Every recorded entry, oldest first. Lines that don't parse are skipped.
+pub fn read(notes_dir: &Path) -> Vec<Entry> {
+    let Ok(text) = std::fs::read_to_string(log_path(notes_dir)) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
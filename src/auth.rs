@@ -0,0 +1,232 @@
+use anyhow::{Result, anyhow, bail};
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use password_hash::rand_core::{OsRng, RngCore};
+use password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use crate::config::{Role, Token, User};
+
+/// Bytes of randomness in a token from [`new_token`].
+const TOKEN_BYTES: usize = 32;
+
+/// Bytes of randomness in a token's id, which is sent with it so the server
+/// only checks the one hash.
+const TOKEN_ID_BYTES: usize = 6;
+
+/// How many signed-in `Authorization` headers [`VERIFIED`] holds before it
+/// starts over.
+const MAX_VERIFIED: usize = 1024;
+
+/// `Authorization` headers that already signed someone in, with the hash or
+/// password they matched, so the slow hash isn't recomputed on every request
+/// a client makes.
+static VERIFIED: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Find the account matching an `Authorization: Basic ...` header value.
+pub fn authenticate<'a>(users: &'a [User], header: &str) -> Option<&'a User> {
+    let encoded = header.strip_prefix("Basic ")?.trim();
+    let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+    let (name, password) = decoded.split_once(':')?;
+    let user = users.iter().find(|user| user.name == name)?;
+    if !is_hash(&user.password) {
+        if !constant_time_eq(&user.password, password) {
+            return None;
+        }
+        remember(header, &user.password);
+        return Some(user);
+    }
+    verify(&user.password, password, header).then_some(user)
+}
+
+/// Find the API token matching an `Authorization: Bearer ...` header value,
+/// as the user it acts for. Tokens from [`new_token`] start with their id,
+/// so only that token's hash is checked; older ones without an id are each
+/// tried in turn.
+pub fn authenticate_token(tokens: &[Token], header: &str) -> Option<User> {
+    let secret = header.strip_prefix("Bearer ")?.trim();
+    let token = match secret.split_once('.') {
+        Some((id, _)) => tokens
+            .iter()
+            .find(|token| !token.id.is_empty() && token.id == id)
+            .filter(|token| verify(&token.hash, secret, header))?,
+        None => tokens
+            .iter()
+            .filter(|token| token.id.is_empty())
+            .find(|token| verify(&token.hash, secret, header))?,
+    };
+    Some(User {
+        name: token.name.clone(),
+        password: String::new(),
+        role: token.role,
+    })
+}
+
+/// Whether `header` already signed someone in, so checking it again costs
+/// nothing. Anything else may mean a slow hash.
+pub fn is_known(header: &str) -> bool {
+    VERIFIED.lock().unwrap().contains_key(header)
+}
+
+/// Whether `secret`, sent in `header`, matches the Argon2 `hash`.
+fn verify(hash: &str, secret: &str, header: &str) -> bool {
+    if VERIFIED.lock().unwrap().get(header).is_some_and(|known| known == hash) {
+        return true;
+    }
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return false;
+    };
+    if Argon2::default().verify_password(secret.as_bytes(), &parsed).is_err() {
+        return false;
+    }
+    remember(header, hash);
+    true
+}
+
+/// Note that `header` matched `hash`, forgetting every header seen so far
+/// once there are [`MAX_VERIFIED`] of them; their clients are checked once
+/// more on their next request.
+fn remember(header: &str, hash: &str) {
+    let mut verified = VERIFIED.lock().unwrap();
+    if verified.len() >= MAX_VERIFIED && !verified.contains_key(header) {
+        verified.clear();
+    }
+    verified.insert(header.to_string(), hash.to_string());
+}
+
+/// An editor account from `--auth name:hash`.
+pub fn parse_user(spec: &str) -> Result<User> {
+    let (name, password) = spec
+        .split_once(':')
+        .filter(|(name, password)| !name.is_empty() && !password.is_empty())
+        .ok_or_else(|| anyhow!("--auth takes name:hash; make the hash with `para hash-password`"))?;
+    if !is_hash(password) {
+        bail!("--auth wants a password hash, not the password; make one with `para hash-password`");
+    }
+    Ok(User {
+        name: name.to_string(),
+        password: password.to_string(),
+        role: Role::Editor,
+    })
+}
+
+/// Hash a password for `--auth` or a `[[users]]` entry.
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow!("failed to hash the password: {e}"))
+}
+
+/// A new random API token, as `{id}.{secret}`, with the id and hash for a
+/// `[[tokens]]` entry.
+pub fn new_token() -> Result<(String, String, String)> {
+    let mut id = [0; TOKEN_ID_BYTES];
+    OsRng.fill_bytes(&mut id);
+    let mut secret = [0; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut secret);
+    let id = URL_SAFE_NO_PAD.encode(id);
+    let token = format!("{id}.{}", URL_SAFE_NO_PAD.encode(secret));
+    let hash = hash_password(&token)?;
+    Ok((token, id, hash))
+}
+
+/// Passwords in the config may be hashes, in the PHC string format `para
+/// hash-password` prints, rather than the password.
+pub fn is_hash(password: &str) -> bool {
+    PasswordHash::new(password).is_ok_and(|hash| hash.salt.is_some() && hash.hash.is_some())
+}
+
+/// Compare secrets without leaking how much of them matched through timing.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(name: &str, password: &str) -> String {
+        format!("Basic {}", STANDARD.encode(format!("{name}:{password}")))
+    }
+
+    fn users(password: &str) -> Vec<User> {
+        vec![User {
+            name: "alice".to_string(),
+            password: password.to_string(),
+            role: Role::Editor,
+        }]
+    }
+
+    #[test]
+    fn authenticate_checks_a_hashed_password() {
+        let users = users(&hash_password("hashed secret").unwrap());
+        assert!(authenticate(&users, &basic("alice", "hashed secret")).is_some());
+        assert!(authenticate(&users, &basic("alice", "wrong")).is_none());
+        assert!(authenticate(&users, &basic("bob", "hashed secret")).is_none());
+    }
+
+    #[test]
+    fn authenticate_checks_a_plain_password() {
+        let users = users("plain secret");
+        assert!(authenticate(&users, &basic("alice", "plain secret")).is_some());
+        assert!(is_known(&basic("alice", "plain secret")));
+        assert!(authenticate(&users, &basic("alice", "plain secre")).is_none());
+        assert!(!is_known(&basic("alice", "plain secre")));
+    }
+
+    #[test]
+    fn authenticate_refuses_malformed_headers() {
+        let users = users("secret");
+        assert!(authenticate(&users, "Bearer secret").is_none());
+        assert!(authenticate(&users, "Basic not base64!").is_none());
+        assert!(authenticate(&users, &format!("Basic {}", STANDARD.encode("alice"))).is_none());
+    }
+
+    #[test]
+    fn authenticate_token_looks_up_by_id() {
+        let (token, id, hash) = new_token().unwrap();
+        let tokens = vec![Token {
+            name: "script".to_string(),
+            id: id.clone(),
+            hash,
+            role: Role::ReadOnly,
+        }];
+        let user = authenticate_token(&tokens, &format!("Bearer {token}")).unwrap();
+        assert_eq!(user.name, "script");
+        assert!(user.role == Role::ReadOnly);
+
+        let (_, secret) = token.split_once('.').unwrap();
+        assert!(authenticate_token(&tokens, &format!("Bearer {id}.x{secret}")).is_none());
+        assert!(authenticate_token(&tokens, &format!("Bearer other.{secret}")).is_none());
+        assert!(authenticate_token(&tokens, &basic("script", &token)).is_none());
+    }
+
+    #[test]
+    fn parse_user_wants_a_hash() {
+        let hash = hash_password("pw").unwrap();
+        let user = parse_user(&format!("me:{hash}")).unwrap();
+        assert_eq!(user.name, "me");
+        assert!(user.role == Role::Editor);
+        assert!(parse_user("me:pw").is_err());
+        assert!(parse_user(&format!(":{hash}")).is_err());
+        assert!(parse_user("me").is_err());
+    }
+
+    #[test]
+    fn is_hash_parses_phc_strings() {
+        assert!(is_hash(&hash_password("pw").unwrap()));
+        assert!(!is_hash("correct horse battery staple"));
+        assert!(!is_hash("$argon2id$broken"));
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_strings() {
+        assert!(constant_time_eq("abc", "abc"));
+        assert!(!constant_time_eq("abc", "abd"));
+        assert!(!constant_time_eq("abc", "ab"));
+    }
+}
@@ -0,0 +1,143 @@
+use comrak::Arena;
+use comrak::nodes::{AstNode, NodeHtmlBlock, NodeValue};
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::serve::html_escape;
+
+/// `[!type]` opening a blockquote, then `-` (folded) or `+` (foldable but
+/// open), then an optional title.
+static MARKER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[!([A-Za-z][\w-]*)\]([+-]?)\s*(.*)$").unwrap());
+
+/// Turn blockquotes opening with an Obsidian `[!type]` marker into callout
+/// boxes, leaving their content to render as usual. Nested callouts work.
+pub fn render<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>) {
+    let quotes: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::BlockQuote))
+        .collect();
+    for quote in quotes {
+        convert(arena, quote);
+    }
+}
+
+fn convert<'a>(arena: &'a Arena<AstNode<'a>>, quote: &'a AstNode<'a>) {
+    let Some(paragraph) = quote
+        .first_child()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::Paragraph))
+    else {
+        return;
+    };
+    let Some(marker) = paragraph.first_child() else {
+        return;
+    };
+    let (kind, fold, mut title) = {
+        let NodeValue::Text(text) = &marker.data.borrow().value else {
+            return;
+        };
+        let Some(caps) = MARKER_RE.captures(text) else {
+            return;
+        };
+        (caps[1].to_lowercase(), caps[2].to_string(), caps[3].to_string())
+    };
+    marker.detach();
+
+    // The rest of the first line belongs to the title
+    while let Some(node) = paragraph.first_child() {
+        let line_break = matches!(
+            node.data.borrow().value,
+            NodeValue::SoftBreak | NodeValue::LineBreak
+        );
+        if !line_break {
+            title.push_str(&text_of(node));
+        }
+        node.detach();
+        if line_break {
+            break;
+        }
+    }
+    if paragraph.first_child().is_none() {
+        paragraph.detach();
+    }
+
+    let title = match title.trim() {
+        "" => capitalize(&kind),
+        title => title.to_string(),
+    };
+    let attrs = format!("class=\"callout\" data-callout=\"{}\"", family(&kind));
+    let (open, close) = match fold.as_str() {
+        "" => (
+            format!(
+                "<div {attrs}>\n<div class=\"callout-title\">{}</div>\n<div class=\"callout-content\">\n",
+                html_escape(&title)
+            ),
+            "</div>\n</div>\n",
+        ),
+        fold => (
+            format!(
+                "<details {attrs}{}>\n<summary class=\"callout-title\">{}</summary>\n<div class=\"callout-content\">\n",
+                if fold == "+" { " open" } else { "" },
+                html_escape(&title)
+            ),
+            "</div>\n</details>\n",
+        ),
+    };
+
+    quote.insert_before(html_block(arena, open));
+    for child in quote.children().collect::<Vec<_>>() {
+        quote.insert_before(child);
+    }
+    quote.insert_before(html_block(arena, close.to_string()));
+    quote.detach();
+}
+
+/// A raw HTML node, passed through as is when rendering.
+pub(crate) fn html_block<'a>(arena: &'a Arena<AstNode<'a>>, literal: String) -> &'a AstNode<'a> {
+    arena.alloc(
+        NodeValue::HtmlBlock(NodeHtmlBlock {
+            block_type: 6,
+            literal,
+        })
+        .into(),
+    )
+}
+
+/// The plain text of an inline node and everything in it.
+fn text_of<'a>(node: &'a AstNode<'a>) -> String {
+    node.descendants()
+        .filter_map(|n| match &n.data.borrow().value {
+            NodeValue::Text(text) => Some(text.clone()),
+            NodeValue::Code(code) => Some(code.literal.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The callout a type is styled as, folding Obsidian's aliases together.
+/// Unknown types look like notes.
+fn family(kind: &str) -> &'static str {
+    match kind {
+        "abstract" | "summary" | "tldr" => "abstract",
+        "info" => "info",
+        "todo" => "todo",
+        "tip" | "hint" | "important" => "tip",
+        "success" | "check" | "done" => "success",
+        "question" | "help" | "faq" => "question",
+        "warning" | "caution" | "attention" => "warning",
+        "failure" | "fail" | "missing" => "failure",
+        "danger" | "error" => "danger",
+        "bug" => "bug",
+        "example" => "example",
+        "quote" | "cite" => "quote",
+        _ => "note",
+    }
+}
+
+fn capitalize(kind: &str) -> String {
+    let mut chars = kind.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,46 @@
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDateTime;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::vault;
+
+/// Where captures go, unless the vault has an inbox folder by another case.
+const INBOX_DIR: &str = "Inbox";
+
+/// Save `text` as a new note in the inbox, named for the time it was
+/// captured. A second capture in the same second is appended to the first.
+/// Returns the note's path relative to the notes root.
+pub fn run(notes_dir: &Path, text: &str, now: NaiveDateTime) -> Result<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        bail!("nothing to capture");
+    }
+    let inbox = inbox(notes_dir);
+    std::fs::create_dir_all(&inbox).with_context(|| format!("failed to create {}", inbox.display()))?;
+    let path = inbox.join(format!("{}.md", now.format("%Y-%m-%d %H%M%S")));
+    let relative = vault::relative(notes_dir, &path);
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {relative}"))?;
+    let separator = if file.metadata()?.len() > 0 { "\n" } else { "" };
+    writeln!(file, "{separator}{text}").with_context(|| format!("failed to write {relative}"))?;
+    Ok(relative)
+}
+
+/// The vault's top-level inbox folder, matched without regard to case.
+pub fn inbox(notes_dir: &Path) -> PathBuf {
+    std::fs::read_dir(notes_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            entry.file_type().is_ok_and(|t| t.is_dir())
+                && entry.file_name().to_string_lossy().eq_ignore_ascii_case(INBOX_DIR)
+        })
+        .map(|entry| entry.path())
+        .unwrap_or_else(|| notes_dir.join(INBOX_DIR))
+}
@@ -0,0 +1,86 @@
+use std::borrow::Cow;
+
+const MARKER: &str = "%%";
+
+/// Remove Obsidian `%%comments%%`, inline or spanning lines, so they're
+/// never rendered or shown in search results. Code blocks and inline code
+/// are left alone, and so are line breaks, so lines keep their numbers. As in
+/// Obsidian, an unclosed `%%` hides the rest of the note.
+pub fn strip(content: &str) -> Cow<'_, str> {
+    if !content.contains(MARKER) {
+        return Cow::Borrowed(content);
+    }
+
+    let mut out = String::with_capacity(content.len());
+    let mut fence: Option<&str> = None;
+    let mut in_comment = false;
+    for line in content.split_inclusive('\n') {
+        if !in_comment {
+            let trimmed = line.trim_start();
+            if let Some(marker) = fence {
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                }
+                out.push_str(line);
+                continue;
+            }
+            if let Some(marker) = fence_marker(trimmed) {
+                fence = Some(marker);
+                out.push_str(line);
+                continue;
+            }
+        }
+        strip_line(line, &mut in_comment, &mut out);
+    }
+    Cow::Owned(out)
+}
+
+/// The backticks or tildes opening a fenced code block, if `line` starts one.
+fn fence_marker(line: &str) -> Option<&str> {
+    let first = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.len() - line.trim_start_matches(first).len();
+    (len >= 3).then(|| &line[..len])
+}
+
+/// Copy `line` to `out` without the comments in it, tracking whether one
+/// is still open at its end.
+fn strip_line(line: &str, in_comment: &mut bool, out: &mut String) {
+    let mut rest = line;
+    while !rest.is_empty() {
+        if *in_comment {
+            match rest.find(MARKER) {
+                Some(end) => {
+                    *in_comment = false;
+                    rest = &rest[end + MARKER.len()..];
+                }
+                None => {
+                    if line.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    return;
+                }
+            }
+            continue;
+        }
+
+        // Copy up to whichever comes first: a comment or inline code
+        let next = rest.find(['%', '`']).unwrap_or(rest.len());
+        out.push_str(&rest[..next]);
+        rest = &rest[next..];
+        if rest.starts_with(MARKER) {
+            *in_comment = true;
+            rest = &rest[MARKER.len()..];
+        } else if rest.starts_with('`') {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let code = match rest[ticks..].find(&rest[..ticks]) {
+                Some(close) => ticks + close + ticks,
+                None => ticks,
+            };
+            out.push_str(&rest[..code]);
+            rest = &rest[code..];
+        } else if !rest.is_empty() {
+            out.push('%');
+            rest = &rest[1..];
+        }
+    }
+}
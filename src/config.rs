@@ -0,0 +1,691 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
+
+use crate::search::Mode;
+
+/// Name of the per-directory override file.
+pub const DIR_CONFIG_FILE: &str = ".para.toml";
+
+/// Every `.para.toml` read so far, by path, parsed; `None` where there's no
+/// readable, valid file. Emptied by [`forget_dir_configs`].
+static DIR_CONFIGS: LazyLock<RwLock<HashMap<PathBuf, Option<toml::Table>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Drop every cached `.para.toml`, after one may have been added, changed,
+/// moved or removed.
+pub fn forget_dir_configs() {
+    DIR_CONFIGS.write().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// The parsed `.para.toml` at `path`, read once and then cached.
+fn dir_config(path: PathBuf) -> Option<toml::Table> {
+    if let Some(layer) = DIR_CONFIGS.read().unwrap_or_else(|e| e.into_inner()).get(&path) {
+        return layer.clone();
+    }
+    let layer = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| text.parse::<toml::Table>().ok());
+    DIR_CONFIGS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(path, layer.clone());
+    layer
+}
+
+/// Settings read from `~/.config/para/config.toml`. Every field has a default,
+/// so a missing file or section behaves like an empty one.
+///
+/// Any folder in the vault may contain a `.para.toml` with the same shape; its
+/// values override these for that folder and everything below it.
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Colour scheme for pages.
+    pub theme: Theme,
+    /// Fallback order for entries without a weight or `.para-order` position.
+    pub sort: SortOrder,
+    /// Hide the subtree from navigation and search and refuse to serve it.
+    /// Usually set in a folder's `.para.toml`.
+    pub private: bool,
+    pub links: LinkConfig,
+    pub files: FilesConfig,
+    pub uploads: UploadsConfig,
+    pub semantic: SemanticConfig,
+    pub spelling: SpellingConfig,
+    pub lint: LintConfig,
+    /// Frontmatter rules, usually set in a folder's `.para.toml`.
+    pub schema: SchemaConfig,
+    pub journal: JournalConfig,
+    pub review: ReviewConfig,
+    /// Only read from the global config, since it names commands to run.
+    pub diagrams: DiagramConfig,
+    /// Accounts allowed to sign in to `para serve`. With none, the server is
+    /// open to anyone who can reach it.
+    pub users: Vec<User>,
+    /// API tokens for scripts and other clients that send `Authorization:
+    /// Bearer`. Like `users`, any of them puts the server behind a login.
+    pub tokens: Vec<Token>,
+    /// Who may read and edit a subtree, usually set in a folder's `.para.toml`.
+    pub access: AccessConfig,
+    /// Searches pinned at the top of the sidebar, usually set in the vault's
+    /// root `.para.toml`.
+    pub searches: Vec<SavedSearch>,
+    /// Vaults `para serve` can switch between. Only read from the global
+    /// config.
+    pub vaults: Vec<Vault>,
+    /// How often each client may search or change the vault through `para
+    /// serve`. Only read from the global config.
+    pub rate_limit: RateLimitConfig,
+    /// Bounds on every request to `para serve`. Only read from the global
+    /// config.
+    pub limits: LimitsConfig,
+    /// The signed-in user this config is resolved for; unrestricted if unset.
+    #[serde(skip)]
+    pub viewer: Option<User>,
+    /// `para serve` refuses every change, whoever asks. `--read-only` and
+    /// `--writable` override it. Only read from the global config.
+    pub read_only: bool,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Auto => "auto",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    /// Alphabetical by file name.
+    #[default]
+    Name,
+    /// Reverse alphabetical, handy for date-prefixed notes.
+    NameDesc,
+    /// Most recently modified first.
+    Modified,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct LinkConfig {
+    /// Fetch title/description/favicon for standalone external links and
+    /// render them as cards.
+    pub unfurl: bool,
+    /// Add the `external` class (and its icon) to outbound links.
+    pub mark_external: bool,
+    /// Open outbound links in a new tab.
+    pub external_new_tab: bool,
+    /// `rel` values added to outbound links, e.g. `["noopener", "nofollow"]`.
+    pub external_rel: Vec<String>,
+    /// What to do with links to notes moved or renamed outside para.
+    pub on_move: OnMove,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnMove {
+    /// Leave links alone.
+    Ignore,
+    /// Show a banner in open pages offering to update the links.
+    #[default]
+    Ask,
+    /// Update the links straight away.
+    Rewrite,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct FilesConfig {
+    /// Extensions of non-markdown files listed in the sidebar and directory
+    /// views, without the leading dot.
+    pub show_extensions: Vec<String>,
+    /// Files the server sends up to this many bytes are read into memory
+    /// whole; larger ones are streamed from disk.
+    pub max_buffered_bytes: u64,
+    /// Extensions of files whose changes reload open pages, without the
+    /// leading dot.
+    pub reload_extensions: Vec<String>,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            show_extensions: ["pdf", "png", "jpg", "jpeg", "gif", "svg", "webp", "csv"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            max_buffered_bytes: 1024 * 1024,
+            reload_extensions: ["md", "css", "pdf", "png", "jpg", "jpeg", "gif", "svg", "webp", "csv"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct UploadsConfig {
+    /// Remove EXIF/XMP metadata (GPS position, camera details) from uploaded
+    /// and pasted images before they are written to disk.
+    pub strip_metadata: bool,
+}
+
+impl Default for UploadsConfig {
+    fn default() -> Self {
+        Self {
+            strip_metadata: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct SemanticConfig {
+    /// OpenAI-compatible embeddings endpoint, such as Ollama's
+    /// `http://localhost:11434/v1/embeddings`. Semantic search is off while
+    /// this is empty.
+    pub url: String,
+    pub model: String,
+    /// Environment variable holding an API key to send as a bearer token.
+    pub api_key_env: String,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct SpellingConfig {
+    /// Underline misspelled words in rendered notes.
+    pub enabled: bool,
+    /// Hunspell dictionary: a name like `en_US` looked up in the usual
+    /// system folders, or a path to the `.dic`/`.aff` pair without extension.
+    pub dictionary: String,
+}
+
+impl Default for SpellingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dictionary: "en_US".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct LintConfig {
+    /// Rules `para lint` skips, e.g. `["trailing-whitespace"]`.
+    pub disable: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct SchemaConfig {
+    /// Frontmatter fields every note must have.
+    pub required: Vec<String>,
+    /// Expected type for each field, when present.
+    pub fields: BTreeMap<String, FieldRule>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum FieldRule {
+    Kind(FieldKind),
+    /// One of a fixed set of values, e.g. `["active", "paused", "done"]`.
+    OneOf(Vec<String>),
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    String,
+    Number,
+    Bool,
+    /// `YYYY-MM-DD`.
+    Date,
+    List,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct JournalConfig {
+    /// Where daily notes live, relative to the notes root, as a strftime
+    /// pattern.
+    pub path: String,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            path: "Journal/%Y/%m/%Y-%m-%d.md".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Requests a client may make in a burst. `0` turns the limit off.
+    pub burst: u32,
+    /// Milliseconds before a client may make one more.
+    pub refill_ms: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            burst: 30,
+            refill_ms: 500,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Largest request body, uploads included, in bytes.
+    pub max_body_bytes: usize,
+    /// Seconds a handler may take to start its response, once the request
+    /// has arrived; a body already being sent isn't cut off. `0` waits
+    /// forever.
+    pub response_timeout_secs: u64,
+    /// Requests handled at once; more wait their turn. `0` for no cap.
+    pub max_concurrent: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 50 * 1024 * 1024,
+            response_timeout_secs: 60,
+            max_concurrent: 256,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ReviewConfig {
+    /// Days without an edit before `/review` calls a project stale.
+    pub stale_days: u32,
+}
+
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        Self { stale_days: 30 }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct DiagramConfig {
+    /// Command that renders ```` ```plantuml ```` blocks, given the source on
+    /// stdin and printing SVG.
+    pub plantuml: String,
+    /// PlantUML server to render through instead, such as
+    /// `https://www.plantuml.com/plantuml`.
+    pub plantuml_server: String,
+    /// Command that renders ```` ```d2 ```` blocks the same way.
+    pub d2: String,
+    /// Command that renders ```` ```mermaid ```` blocks the same way, such as
+    /// `mmdc -i - -o - -e svg`. While empty they're drawn in the browser,
+    /// and every page carries mermaid.js to do it.
+    pub mermaid: String,
+}
+
+impl Default for DiagramConfig {
+    fn default() -> Self {
+        Self {
+            plantuml: "plantuml -tsvg -pipe".to_string(),
+            plantuml_server: String::new(),
+            d2: "d2 - -".to_string(),
+            mermaid: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct SavedSearch {
+    /// Shown in the sidebar; the query itself if unset.
+    #[serde(default)]
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub mode: Mode,
+}
+
+impl SavedSearch {
+    pub fn label(&self) -> &str {
+        if self.name.is_empty() { &self.query } else { &self.name }
+    }
+}
+
+/// A notes folder known by name, from a `[[vaults]]` entry.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Vault {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct User {
+    pub name: String,
+    pub password: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+/// A static API token, from a `[[tokens]]` entry.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Token {
+    /// Who the token acts as, in access lists and the audit log.
+    pub name: String,
+    /// The start of the token, before its `.`, from `para token`. Tokens
+    /// made before ids existed have none.
+    #[serde(default)]
+    pub id: String,
+    /// Argon2 hash of the token, from `para token`.
+    pub hash: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// Browse and search, but never change anything.
+    #[default]
+    ReadOnly,
+    /// Also save, create, and relink notes.
+    Editor,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct AccessConfig {
+    /// Users who may see this subtree. Empty means everyone.
+    pub read: Vec<String>,
+    /// Editors who may change notes here. Empty means every editor.
+    pub write: Vec<String>,
+}
+
+impl FilesConfig {
+    /// Whether a non-markdown file should appear in navigation.
+    pub fn shows(&self, name: &str) -> bool {
+        name.rsplit_once('.').is_some_and(|(_, ext)| {
+            self.show_extensions
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(ext))
+        })
+    }
+}
+
+impl Config {
+    /// Load from an explicit path, or from the default location if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => match default_path() {
+                Some(p) if p.exists() => p,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Failed to parse config {}", path.display()))
+    }
+
+    /// This config as seen by a signed-in user: folders they can't read
+    /// behave as private.
+    pub fn for_viewer(&self, viewer: Option<&User>) -> Config {
+        let mut config = self.clone();
+        config.viewer = viewer.cloned();
+        config.restrict();
+        config
+    }
+
+    /// Whether the viewer may change notes governed by this config.
+    pub fn can_write(&self) -> bool {
+        if self.read_only {
+            return false;
+        }
+        match &self.viewer {
+            None => true,
+            Some(user) => {
+                user.role == Role::Editor
+                    && !self.private
+                    && (self.access.write.is_empty() || self.access.write.contains(&user.name))
+            }
+        }
+    }
+
+    /// Mark the config private when the viewer isn't on its read list.
+    fn restrict(&mut self) {
+        if let Some(user) = &self.viewer
+            && !self.access.read.is_empty()
+            && !self.access.read.contains(&user.name)
+        {
+            self.private = true;
+        }
+    }
+
+    /// Resolve the settings that apply inside `dir`, layering every
+    /// `.para.toml` from the notes root down to `dir` over this config.
+    /// Unreadable or invalid override files are ignored. A folder can only
+    /// add to its parents' restrictions: once a subtree is private, or leaves
+    /// the viewer off an `[access]` list, nothing further down undoes it.
+    pub fn for_dir(&self, notes_dir: &Path, dir: &Path) -> Config {
+        let Ok(relative) = dir.strip_prefix(notes_dir) else {
+            return self.clone();
+        };
+
+        let mut layers = Vec::new();
+        let mut current = notes_dir.to_path_buf();
+        for component in std::iter::once(None).chain(relative.components().map(Some)) {
+            if let Some(component) = component {
+                current.push(component);
+            }
+            if let Some(layer) = dir_config(current.join(DIR_CONFIG_FILE)) {
+                layers.push(layer);
+            }
+        }
+        if layers.is_empty() {
+            return self.clone();
+        }
+
+        let Ok(mut merged) = toml::Table::try_from(self) else {
+            return self.clone();
+        };
+        let private = self.private
+            || layers
+                .iter()
+                .any(|layer| layer.get("private").and_then(toml::Value::as_bool) == Some(true));
+        let access: Vec<AccessConfig> = layers
+            .iter()
+            .filter_map(|layer| layer.get("access")?.clone().try_into().ok())
+            .collect();
+        for layer in layers {
+            merge_tables(&mut merged, layer);
+        }
+        let mut config: Config = merged.try_into().unwrap_or_else(|_| self.clone());
+        // Accounts, vaults and server settings only come from the global
+        // config, never from a vault
+        config.users = self.users.clone();
+        config.tokens = self.tokens.clone();
+        config.vaults = self.vaults.clone();
+        config.diagrams = self.diagrams.clone();
+        config.rate_limit = self.rate_limit.clone();
+        config.limits = self.limits.clone();
+        config.viewer = self.viewer.clone();
+        config.read_only = self.read_only;
+        config.private = private;
+        if let Some(user) = &self.viewer {
+            // The viewer has to be on every list from the root down, so keep
+            // whichever one leaves them off
+            let lists = std::iter::once(&self.access).chain(&access);
+            if let Some(denied) = lists.clone().find(|a| !a.read.is_empty() && !a.read.contains(&user.name)) {
+                config.access.read = denied.read.clone();
+            }
+            if let Some(denied) = lists.clone().find(|a| !a.write.is_empty() && !a.write.contains(&user.name)) {
+                config.access.write = denied.write.clone();
+            }
+        }
+        config.restrict();
+        config
+    }
+
+    /// Whether `path` (a file or folder) sits in a subtree marked private.
+    pub fn is_private(&self, notes_dir: &Path, path: &Path) -> bool {
+        let dir = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(notes_dir)
+        };
+        self.for_dir(notes_dir, dir).private
+    }
+}
+
+/// Recursively overlay `overlay` onto `base`; nested tables merge, other
+/// values replace.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(incoming)) => {
+                merge_tables(existing, incoming);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join("para").join("config.toml"));
+    }
+    let home = std::env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".config").join("para").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, role: Role) -> User {
+        User {
+            name: name.to_string(),
+            password: String::new(),
+            role,
+        }
+    }
+
+    /// An empty folder of its own under the system temp dir.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("para-config-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn can_write_without_a_viewer_unless_read_only() {
+        let mut config = Config::default();
+        assert!(config.can_write());
+        config.read_only = true;
+        assert!(!config.can_write());
+    }
+
+    #[test]
+    fn can_write_follows_role_and_write_list() {
+        let mut config = Config::default();
+        config.access.write = vec!["alice".to_string()];
+        assert!(config.for_viewer(Some(&user("alice", Role::Editor))).can_write());
+        assert!(!config.for_viewer(Some(&user("bob", Role::Editor))).can_write());
+        assert!(!config.for_viewer(Some(&user("alice", Role::ReadOnly))).can_write());
+
+        let mut config = config.for_viewer(Some(&user("alice", Role::Editor)));
+        config.private = true;
+        assert!(!config.can_write());
+    }
+
+    #[test]
+    fn for_viewer_hides_what_the_viewer_cannot_read() {
+        let mut config = Config::default();
+        config.access.read = vec!["alice".to_string()];
+        assert!(!config.for_viewer(Some(&user("alice", Role::ReadOnly))).private);
+        assert!(config.for_viewer(Some(&user("bob", Role::ReadOnly))).private);
+        assert!(!config.for_viewer(None).private);
+    }
+
+    #[test]
+    fn for_dir_keeps_a_parent_private() {
+        let notes = scratch("private");
+        std::fs::create_dir_all(notes.join("a/b")).unwrap();
+        std::fs::write(notes.join("a").join(DIR_CONFIG_FILE), "private = true").unwrap();
+        std::fs::write(notes.join("a/b").join(DIR_CONFIG_FILE), "private = false").unwrap();
+        let config = Config::default();
+        assert!(config.for_dir(&notes, &notes.join("a/b")).private);
+        assert!(!config.for_dir(&notes, &notes).private);
+        std::fs::remove_dir_all(&notes).unwrap();
+    }
+
+    #[test]
+    fn for_dir_access_lists_only_narrow() {
+        let notes = scratch("access");
+        std::fs::create_dir_all(notes.join("a/b")).unwrap();
+        std::fs::write(
+            notes.join("a").join(DIR_CONFIG_FILE),
+            "[access]\nread = [\"alice\", \"bob\"]\nwrite = [\"alice\"]",
+        )
+        .unwrap();
+        std::fs::write(notes.join("a/b").join(DIR_CONFIG_FILE), "[access]\nread = []\nwrite = [\"bob\"]").unwrap();
+        let config = Config::default();
+
+        let bob = config.for_viewer(Some(&user("bob", Role::Editor)));
+        let inner = bob.for_dir(&notes, &notes.join("a/b"));
+        assert!(!inner.private);
+        assert!(!inner.can_write());
+
+        let carol = config.for_viewer(Some(&user("carol", Role::Editor)));
+        assert!(carol.for_dir(&notes, &notes.join("a/b")).private);
+        std::fs::remove_dir_all(&notes).unwrap();
+    }
+
+    #[test]
+    fn for_dir_ignores_global_only_settings() {
+        let notes = scratch("global");
+        std::fs::write(
+            notes.join(DIR_CONFIG_FILE),
+            "read_only = false\n[diagrams]\nd2 = \"sh -c evil\"\n[limits]\nmax_body_bytes = 1\n[rate_limit]\nburst = 0\n[[users]]\nname = \"x\"\npassword = \"y\"",
+        )
+        .unwrap();
+        let config = Config {
+            read_only: true,
+            ..Config::default()
+        };
+        let resolved = config.for_dir(&notes, &notes);
+        assert!(resolved.read_only);
+        assert_eq!(resolved.diagrams.d2, config.diagrams.d2);
+        assert_eq!(resolved.limits.max_body_bytes, config.limits.max_body_bytes);
+        assert_eq!(resolved.rate_limit.burst, config.rate_limit.burst);
+        assert!(resolved.users.is_empty());
+        std::fs::remove_dir_all(&notes).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use regex::Regex;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use crate::config::DiagramConfig;
+use crate::serve::html_escape;
+
+/// A fenced code block as comrak renders it.
+static BLOCK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<pre><code class="language-([\w-]+)">(.*?)</code></pre>"#).unwrap()
+});
+
+/// Renders diagram blocks to SVG with external tools, keeping the results
+/// under `.para/cache/diagrams` by a hash of their source so each diagram
+/// is only drawn once.
+pub struct Diagrams {
+    cache_dir: PathBuf,
+    /// Diagrams that failed to render this run, so a missing tool or
+    /// unreachable server doesn't slow down every page load.
+    failed: Mutex<HashMap<String, String>>,
+}
+
+impl Diagrams {
+    pub fn new(notes_dir: &Path) -> Self {
+        Self {
+            cache_dir: notes_dir.join(".para").join("cache").join("diagrams"),
+            failed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replace diagram blocks in rendered HTML with images. Blocks that
+    /// can't be rendered are left as code, under a note saying why.
+    pub fn render(&self, html: &str, config: &DiagramConfig) -> String {
+        BLOCK_RE
+            .replace_all(html, |caps: &regex::Captures| {
+                let Some((name, tool)) = tool(&caps[1], config) else {
+                    return caps[0].to_string();
+                };
+                let source = decode_entities(&caps[2]);
+                match self.svg(name, &tool, &source) {
+                    Ok(svg) => format!(
+                        "<img class=\"diagram\" alt=\"{name} diagram\" src=\"data:image/svg+xml;base64,{}\">",
+                        STANDARD.encode(svg)
+                    ),
+                    Err(e) => format!(
+                        "<p class=\"diagram-error\">Couldn't render this diagram: {}</p>\n{}",
+                        html_escape(&format!("{e:#}")),
+                        &caps[0]
+                    ),
+                }
+            })
+            .to_string()
+    }
+
+    /// The SVG for a diagram, from the cache or freshly rendered.
+    fn svg(&self, name: &str, tool: &Tool, source: &str) -> Result<String> {
+        let key = cache_key(name, source);
+        let path = self.cache_dir.join(format!("{key}.svg"));
+        if let Ok(svg) = std::fs::read_to_string(&path) {
+            return Ok(svg);
+        }
+        if let Some(error) = self.failed.lock().unwrap().get(&key) {
+            bail!("{error}");
+        }
+
+        let rendered = match tool {
+            Tool::Command(command) => run(command, source),
+            Tool::Server(server) => post(server, source),
+        }
+        .and_then(|output| {
+            // Drop any XML prolog so the result is just the drawing
+            match output.find("<svg") {
+                Some(start) => Ok(output[start..].to_string()),
+                None => bail!("the renderer didn't produce SVG"),
+            }
+        });
+        match rendered {
+            Ok(svg) => {
+                let _ = std::fs::create_dir_all(&self.cache_dir);
+                let _ = std::fs::write(&path, &svg);
+                Ok(svg)
+            }
+            Err(e) => {
+                self.failed.lock().unwrap().insert(key, format!("{e:#}"));
+                Err(e)
+            }
+        }
+    }
+}
+
+/// How a diagram is drawn.
+enum Tool<'a> {
+    /// A command reading the source on stdin and printing SVG.
+    Command(&'a str),
+    /// A PlantUML server.
+    Server(&'a str),
+}
+
+/// The name and tool for a code block's language, if it's a diagram drawn
+/// on the server.
+fn tool<'a>(language: &str, config: &'a DiagramConfig) -> Option<(&'static str, Tool<'a>)> {
+    match language {
+        "plantuml" | "puml" if !config.plantuml_server.is_empty() => {
+            Some(("PlantUML", Tool::Server(&config.plantuml_server)))
+        }
+        "plantuml" | "puml" => Some(("PlantUML", Tool::Command(&config.plantuml))),
+        "d2" => Some(("D2", Tool::Command(&config.d2))),
+        "mermaid" if !config.mermaid.is_empty() => Some(("Mermaid", Tool::Command(&config.mermaid))),
+        _ => None,
+    }
+}
+
+/// Run `command`, giving it `source` on stdin and returning what it prints.
+fn run(command: &str, source: &str) -> Result<String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().context("no command configured")?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {program}"))?;
+
+    // Write from another thread so a chatty renderer can't fill its output
+    // pipe while we're still feeding it
+    let mut stdin = child.stdin.take().context("no stdin")?;
+    let input = source.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+
+    if !output.status.success() && !String::from_utf8_lossy(&output.stdout).contains("<svg") {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{program} failed: {}", stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Render through a PlantUML server's `/svg` endpoint.
+fn post(server: &str, source: &str) -> Result<String> {
+    let url = format!("{}/svg", server.trim_end_matches('/'));
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(10)))
+        .build()
+        .into();
+    Ok(agent
+        .post(&url)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .send(source)
+        .with_context(|| format!("failed to reach {url}"))?
+        .body_mut()
+        .read_to_string()?)
+}
+
+fn cache_key(language: &str, source: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    language.hash(&mut hasher);
+    source.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
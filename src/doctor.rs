@@ -0,0 +1,72 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::links::{self, Resolution};
+use crate::note::Note;
+use crate::schema;
+use crate::spelling::Speller;
+use crate::vault;
+
+pub struct Checks {
+    pub spelling: bool,
+}
+
+/// Check the vault and print one `path:line: problem` line per issue.
+/// Returns the number of issues found.
+pub fn run(notes_dir: &Path, config: &Config, checks: &Checks) -> Result<usize> {
+    let speller = if checks.spelling {
+        Some(Speller::load(notes_dir, &config.spelling)?)
+    } else {
+        None
+    };
+
+    let index = links::index(notes_dir, config);
+    let mut issues = 0;
+    for path in vault::files(notes_dir, config) {
+        if path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let note = Note::parse(vault::relative(notes_dir, &path), &content);
+
+        let dir_config = config.for_dir(notes_dir, path.parent().unwrap_or(notes_dir));
+        for problem in schema::validate(&note.frontmatter, &dir_config.schema) {
+            println!("{}:1: {problem}", note.path);
+            issues += 1;
+        }
+
+        for link in &note.links {
+            let problem = match index.lookup(&note.path, link) {
+                Resolution::Found(_) => continue,
+                Resolution::Missing => "broken link".to_string(),
+                Resolution::Ambiguous(candidates) => {
+                    format!("ambiguous link ({})", candidates.join(", "))
+                }
+            };
+            println!("{}: {problem} {}", note.path, link.target);
+            issues += 1;
+        }
+
+        if let Some(speller) = &speller {
+            for misspelling in speller.check_note(&content) {
+                let suggestions = speller.suggest(&misspelling.word);
+                if suggestions.is_empty() {
+                    println!("{}:{}: {}", note.path, misspelling.line, misspelling.word);
+                } else {
+                    println!(
+                        "{}:{}: {} (did you mean {}?)",
+                        note.path,
+                        misspelling.line,
+                        misspelling.word,
+                        suggestions.join(", ")
+                    );
+                }
+                issues += 1;
+            }
+        }
+    }
+    Ok(issues)
+}
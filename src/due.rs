@@ -0,0 +1,103 @@
+use chrono::NaiveDate;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::note::Note;
+use crate::para::{self, Category};
+use crate::vault;
+
+/// Something with a deadline: an open task, or a note with `due:` set.
+pub struct DueItem {
+    /// Note the item lives in, relative to the notes root.
+    pub path: String,
+    /// Task text, or the note's name for frontmatter dates.
+    pub text: String,
+    /// Line of the task; `None` for a note's own due date.
+    pub line: Option<usize>,
+    pub due: NaiveDate,
+}
+
+/// How close a due date is, relative to today.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Overdue,
+    Today,
+    ThisWeek,
+    Later,
+}
+
+impl Bucket {
+    pub fn of(due: NaiveDate, today: NaiveDate) -> Bucket {
+        match (due - today).num_days() {
+            ..0 => Bucket::Overdue,
+            0 => Bucket::Today,
+            1..=7 => Bucket::ThisWeek,
+            _ => Bucket::Later,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Bucket::Overdue => "Overdue",
+            Bucket::Today => "Today",
+            Bucket::ThisWeek => "This week",
+            Bucket::Later => "Later",
+        }
+    }
+}
+
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()
+}
+
+/// Collect open tasks with a due date and notes with a `due:` field,
+/// soonest first. Finished items and anything in the archive are skipped.
+pub fn collect(notes_dir: &Path, config: &Config) -> Vec<DueItem> {
+    let archive = para::category_dir(notes_dir, Category::Archive);
+    let mut items = Vec::new();
+    for path in vault::files(notes_dir, config) {
+        if path.extension().is_none_or(|e| e != "md")
+            || archive.as_ref().is_some_and(|a| path.starts_with(a))
+        {
+            continue;
+        }
+        let Some(note) = Note::load(notes_dir, &path) else {
+            continue;
+        };
+
+        let finished = note.frontmatter.get_bool("done") == Some(true)
+            || note
+                .frontmatter
+                .get_str("status")
+                .is_some_and(|s| matches!(s.to_lowercase().as_str(), "done" | "completed"));
+        if !finished && let Some(due) = note.frontmatter.get_str("due").and_then(|d| parse_date(&d))
+        {
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            items.push(DueItem {
+                path: note.path.clone(),
+                text: name,
+                line: None,
+                due,
+            });
+        }
+
+        for task in &note.tasks {
+            if task.done {
+                continue;
+            }
+            if let Some(due) = task.due.as_deref().and_then(parse_date) {
+                items.push(DueItem {
+                    path: note.path.clone(),
+                    text: task.text.clone(),
+                    line: Some(task.line),
+                    due,
+                });
+            }
+        }
+    }
+    items.sort_by(|a, b| a.due.cmp(&b.due).then_with(|| a.path.cmp(&b.path)));
+    items
+}
@@ -0,0 +1,99 @@
+//! Lossless removal of EXIF/XMP/text metadata from uploaded images, so
+//! location data and camera details never reach the vault.
+
+/// Strip metadata from an image based on its extension. Unknown formats and
+/// files that fail to parse are returned unchanged.
+pub fn strip_metadata(ext: &str, bytes: &[u8]) -> Vec<u8> {
+    let stripped = match ext.to_lowercase().as_str() {
+        "jpg" | "jpeg" => strip_jpeg(bytes),
+        "png" => strip_png(bytes),
+        "webp" => strip_webp(bytes),
+        _ => None,
+    };
+    stripped.unwrap_or_else(|| bytes.to_vec())
+}
+
+/// Drop APP1 (EXIF, XMP) and APP13 (IPTC) segments, keeping JFIF and ICC data.
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.get(0..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut out = vec![0xFF, 0xD8];
+    let mut pos = 2;
+    loop {
+        if *bytes.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(pos + 1)?;
+        // Start of scan: the rest is image data
+        if marker == 0xDA {
+            out.extend_from_slice(&bytes[pos..]);
+            return Some(out);
+        }
+        let length = u16::from_be_bytes([*bytes.get(pos + 2)?, *bytes.get(pos + 3)?]) as usize;
+        let end = pos + 2 + length;
+        let segment = bytes.get(pos..end)?;
+        if marker != 0xE1 && marker != 0xED {
+            out.extend_from_slice(segment);
+        }
+        pos = end;
+    }
+}
+
+/// Drop eXIf, textual, and timestamp chunks.
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    if bytes.get(0..8)? != SIGNATURE {
+        return None;
+    }
+
+    let mut out = SIGNATURE.to_vec();
+    let mut pos = 8;
+    while pos < bytes.len() {
+        let length = u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let chunk_type = bytes.get(pos + 4..pos + 8)?;
+        let end = pos + 12 + length;
+        let chunk = bytes.get(pos..end)?;
+        if !matches!(chunk_type, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            out.extend_from_slice(chunk);
+        }
+        pos = end;
+    }
+    Some(out)
+}
+
+/// Drop EXIF and XMP chunks from an extended WebP and clear their flags.
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+
+    let mut body = b"WEBP".to_vec();
+    let mut pos = 12;
+    while pos < bytes.len() {
+        let fourcc = bytes.get(pos..pos + 4)?;
+        let length = u32::from_le_bytes(bytes.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        // Chunks are padded to an even length
+        let end = (pos + 8 + length + (length & 1)).min(bytes.len());
+        let chunk = bytes.get(pos..end)?;
+        match fourcc {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let mut chunk = chunk.to_vec();
+                // Clear the EXIF (0x08) and XMP (0x04) presence flags
+                if let Some(flags) = chunk.get_mut(8) {
+                    *flags &= !(0x08 | 0x04);
+                }
+                body.extend_from_slice(&chunk);
+            }
+            _ => body.extend_from_slice(chunk),
+        }
+        pos = end;
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Some(out)
+}
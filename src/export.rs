@@ -0,0 +1,139 @@
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::links::LinkIndex;
+use crate::note::{LinkKind, Note, Task};
+use crate::serve::vault_name;
+use crate::site;
+use crate::vault;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// One JSON document holding every note
+    Json,
+    /// One JSON object per line, per note
+    Jsonl,
+    /// A static HTML site in the `--out` folder
+    Html,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    vault: String,
+    notes: Vec<ExportedNote<'a>>,
+}
+
+#[derive(Serialize)]
+struct ExportedNote<'a> {
+    path: &'a str,
+    frontmatter: serde_json::Value,
+    body: &'a str,
+    tags: &'a [String],
+    links: Vec<ExportedLink<'a>>,
+    backlinks: Vec<&'a str>,
+    tasks: &'a [Task],
+}
+
+#[derive(Serialize)]
+struct ExportedLink<'a> {
+    target: &'a str,
+    kind: LinkKind,
+    /// The note the link resolves to, if any.
+    path: Option<String>,
+}
+
+/// Write every markdown note in the vault as structured JSON to `out`, or
+/// stdout when no file is given. HTML goes to the folder `out` as a site.
+pub fn run(notes_dir: &Path, config: &Config, format: Format, out: Option<&Path>) -> Result<()> {
+    if let Format::Html = format {
+        let Some(out) = out else {
+            bail!("--format html needs --out <dir>");
+        };
+        let notes = site::run(notes_dir, config, out)?;
+        println!("exported {notes} notes to {}", out.display());
+        return Ok(());
+    }
+    let notes: Vec<Note> = vault::files(notes_dir, config)
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .filter_map(|p| Note::load(notes_dir, p))
+        .collect();
+
+    let index = LinkIndex::new(notes.iter().map(|n| n.path.as_str()));
+    let mut backlinks: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    let resolved: Vec<Vec<Option<String>>> = notes
+        .iter()
+        .map(|note| {
+            note.links
+                .iter()
+                .map(|link| {
+                    let target = index.resolve(&note.path, link);
+                    if let Some(target) = &target
+                        && target != &note.path
+                    {
+                        backlinks
+                            .entry(target.clone())
+                            .or_default()
+                            .insert(&note.path);
+                    }
+                    target
+                })
+                .collect()
+        })
+        .collect();
+
+    let exported: Vec<ExportedNote> = notes
+        .iter()
+        .zip(resolved)
+        .map(|(note, targets)| ExportedNote {
+            path: &note.path,
+            frontmatter: note.frontmatter.to_json(),
+            body: &note.body,
+            tags: &note.tags,
+            links: note
+                .links
+                .iter()
+                .zip(targets)
+                .map(|(link, path)| ExportedLink {
+                    target: &link.target,
+                    kind: link.kind,
+                    path,
+                })
+                .collect(),
+            backlinks: backlinks
+                .get(&note.path)
+                .map(|set| set.iter().copied().collect())
+                .unwrap_or_default(),
+            tasks: &note.tasks,
+        })
+        .collect();
+
+    let mut writer: Box<dyn Write> = match out {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    match format {
+        Format::Json => {
+            let document = Document {
+                vault: vault_name(notes_dir),
+                notes: exported,
+            };
+            serde_json::to_writer_pretty(&mut writer, &document)?;
+            writeln!(writer)?;
+        }
+        Format::Jsonl => {
+            for note in &exported {
+                serde_json::to_writer(&mut writer, note)?;
+                writeln!(writer)?;
+            }
+        }
+        Format::Html => unreachable!("written by site::run"),
+    }
+    writer.flush()?;
+    Ok(())
+}
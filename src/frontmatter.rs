@@ -0,0 +1,161 @@
+use serde_yaml_ng::{Mapping, Value};
+
+/// YAML metadata from the `---` block at the top of a note.
+#[derive(Default, Clone)]
+pub struct Frontmatter {
+    values: Mapping,
+}
+
+/// Split a note into its raw frontmatter block (without delimiters) and body.
+pub fn split(content: &str) -> (Option<&str>, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (None, content);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let yaml = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            return (Some(yaml), body);
+        }
+        offset += line.len();
+    }
+
+    (None, content)
+}
+
+/// Parse a note's frontmatter, returning it alongside the remaining body.
+/// Invalid YAML is treated as absent.
+pub fn parse(content: &str) -> (Frontmatter, &str) {
+    match split(content) {
+        (Some(yaml), body) => {
+            let values = serde_yaml_ng::from_str::<Mapping>(yaml).unwrap_or_default();
+            (Frontmatter { values }, body)
+        }
+        (None, body) => (Frontmatter::default(), body),
+    }
+}
+
+/// Read and parse the frontmatter of a file, ignoring read errors.
+pub fn read(path: &std::path::Path) -> Frontmatter {
+    std::fs::read_to_string(path)
+        .map(|content| parse(&content).0)
+        .unwrap_or_default()
+}
+
+impl Frontmatter {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    /// A scalar value rendered as a string (numbers and booleans included).
+    pub fn get_str(&self, key: &str) -> Option<String> {
+        match self.get(key)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+
+    /// A boolean value, accepting `yes`/`no` and quoted booleans too.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            Value::Bool(b) => Some(*b),
+            Value::String(s) => match s.trim().to_lowercase().as_str() {
+                "true" | "yes" => Some(true),
+                "false" | "no" => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// A list of strings, accepting a single comma-separated string too.
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        match self.get(key) {
+            Some(Value::Sequence(items)) => items
+                .iter()
+                .filter_map(|v| match v {
+                    Value::String(s) => Some(s.trim().to_string()),
+                    Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                })
+                .filter(|s| !s.is_empty())
+                .collect(),
+            Some(Value::String(s)) => s
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Each field in the order written. Entries with non-string keys are
+    /// skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values.iter().filter_map(|(k, v)| Some((k.as_str()?, v)))
+    }
+
+    /// The whole block as JSON. Entries with non-string keys are dropped.
+    pub fn to_json(&self) -> serde_json::Value {
+        let map = self
+            .values
+            .iter()
+            .filter_map(|(k, v)| {
+                let key = k.as_str()?.to_string();
+                Some((key, serde_json::to_value(v).unwrap_or_default()))
+            })
+            .collect();
+        serde_json::Value::Object(map)
+    }
+
+    /// Add the fields of `other` that this block lacks. Where both have a
+    /// list (tags, aliases), the lists are combined.
+    pub fn merge(&mut self, other: &Frontmatter) {
+        for (key, theirs) in &other.values {
+            match (self.values.get_mut(key), theirs) {
+                (Some(Value::Sequence(ours)), Value::Sequence(theirs)) => {
+                    for item in theirs {
+                        if !ours.contains(item) {
+                            ours.push(item.clone());
+                        }
+                    }
+                }
+                (Some(_), _) => {}
+                (None, theirs) => {
+                    self.values.insert(key.clone(), theirs.clone());
+                }
+            }
+        }
+    }
+
+    /// Set a field, keeping its place if it's already there.
+    pub fn set(&mut self, key: &str, value: Value) {
+        self.values.insert(Value::String(key.to_string()), value);
+    }
+
+    /// Render the block back into a note, delimiters included, or an empty
+    /// string if there are no fields.
+    pub fn to_block(&self) -> String {
+        if self.values.is_empty() {
+            return String::new();
+        }
+        let yaml = serde_yaml_ng::to_string(&self.values).unwrap_or_default();
+        format!("---\n{yaml}---\n")
+    }
+
+    /// A numeric value, accepting quoted numbers too.
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        match self.get(key)? {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::links;
+use crate::note::Note;
+use crate::para::Category;
+use crate::search;
+use crate::vault;
+
+/// Every note the viewer can see and the links between them, for `/graph`.
+#[derive(Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Serialize)]
+pub struct Node {
+    /// Path relative to the notes root.
+    pub id: String,
+    pub name: String,
+    /// Lowercased PARA category, or `None` for notes outside the four folders.
+    pub category: Option<String>,
+    /// Links in and out.
+    pub degree: usize,
+}
+
+#[derive(Serialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+}
+
+/// Build the graph from the link index. Links that are broken, ambiguous,
+/// point at a note that doesn't exist yet or point back at their own note
+/// are left out, as is linking the same note twice.
+pub fn build(notes_dir: &Path, config: &Config) -> Graph {
+    let index = links::index(notes_dir, config);
+    let notes: Vec<Note> = vault::files(notes_dir, config)
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .filter_map(|p| Note::load(notes_dir, p))
+        .collect();
+
+    let mut pairs = BTreeSet::new();
+    for note in &notes {
+        for link in &note.links {
+            if let Some(target) = index.resolve(&note.path, link)
+                && target != note.path
+                && index.contains(&target)
+            {
+                pairs.insert((note.path.clone(), target));
+            }
+        }
+    }
+
+    let mut degree: HashMap<&str, usize> = HashMap::new();
+    for (source, target) in &pairs {
+        *degree.entry(source).or_default() += 1;
+        *degree.entry(target).or_default() += 1;
+    }
+
+    let nodes = notes
+        .iter()
+        .map(|note| Node {
+            id: note.path.clone(),
+            name: search::name(Path::new(&note.path)),
+            category: Category::of(&note.path).map(|c| c.name().to_lowercase()),
+            degree: degree.get(note.path.as_str()).copied().unwrap_or(0),
+        })
+        .collect();
+    let edges = pairs
+        .into_iter()
+        .map(|(source, target)| Edge { source, target })
+        .collect();
+    Graph { nodes, edges }
+}
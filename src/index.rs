@@ -0,0 +1,360 @@
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, RegexQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, STORED, STRING, Schema, TEXT, TextFieldIndexing, TextOptions, Value,
+};
+use tantivy::tokenizer::{LowerCaser, RawTokenizer, TextAnalyzer};
+use tantivy::{DocAddress, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term, doc};
+
+use crate::comments;
+use crate::config::Config;
+use crate::note::Note;
+use crate::search::{self, FileMatches, Results, Searcher};
+use crate::vault;
+
+/// Most notes ranked for one search, across every page.
+const MAX_RESULTS: usize = 1000;
+/// Memory the index writer may buffer before flushing, in bytes.
+const WRITER_MEMORY: usize = 50_000_000;
+/// How much more a match in the note's name, or in a heading, counts than
+/// one in its body.
+const TITLE_BOOST: f32 = 3.0;
+const HEADING_BOOST: f32 = 2.0;
+/// Keeps each tag whole, so `tag:next-action` doesn't match `next` alone.
+const TAG_TOKENIZER: &str = "tag";
+/// Edits (including swapped letters) a word may be off by in fuzzy mode.
+const FUZZY_DISTANCE: u8 = 1;
+
+/// Full-text index of the vault's notes, held in memory. Built when the
+/// server starts and kept current from the file watcher.
+pub struct SearchIndex {
+    notes_dir: PathBuf,
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    path: Field,
+    /// The note's name, lowercased and untokenized, for suggestions.
+    name: Field,
+    title: Field,
+    headings: Field,
+    body: Field,
+    /// Tags from frontmatter and `#tag`s, whole and lowercased, for
+    /// `tag:name` queries.
+    tag: Field,
+}
+
+impl SearchIndex {
+    /// Index every markdown note outside hidden and private folders.
+    pub fn build(notes_dir: &Path, config: &Config) -> Result<Self> {
+        let mut schema = Schema::builder();
+        let path = schema.add_text_field("path", STRING | STORED);
+        let name = schema.add_text_field("name", STRING);
+        let title = schema.add_text_field("title", TEXT);
+        let headings = schema.add_text_field("headings", TEXT);
+        let body = schema.add_text_field("body", TEXT);
+        let tag = schema.add_text_field(
+            "tag",
+            TextOptions::default().set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer(TAG_TOKENIZER)
+                    .set_index_option(IndexRecordOption::Basic),
+            ),
+        );
+
+        let index = Index::create_in_ram(schema.build());
+        index.tokenizers().register(
+            TAG_TOKENIZER,
+            TextAnalyzer::builder(RawTokenizer::default())
+                .filter(LowerCaser)
+                .build(),
+        );
+        let writer: IndexWriter = index.writer(WRITER_MEMORY)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+
+        let search_index = SearchIndex {
+            notes_dir: notes_dir.to_path_buf(),
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            path,
+            name,
+            title,
+            headings,
+            body,
+            tag,
+        };
+        {
+            let mut writer = search_index.writer.lock().unwrap();
+            for file in vault::files(notes_dir, config) {
+                search_index.add(&mut writer, &file)?;
+            }
+            writer.commit()?;
+        }
+        search_index.reader.reload()?;
+        Ok(search_index)
+    }
+
+    /// Number of notes in the index.
+    pub fn num_notes(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Re-index whatever is at `path` after it changed on disk: a note, or a
+    /// folder that was created, moved or removed.
+    pub fn update(&self, config: &Config, path: &Path) -> Result<()> {
+        let relative = vault::relative(&self.notes_dir, path);
+        if relative.is_empty() || relative.split('/').any(|part| part.starts_with('.')) {
+            return Ok(());
+        }
+        let is_note = search::is_searchable(path);
+        let is_dir = path.is_dir() || (!path.exists() && path.extension().is_none());
+        if !is_note && !is_dir {
+            return Ok(());
+        }
+
+        let mut writer = self.writer.lock().unwrap();
+        if is_note {
+            writer.delete_term(Term::from_field_text(self.path, &relative));
+            if path.is_file() && !config.is_private(&self.notes_dir, path) {
+                self.add(&mut writer, path)?;
+            }
+        } else {
+            let prefix = format!("{}/", relative);
+            writer.delete_query(Box::new(self.under(&prefix)?))?;
+            if path.is_dir() {
+                for file in vault::files(&self.notes_dir, config) {
+                    if vault::relative(&self.notes_dir, &file).starts_with(&prefix) {
+                        self.add(&mut writer, &file)?;
+                    }
+                }
+            }
+        }
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+
+    /// Search the index as `config`'s viewer sees the vault, optionally
+    /// tolerating typos.
+    pub fn searcher<'a>(&'a self, config: &'a Config, fuzzy: bool) -> IndexSearcher<'a> {
+        IndexSearcher {
+            index: self,
+            config,
+            fuzzy,
+        }
+    }
+
+    fn add(&self, writer: &mut IndexWriter, file: &Path) -> Result<()> {
+        if !search::is_searchable(file) {
+            return Ok(());
+        }
+        let Ok(content) = std::fs::read_to_string(file) else {
+            return Ok(());
+        };
+        let content = comments::strip(&content).into_owned();
+        let title = search::name(file);
+        let relative = vault::relative(&self.notes_dir, file);
+        let mut document = doc!(
+            self.path => relative.clone(),
+            self.name => title.to_lowercase(),
+            self.title => title,
+            self.headings => content
+                .lines()
+                .filter(|line| search::is_document_heading(file, line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            self.body => content,
+        );
+        for tag in Note::parse(relative, &content).tags {
+            document.add_text(self.tag, tag);
+        }
+        writer.add_document(document)?;
+        Ok(())
+    }
+
+    /// Matches every note whose path starts with `prefix`.
+    fn under(&self, prefix: &str) -> Result<RegexQuery> {
+        Ok(RegexQuery::from_pattern(
+            &format!("{}.*", regex::escape(prefix)),
+            self.path,
+        )?)
+    }
+
+    fn ranked(
+        &self,
+        config: &Config,
+        query: &str,
+        scope: Option<&str>,
+        fuzzy: bool,
+    ) -> Result<Vec<(String, f32)>> {
+        let fields = vec![self.title, self.headings, self.body];
+        let mut parser = QueryParser::for_index(&self.index, fields.clone());
+        parser.set_conjunction_by_default();
+        parser.set_field_boost(self.title, TITLE_BOOST);
+        parser.set_field_boost(self.headings, HEADING_BOOST);
+        if fuzzy {
+            for field in fields {
+                parser.set_field_fuzzy(field, false, FUZZY_DISTANCE, true);
+            }
+        }
+        let (mut query, _) = parser.parse_query_lenient(query);
+        if let Some(scope) = scope {
+            let scope = format!("{}/", scope.trim_end_matches('/'));
+            query = Box::new(BooleanQuery::new(vec![
+                (Occur::Must, query),
+                (Occur::Must, Box::new(self.under(&scope)?) as Box<dyn Query>),
+            ]));
+        }
+
+        let top = self
+            .reader
+            .searcher()
+            .search(&query, &TopDocs::with_limit(MAX_RESULTS))?;
+        self.visible(config, top.into_iter().map(|(score, address)| (address, score)))
+    }
+
+    fn named(&self, config: &Config, query: &str) -> Result<Vec<String>> {
+        let query = RegexQuery::from_pattern(&format!(".*{}.*", regex::escape(query)), self.name)?;
+        let found = self.reader.searcher().search(&query, &DocSetCollector)?;
+        let visible = self.visible(config, found.into_iter().map(|address| (address, ())))?;
+        Ok(visible.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Paths of the given documents, keeping what came with each and leaving
+    /// out notes `config`'s viewer can't see.
+    fn visible<T>(
+        &self,
+        config: &Config,
+        docs: impl Iterator<Item = (DocAddress, T)>,
+    ) -> Result<Vec<(String, T)>> {
+        let searcher = self.reader.searcher();
+        let mut paths = Vec::new();
+        for (address, extra) in docs {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let Some(path) = doc.get_first(self.path).and_then(|v| v.as_str()) else {
+                continue;
+            };
+            if !config.is_private(&self.notes_dir, &self.notes_dir.join(path)) {
+                paths.push((path.to_string(), extra));
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// Answers searches from a [`SearchIndex`], best matches first. Queries use
+/// tantivy's syntax: words must all appear, `"quoted phrases"` match in order,
+/// `-word` excludes and `OR` widens.
+pub struct IndexSearcher<'a> {
+    index: &'a SearchIndex,
+    config: &'a Config,
+    fuzzy: bool,
+}
+
+impl Searcher for IndexSearcher<'_> {
+    fn search(&self, query: &str, scope: Option<&str>, page: usize) -> Results {
+        let ranked = match self.index.ranked(self.config, query, scope, self.fuzzy) {
+            Ok(ranked) => ranked,
+            Err(e) => {
+                eprintln!("Search failed: {e}");
+                return Results { matches: Vec::new(), total: 0 };
+            }
+        };
+        let total = ranked.len();
+        let re = self.highlight(query);
+        let matches = search::on_page(ranked, page)
+            .into_iter()
+            .map(|(path, score)| {
+                let content =
+                    std::fs::read_to_string(self.index.notes_dir.join(&path)).unwrap_or_default();
+                let lines = re
+                    .as_ref()
+                    .map(|re| search::excerpts(&comments::strip(&content), re))
+                    .unwrap_or_default();
+                FileMatches { path, score, lines }
+            })
+            .collect();
+        Results { matches, total }
+    }
+
+    fn suggest(&self, query: &str) -> Vec<String> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        match self.index.named(self.config, &query) {
+            Ok(paths) => search::rank_suggestions(&query, paths),
+            Err(e) => {
+                eprintln!("Search failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// The query's words and phrases, ignoring operators and excluded terms.
+    fn highlight(&self, query: &str) -> Option<Regex> {
+        static PART: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r#""([^"]*)"|(\S+)"#).unwrap());
+
+        let mut alternatives = Vec::new();
+        for caps in PART.captures_iter(query) {
+            let text = match (caps.get(1), caps.get(2)) {
+                (Some(phrase), _) => phrase.as_str(),
+                (_, Some(word)) if word.as_str().starts_with('-') => continue,
+                (_, Some(word)) if matches!(word.as_str(), "AND" | "OR" | "NOT") => continue,
+                (_, Some(word)) => word.as_str().rsplit(':').next().unwrap_or_default(),
+                _ => continue,
+            };
+            let words: Vec<&str> = text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .collect();
+            if words.is_empty() {
+                continue;
+            }
+            if caps.get(1).is_some() {
+                let escaped: Vec<String> = words.into_iter().map(regex::escape).collect();
+                alternatives.push(escaped.join(r"\W+"));
+            } else if self.fuzzy {
+                alternatives.extend(words.into_iter().flat_map(one_edit_away));
+            } else {
+                alternatives.extend(words.into_iter().map(regex::escape));
+            }
+        }
+        if alternatives.is_empty() {
+            return None;
+        }
+        RegexBuilder::new(&format!(r"\b(?:{})\b", alternatives.join("|")))
+            .case_insensitive(true)
+            .build()
+            .ok()
+    }
+}
+
+/// Patterns matching `word` with at most one letter added, removed, changed
+/// or swapped with its neighbour, as fuzzy mode matches.
+fn one_edit_away(word: &str) -> Vec<String> {
+    let chars: Vec<String> = word.chars().map(|c| regex::escape(&c.to_string())).collect();
+    let joined = |parts: &[String]| parts.concat();
+    let mut patterns = Vec::new();
+    for i in 0..=chars.len() {
+        let (before, after) = chars.split_at(i);
+        patterns.push(format!(r"{}\w{}", joined(before), joined(after)));
+        if let Some((_, rest)) = after.split_first() {
+            patterns.push(format!("{}{}", joined(before), joined(rest)));
+            patterns.push(format!(r"{}\w{}", joined(before), joined(rest)));
+        }
+        if let [a, b, rest @ ..] = after {
+            patterns.push(format!("{}{b}{a}{}", joined(before), joined(rest)));
+        }
+    }
+    patterns.retain(|p| !p.is_empty());
+    patterns
+}
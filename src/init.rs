@@ -0,0 +1,81 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Command;
+
+use crate::para::{self, Category};
+use crate::vault;
+
+/// Folders a new vault starts with, and the README each gets.
+const FOLDERS: [(&str, Option<Category>, &str); 5] = [
+    (
+        "1 Projects",
+        Some(Category::Projects),
+        "# Projects\n\nShort-term efforts with a goal and a deadline: a launch, a trip, a talk.\nGive each one a folder, and move it to the archive when it's done.\n",
+    ),
+    (
+        "2 Areas",
+        Some(Category::Areas),
+        "# Areas\n\nResponsibilities you keep up over time, with no end date: health,\nfinances, a team you run.\n",
+    ),
+    (
+        "3 Resources",
+        Some(Category::Resources),
+        "# Resources\n\nTopics and reference material you might want again: recipes, tools,\nthings you're learning about.\n",
+    ),
+    (
+        "4 Archive",
+        Some(Category::Archive),
+        "# Archive\n\nFinished projects, and anything else no longer active. Nothing here is\ndeleted, just out of the way.\n",
+    ),
+    (
+        "Inbox",
+        None,
+        "# Inbox\n\nCapture notes here first, then file them under a project, area or\nresource when you have a moment.\n",
+    ),
+];
+
+/// Set up a PARA vault in `notes_dir`: the four category folders and an
+/// inbox, each with a README. Anything already there is left alone, so
+/// running it on an existing vault only fills in what's missing.
+pub fn run(notes_dir: &Path, git: bool) -> Result<()> {
+    if notes_dir.is_file() {
+        bail!("{} is a file", notes_dir.display());
+    }
+    std::fs::create_dir_all(notes_dir)
+        .with_context(|| format!("failed to create {}", notes_dir.display()))?;
+
+    for (name, category, readme) in FOLDERS {
+        // Keep an existing "4. Archive" or "Projects" rather than adding ours
+        let dir = category
+            .and_then(|category| para::category_dir(notes_dir, category))
+            .unwrap_or_else(|| notes_dir.join(name));
+        if !dir.exists() {
+            std::fs::create_dir(&dir).with_context(|| format!("failed to create {name}"))?;
+            println!("created {name}/");
+        }
+        let path = dir.join("README.md");
+        if !path.exists() {
+            std::fs::write(&path, readme).with_context(|| format!("failed to write {}", path.display()))?;
+            println!("created {}", vault::relative(notes_dir, &path));
+        }
+    }
+
+    if git && !notes_dir.join(".git").exists() {
+        let status = Command::new("git")
+            .arg("init")
+            .arg("--quiet")
+            .current_dir(notes_dir)
+            .status()
+            .context("failed to run git")?;
+        if !status.success() {
+            bail!("git init failed");
+        }
+        let ignore = notes_dir.join(".gitignore");
+        if !ignore.exists() {
+            // Thumbnails, embeddings and the like are rebuilt on demand
+            std::fs::write(&ignore, ".para/cache/\n")?;
+        }
+        println!("initialized git repository");
+    }
+    Ok(())
+}
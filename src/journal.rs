@@ -0,0 +1,61 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::config::JournalConfig;
+use crate::templates::{self, Vars};
+
+/// Path of the daily note for `date`, relative to the notes root.
+pub fn daily_path(config: &JournalConfig, date: NaiveDate) -> String {
+    let mut path = String::new();
+    // An invalid pattern falls back to the default layout
+    if write!(path, "{}", date.format(&config.path)).is_err() {
+        path = date.format("Journal/%Y/%m/%Y-%m-%d.md").to_string();
+    }
+    path
+}
+
+/// Human-friendly form of a date, e.g. `May 1, 2024`.
+pub fn display(date: NaiveDate) -> String {
+    date.format("%B %-d, %Y").to_string()
+}
+
+/// The date of a daily note from its path relative to the notes root, or
+/// `None` if the path isn't where the journal would put a daily note.
+pub fn date_of(config: &JournalConfig, path: &str) -> Option<NaiveDate> {
+    let name_pattern = Path::new(&config.path).file_stem()?.to_str()?;
+    let stem = Path::new(path).file_stem()?.to_str()?;
+    let date = NaiveDate::parse_from_str(stem, name_pattern).ok()?;
+    (daily_path(config, date) == path).then_some(date)
+}
+
+/// The dates of the daily notes among `paths`, relative to the notes root.
+pub fn dates<'a>(config: &JournalConfig, paths: impl IntoIterator<Item = &'a String>) -> BTreeSet<NaiveDate> {
+    paths.into_iter().filter_map(|path| date_of(config, path)).collect()
+}
+
+/// Create the daily note for `date` from `_templates/daily.md`, or with just
+/// a heading, unless it exists. Returns its path relative to the notes root
+/// and whether it was created.
+pub fn create(notes_dir: &Path, config: &JournalConfig, date: NaiveDate) -> Result<(String, bool)> {
+    let relative = daily_path(config, date);
+    let path = notes_dir.join(&relative);
+    if path.exists() {
+        return Ok((relative, false));
+    }
+    let title = date.format("%A, %B %-d, %Y").to_string();
+    let content = match templates::load(notes_dir, "daily")? {
+        Some(template) => {
+            let now = date.and_time(chrono::Local::now().time());
+            templates::fill(&template, &Vars { title: &title, now })
+        }
+        None => format!("# {title}\n"),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(&path, content).with_context(|| format!("failed to write {relative}"))?;
+    Ok((relative, true))
+}
@@ -0,0 +1,448 @@
+use anyhow::Result;
+use regex::Captures;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::attachments::percent_decode;
+use crate::config::{Config, JournalConfig};
+use crate::due;
+use crate::frontmatter;
+use crate::journal;
+use crate::note::{self, Link, LinkKind, MD_LINK_RE, WIKILINK_RE};
+use crate::vault;
+
+/// Maps link targets to note paths (relative to the notes root).
+pub struct LinkIndex {
+    /// Lowercased path without `.md` → path.
+    by_path: HashMap<String, String>,
+    /// Lowercased file stem → every path with that stem.
+    by_name: HashMap<String, Vec<String>>,
+    /// When set, `[[YYYY-MM-DD]]` resolves to that day's daily note.
+    journal: Option<JournalConfig>,
+    /// Lowercased file name → every non-markdown file with that name.
+    attachments: HashMap<String, Vec<String>>,
+    /// Lowercased name from a note's `aliases` → every note listing it.
+    aliases: HashMap<String, Vec<String>>,
+}
+
+impl LinkIndex {
+    pub fn new<'a>(paths: impl IntoIterator<Item = &'a str>) -> LinkIndex {
+        let mut by_path = HashMap::new();
+        let mut by_name: HashMap<String, Vec<String>> = HashMap::new();
+        for path in paths {
+            let key = strip_md(path).to_lowercase();
+            let name = key.rsplit('/').next().unwrap_or(&key).to_string();
+            by_path.insert(key, path.to_string());
+            by_name.entry(name).or_default().push(path.to_string());
+        }
+        LinkIndex {
+            by_path,
+            by_name,
+            journal: None,
+            attachments: HashMap::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Resolve an attachment embedded as `![[name]]` in the note at `from`:
+    /// a full vault path, then a file next to the note, then the file with
+    /// that name nearest the vault root.
+    pub fn attachment(&self, from: &str, target: &str) -> Option<String> {
+        let target = target.trim().trim_start_matches('/');
+        let suffix = format!("/{}", target.to_lowercase());
+        let name = target.rsplit('/').next().unwrap_or(target).to_lowercase();
+        let candidates = self.attachments.get(&name)?;
+        let beside = match from.rsplit_once('/') {
+            Some((dir, _)) => format!("{dir}/{target}"),
+            None => target.to_string(),
+        };
+        [target, beside.as_str()]
+            .iter()
+            .find_map(|want| candidates.iter().find(|path| path.eq_ignore_ascii_case(want)))
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .filter(|path| path.to_lowercase().ends_with(&suffix))
+                    .min_by_key(|path| path.matches('/').count())
+            })
+            .cloned()
+    }
+
+    /// The note that lists `name` among its `aliases`, if exactly one does.
+    /// `name` may be an old path, in which case its file name is tried too.
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        let key = strip_md(name.trim().trim_start_matches('/')).to_lowercase();
+        let stem = key.rsplit('/').next().unwrap_or(&key);
+        match self.aliases.get(&key).or_else(|| self.aliases.get(stem))?.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        }
+    }
+
+    /// Whether a note exists at `path`.
+    pub fn contains(&self, path: &str) -> bool {
+        self.by_path.contains_key(&strip_md(path).to_lowercase())
+    }
+
+    /// Every note a wikilink name could refer to.
+    pub fn candidates(&self, name: &str) -> &[String] {
+        self.by_name
+            .get(&strip_md(name.trim()).to_lowercase())
+            .map(|v| v.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Resolve a link written in the note at `from`, or `None` if it is
+    /// broken or ambiguous.
+    pub fn resolve(&self, from: &str, link: &Link) -> Option<String> {
+        match self.lookup(from, link) {
+            Resolution::Found(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Resolve a link written in the note at `from`. Wikilinks match a full
+    /// vault path first, then a file name, then a note's `aliases`; markdown
+    /// links are relative to the linking note.
+    pub fn lookup(&self, from: &str, link: &Link) -> Resolution {
+        match link.kind {
+            LinkKind::Wiki => {
+                let target = strip_md(link.target.trim().trim_start_matches('/')).to_lowercase();
+                if let Some(path) = self.by_path.get(&target) {
+                    return Resolution::Found(path.clone());
+                }
+                // Daily notes count as found even before they're created
+                if let Some(journal) = &self.journal
+                    && let Some(date) = due::parse_date(&target)
+                {
+                    return Resolution::Found(journal::daily_path(journal, date));
+                }
+                match self.candidates(&target) {
+                    [] => match self.aliases.get(&target).map(Vec::as_slice) {
+                        None | Some([]) => Resolution::Missing,
+                        Some([only]) => Resolution::Found(only.clone()),
+                        Some(many) => Resolution::Ambiguous(many.to_vec()),
+                    },
+                    [only] => Resolution::Found(only.clone()),
+                    many => Resolution::Ambiguous(many.to_vec()),
+                }
+            }
+            LinkKind::Markdown => {
+                let joined = if let Some(absolute) = link.target.strip_prefix('/') {
+                    absolute.to_string()
+                } else {
+                    match from.rsplit_once('/') {
+                        Some((dir, _)) => format!("{dir}/{}", link.target),
+                        None => link.target.clone(),
+                    }
+                };
+                normalize(&joined)
+                    .and_then(|path| self.by_path.get(&strip_md(&path).to_lowercase()))
+                    .map(|path| Resolution::Found(path.clone()))
+                    .unwrap_or(Resolution::Missing)
+            }
+        }
+    }
+}
+
+pub enum Resolution {
+    Found(String),
+    /// Several notes share the linked name; sorted by path.
+    Ambiguous(Vec<String>),
+    Missing,
+}
+
+/// Each viewer's [`LinkIndex`], so pages and alias redirects don't reread
+/// every note's frontmatter. Built on first use and dropped by the file
+/// watcher whenever the vault changes.
+pub struct LinkIndexes {
+    notes_dir: PathBuf,
+    /// By the name of the viewer it was built for; private notes differ.
+    by_viewer: RwLock<HashMap<Option<String>, Arc<LinkIndex>>>,
+    /// Bumped on every change, so an index built meanwhile isn't kept.
+    generation: AtomicU64,
+}
+
+impl LinkIndexes {
+    pub fn new(notes_dir: &Path) -> Self {
+        LinkIndexes {
+            notes_dir: notes_dir.to_path_buf(),
+            by_viewer: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// The index of what `config`'s viewer can see.
+    pub fn get(&self, config: &Config) -> Arc<LinkIndex> {
+        let viewer = config.viewer.as_ref().map(|user| user.name.clone());
+        if let Some(index) = self.by_viewer.read().unwrap().get(&viewer) {
+            return index.clone();
+        }
+        let generation = self.generation.load(Ordering::Acquire);
+        let index = Arc::new(index(&self.notes_dir, config));
+        let mut by_viewer = self.by_viewer.write().unwrap();
+        if self.generation.load(Ordering::Acquire) == generation {
+            by_viewer.insert(viewer, index.clone());
+        }
+        index
+    }
+
+    /// Forget every index after notes were added, moved, removed or edited.
+    pub fn refresh(&self) {
+        let mut by_viewer = self.by_viewer.write().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        by_viewer.clear();
+    }
+}
+
+/// Index every markdown note in the vault, the names in their `aliases`,
+/// and the attachments beside them.
+pub fn index(notes_dir: &Path, config: &Config) -> LinkIndex {
+    let (notes, others): (Vec<_>, Vec<_>) = vault::files(notes_dir, config)
+        .into_iter()
+        .partition(|p| p.extension().is_some_and(|e| e == "md"));
+    let paths: Vec<String> = notes.iter().map(|p| vault::relative(notes_dir, p)).collect();
+    let mut index = LinkIndex::new(paths.iter().map(String::as_str));
+    index.journal = Some(config.journal.clone());
+    for (note, path) in notes.iter().zip(&paths) {
+        for alias in frontmatter::read(note).get_list("aliases") {
+            // An old path also answers to its file name, like a note would
+            let key = strip_md(alias.trim().trim_start_matches('/')).to_lowercase();
+            let stem = key.rsplit('/').next().unwrap_or(&key).to_string();
+            for key in [key, stem] {
+                let listed = index.aliases.entry(key).or_default();
+                if !listed.contains(path) {
+                    listed.push(path.clone());
+                }
+            }
+        }
+    }
+    for path in others {
+        let relative = vault::relative(notes_dir, &path);
+        let name = relative.rsplit('/').next().unwrap_or(&relative).to_lowercase();
+        index.attachments.entry(name).or_default().push(relative);
+    }
+    index
+}
+
+/// Expand a moved file or folder (paths relative to the notes root, `to`
+/// already on disk) into the `(old, new)` paths of every note it contains.
+pub fn expand_move(
+    notes_dir: &Path,
+    config: &Config,
+    from: &str,
+    to: &str,
+) -> Vec<(String, String)> {
+    if notes_dir.join(to).is_dir() {
+        let prefix = format!("{to}/");
+        vault::files(notes_dir, config)
+            .iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "md"))
+            .filter_map(|p| {
+                let new = vault::relative(notes_dir, p);
+                let suffix = new.strip_prefix(&prefix)?;
+                Some((format!("{from}/{suffix}"), new))
+            })
+            .collect()
+    } else if from.ends_with(".md") && to.ends_with(".md") {
+        vec![(from.to_string(), to.to_string())]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Update links after notes moved from the first path of each pair to the
+/// second. Links pointing at a moved note are retargeted, and relative
+/// markdown links inside moved notes are adjusted to their new location.
+/// Returns the notes that needed changes; they're only written if `apply`.
+/// Notes in folders `config`'s viewer can't write are left as they are.
+pub fn rewrite(
+    notes_dir: &Path,
+    config: &Config,
+    moves: &[(String, String)],
+    apply: bool,
+) -> Result<Vec<String>> {
+    relink(notes_dir, config, moves, moves, apply)
+}
+
+/// Point every link to the note `from` at the note `to` instead, without
+/// anything having moved. Returns the notes that needed changes.
+pub fn retarget(
+    notes_dir: &Path,
+    config: &Config,
+    from: &str,
+    to: &str,
+    apply: bool,
+) -> Result<Vec<String>> {
+    relink(
+        notes_dir,
+        config,
+        &[],
+        &[(from.to_string(), to.to_string())],
+        apply,
+    )
+}
+
+/// `moved` lists notes whose file changed location; `retargets` lists link
+/// targets to replace.
+fn relink(
+    notes_dir: &Path,
+    config: &Config,
+    moved: &[(String, String)],
+    retargets: &[(String, String)],
+    apply: bool,
+) -> Result<Vec<String>> {
+    let paths: Vec<String> = vault::files(notes_dir, config)
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .map(|p| vault::relative(notes_dir, p))
+        .collect();
+    let old_to_new: HashMap<&str, &str> = retargets
+        .iter()
+        .map(|(o, n)| (o.as_str(), n.as_str()))
+        .collect();
+    let new_to_old: HashMap<&str, &str> = moved
+        .iter()
+        .map(|(o, n)| (n.as_str(), o.as_str()))
+        .collect();
+    let old_of = |path: &str| new_to_old.get(path).copied().unwrap_or(path).to_string();
+
+    // Resolve links as they were written, against the vault before the move
+    let old_paths: Vec<String> = paths.iter().map(|p| old_of(p)).collect();
+    let before = LinkIndex::new(old_paths.iter().map(String::as_str));
+    let after = LinkIndex::new(paths.iter().map(String::as_str));
+
+    let mut changed = Vec::new();
+    for path in &paths {
+        let file = notes_dir.join(path);
+        if !config.for_dir(notes_dir, file.parent().unwrap_or(notes_dir)).can_write() {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let old_self = old_of(path);
+        let moved_self = old_self != *path;
+        let dir = path.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+
+        let wiki = |caps: &Captures| -> String {
+            let written = caps[1].trim();
+            let link = Link {
+                target: written.to_string(),
+                kind: LinkKind::Wiki,
+            };
+            let Some(new) = before
+                .resolve(&old_self, &link)
+                .and_then(|old| old_to_new.get(old.as_str()).copied())
+            else {
+                return caps[0].to_string();
+            };
+            let name = strip_md(new.rsplit('/').next().unwrap_or(new));
+            let replacement = if !written.contains('/') && after.candidates(name).len() == 1 {
+                name
+            } else {
+                strip_md(new)
+            };
+            splice(caps, replacement)
+        };
+        let markdown = |caps: &Captures| -> String {
+            let (written, anchor) = match caps[1].split_once('#') {
+                Some((p, a)) => (p, format!("#{a}")),
+                None => (&caps[1], String::new()),
+            };
+            if written.contains("://") || !written.to_lowercase().ends_with(".md") {
+                return caps[0].to_string();
+            }
+            let link = Link {
+                target: percent_decode(written),
+                kind: LinkKind::Markdown,
+            };
+            let Some(old) = before.resolve(&old_self, &link) else {
+                return caps[0].to_string();
+            };
+            let new = old_to_new.get(old.as_str()).copied().unwrap_or(&old);
+            if new == old && (!moved_self || link.target.starts_with('/')) {
+                return caps[0].to_string();
+            }
+            let relative = relative_path(dir, new);
+            if relative == link.target {
+                return caps[0].to_string();
+            }
+            splice(caps, &format!("{}{anchor}", relative.replace(' ', "%20")))
+        };
+
+        let mut output = String::with_capacity(content.len());
+        let mut in_code = false;
+        for line in content.split_inclusive('\n') {
+            if note::is_fence(line) {
+                in_code = !in_code;
+                output.push_str(line);
+                continue;
+            }
+            if in_code {
+                output.push_str(line);
+                continue;
+            }
+            let line = WIKILINK_RE.replace_all(line, &wiki);
+            output.push_str(&MD_LINK_RE.replace_all(&line, &markdown));
+        }
+
+        if output != content {
+            if apply {
+                std::fs::write(&file, &output)?;
+            }
+            changed.push(path.clone());
+        }
+    }
+    Ok(changed)
+}
+
+/// Replace the first capture group of a match, keeping the rest of the text.
+fn splice(caps: &Captures, replacement: &str) -> String {
+    let whole = caps.get(0).unwrap();
+    let target = caps.get(1).unwrap();
+    let text = whole.as_str();
+    let start = target.start() - whole.start();
+    let end = target.end() - whole.start();
+    format!("{}{replacement}{}", &text[..start], &text[end..])
+}
+
+/// The path to `to` from inside the folder `from_dir`, both relative to the
+/// notes root.
+fn relative_path(from_dir: &str, to: &str) -> String {
+    let from: Vec<&str> = from_dir.split('/').filter(|s| !s.is_empty()).collect();
+    let to_parts: Vec<&str> = to.split('/').collect();
+    let common = from
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts = vec![".."; from.len() - common];
+    parts.extend(&to_parts[common..]);
+    parts.join("/")
+}
+
+fn strip_md(path: &str) -> &str {
+    if path.to_lowercase().ends_with(".md") {
+        &path[..path.len() - 3]
+    } else {
+        path
+    }
+}
+
+/// Collapse `.` and `..` segments; `None` if the path escapes the root.
+fn normalize(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
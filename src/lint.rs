@@ -0,0 +1,108 @@
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::config::{Config, LintConfig};
+use crate::frontmatter;
+use crate::note;
+use crate::vault;
+
+static INLINE_CODE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`[^`]*`").unwrap());
+static BARE_URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?:^|[^(<\["'=])(https?://[^\s)>\]]+)"#).unwrap());
+static EMPTY_ALT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"!\[\s*\]\(").unwrap());
+
+pub struct Issue {
+    /// 1-based line number in the file, frontmatter included.
+    pub line: usize,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+pub struct FileIssues {
+    /// Path relative to the notes root.
+    pub path: String,
+    pub issues: Vec<Issue>,
+}
+
+/// Lint every note in the vault, using each folder's own rule settings.
+/// Only notes with issues are returned.
+pub fn vault(notes_dir: &Path, config: &Config) -> Vec<FileIssues> {
+    vault::files(notes_dir, config)
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .filter_map(|path| {
+            let content = std::fs::read_to_string(path).ok()?;
+            let dir = path.parent().unwrap_or(notes_dir);
+            let issues = check(&content, &config.for_dir(notes_dir, dir).lint);
+            (!issues.is_empty()).then(|| FileIssues {
+                path: vault::relative(notes_dir, path),
+                issues,
+            })
+        })
+        .collect()
+}
+
+/// Lint one note. Frontmatter and fenced code are not checked.
+pub fn check(content: &str, config: &LintConfig) -> Vec<Issue> {
+    let enabled = |rule: &str| !config.disable.iter().any(|r| r == rule);
+
+    let (_, body) = frontmatter::split(content);
+    let offset = content[..content.len() - body.len()].lines().count();
+
+    let mut issues = Vec::new();
+    let mut previous_level = 0;
+    let mut in_code = false;
+    for (i, line) in body.lines().enumerate() {
+        let line_number = offset + i + 1;
+        if note::is_fence(line) {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        if enabled("trailing-whitespace") {
+            let trailing = line.len() - line.trim_end().len();
+            if trailing > 0 && !(trailing == 2 && line.ends_with("  ") && !line.trim().is_empty()) {
+                issues.push(Issue {
+                    line: line_number,
+                    rule: "trailing-whitespace",
+                    message: format!("{trailing} trailing whitespace character(s)"),
+                });
+            }
+        }
+
+        let level = line.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+            if enabled("heading-increment") && previous_level > 0 && level > previous_level + 1 {
+                issues.push(Issue {
+                    line: line_number,
+                    rule: "heading-increment",
+                    message: format!("h{level} follows h{previous_level}"),
+                });
+            }
+            previous_level = level;
+        }
+
+        let prose = INLINE_CODE_RE.replace_all(line, "");
+        if enabled("bare-url") {
+            for caps in BARE_URL_RE.captures_iter(&prose) {
+                issues.push(Issue {
+                    line: line_number,
+                    rule: "bare-url",
+                    message: format!("bare URL {}", &caps[1]),
+                });
+            }
+        }
+        if enabled("image-alt") && EMPTY_ALT_RE.is_match(&prose) {
+            issues.push(Issue {
+                line: line_number,
+                rule: "image-alt",
+                message: "image without alt text".to_string(),
+            });
+        }
+    }
+    issues
+}
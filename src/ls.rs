@@ -0,0 +1,88 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::note::{self, Note};
+use crate::para::Category;
+use crate::tags;
+use crate::vault;
+
+/// Which notes [`collect`] keeps. Every condition given must hold.
+pub struct Filter<'a> {
+    /// Tags the note must all carry, nested tags included.
+    pub tags: &'a [String],
+    /// Only notes modified on or after this day.
+    pub modified_since: Option<NaiveDate>,
+    pub category: Option<Category>,
+}
+
+#[derive(Serialize)]
+pub struct Listed {
+    /// Relative to the notes root.
+    pub path: String,
+    pub title: String,
+    pub category: Option<&'static str>,
+    pub tags: Vec<String>,
+    /// When the file was last modified, in RFC 3339.
+    pub modified: Option<String>,
+}
+
+/// The vault's notes that pass `filter`, sorted by path.
+pub fn collect(notes_dir: &Path, config: &Config, filter: &Filter) -> Vec<Listed> {
+    let mut listed = Vec::new();
+    for path in vault::files(notes_dir, config) {
+        if path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let note = Note::parse(vault::relative(notes_dir, &path), &content);
+        let category = Category::of(&note.path);
+        if filter.category.is_some_and(|wanted| category != Some(wanted)) {
+            continue;
+        }
+        if !filter
+            .tags
+            .iter()
+            .all(|wanted| note.tags.iter().any(|tag| tags::matches(tag, wanted)))
+        {
+            continue;
+        }
+        let modified = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .map(DateTime::<Local>::from);
+        if let Some(since) = filter.modified_since
+            && modified.is_none_or(|modified| modified.date_naive() < since)
+        {
+            continue;
+        }
+
+        listed.push(Listed {
+            title: note::title(&path, &content),
+            category: category.map(|c| c.name()),
+            tags: note.tags,
+            modified: modified.map(|m| m.to_rfc3339()),
+            path: note.path,
+        });
+    }
+    listed
+}
+
+/// Print the notes that pass `filter`, one path per line, or as JSON with
+/// their titles, tags and modification times.
+pub fn run(notes_dir: &Path, config: &Config, filter: &Filter, json: bool) -> Result<()> {
+    let listed = collect(notes_dir, config, filter);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&listed)?);
+    } else {
+        for note in &listed {
+            println!("{}", note.path);
+        }
+    }
+    Ok(())
+}
@@ -1,23 +1,346 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use clap::{Parser, Subcommand};
+use std::io::IsTerminal;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 
+use crate::config::{Config, Vault};
+
+mod access_log;
+mod activity;
+mod archive;
+mod asciidoc;
+mod attachments;
+mod audit;
+mod auth;
+mod callouts;
+mod capture;
+mod comments;
+mod config;
+mod diagrams;
+mod doctor;
+mod due;
+mod exif;
+mod export;
+mod frontmatter;
+mod graph;
+mod index;
+mod init;
+mod journal;
+mod links;
+mod lint;
+mod ls;
+mod merge;
+mod metrics;
+mod note;
+mod og;
+mod order;
+mod para;
+mod projects;
+mod properties;
+mod quickswitch;
+mod recent;
+mod refactor;
+mod render;
+mod review;
+mod sanitize;
+mod schema;
+mod search;
+mod semantic;
 mod serve;
+mod site;
+mod source;
+mod spelling;
+mod stats;
+mod tables;
+mod tags;
+mod tasks;
+mod templates;
+mod thumbs;
+mod toc;
+mod trash;
+mod unfurl;
+mod uploads;
+mod vault;
 
 #[derive(Parser)]
 #[command(name = "para", version, about = "PARA notes web server")]
 struct Cli {
+    /// Path to config file (default: ~/.config/para/config.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Create the PARA folders in a new or existing Notes directory
+    Init {
+        /// Also make the vault a git repository
+        #[arg(long)]
+        git: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Create a note, optionally from a template in `_templates/`
+    New {
+        /// Title of the note, also used for its file name
+        title: String,
+        /// Folder to create it in, relative to the Notes root; may start with
+        /// a PARA category, e.g. `projects/clientx`
+        #[arg(long = "in", default_value = "")]
+        dir: String,
+        /// Template to fill in, by name, e.g. `meeting` for
+        /// `_templates/meeting.md`
+        #[arg(long)]
+        template: Option<String>,
+        /// Open the new note in $VISUAL or $EDITOR
+        #[arg(long)]
+        open: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Save text from stdin, or given with -m, as a new note in the inbox
+    Capture {
+        /// Text to capture instead of reading stdin; repeat for paragraphs
+        #[arg(short, long)]
+        message: Vec<String>,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Create today's daily note if needed and print its path
+    Daily {
+        /// Another day instead, as YYYY-MM-DD
+        #[arg(long)]
+        date: Option<String>,
+        /// Open the note in $VISUAL or $EDITOR
+        #[arg(long)]
+        open: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
     /// Serve Notes directory as a web interface
     Serve {
         /// Port to listen on
         #[arg(long, default_value = "8989")]
         port: u16,
+        /// Address to listen on; `0.0.0.0` for every interface
+        #[arg(long, alias = "bind", default_value = "127.0.0.1")]
+        host: IpAddr,
+        /// Allow listening beyond this machine without `[[users]]` to log in
+        #[arg(long)]
+        insecure_lan: bool,
+        /// Require a login as `name:hash`, with a hash from `para hash-password`; repeat for more accounts
+        #[arg(long)]
+        auth: Vec<String>,
+        /// Refuse every change to the vault, whoever is signed in
+        #[arg(long, conflicts_with = "writable")]
+        read_only: bool,
+        /// Allow changes to the vault, as signed-in users' roles permit, even with `read_only` in the config
+        #[arg(long)]
+        writable: bool,
+        /// Strip scripts and other unsafe HTML from rendered notes (default:
+        /// on unless bound to a loopback address)
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        sanitize: Option<bool>,
+        /// Show what went wrong on error pages: the failing path and the
+        /// chain of errors. For working on para or a vault's config
+        #[arg(long)]
+        dev: bool,
+        /// Override Notes root directory
+        #[arg(long, conflicts_with = "vault")]
+        notes_dir: Option<PathBuf>,
+        /// Vault from `[[vaults]]` to open first (default: the first listed)
+        #[arg(long)]
+        vault: Option<String>,
+        /// Serve every vault in `[[vaults]]` at once, each under `/{name}/`
+        #[arg(long, conflicts_with = "notes_dir")]
+        mount: bool,
+        /// Public URL the vault is shared at, used for social preview tags
+        #[arg(long)]
+        public_url: Option<String>,
+        /// Open the vault in the default browser once the server is up
+        #[arg(long)]
+        open: bool,
+        /// Listen on a Unix socket instead of TCP, as `unix:/run/para.sock`,
+        /// for a reverse proxy on the same machine
+        #[arg(long, conflicts_with_all = ["host", "port", "tls_cert", "open"])]
+        listen: Option<String>,
+        /// PEM certificate chain to serve HTTPS with
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        /// PEM private key for `--tls-cert`
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// Path a reverse proxy serves para under, e.g. `/notes`
+        #[arg(long)]
+        base_path: Option<String>,
+        /// Least severe log messages to show: `error`, `warn`, `info`, `debug`
+        /// or `trace`, or per module, e.g. `para=debug,tantivy=info`
+        #[arg(long, default_value = "info")]
+        log_level: String,
+        /// Log one JSON object per line, for log collectors
+        #[arg(long)]
+        log_json: bool,
+        /// Append a line per request to this file, in Combined Log Format
+        #[arg(long)]
+        access_log: Option<PathBuf>,
+        /// Let pages from this origin, e.g. `https://app.example.com`, call
+        /// the server from the browser; `*` for any. Repeat for more
+        #[arg(long)]
+        cors_origin: Vec<String>,
+    },
+    /// Export every note as structured data, or as a static site
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: export::Format,
+        /// File to write to (default: stdout), or the folder for `html`
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Find and replace across notes and file names
+    Refactor {
+        /// Regex to search for, matched line by line
+        #[arg(long)]
+        find: String,
+        /// Replacement text; `$1` refers to capture groups
+        #[arg(long)]
+        replace: String,
+        /// Only touch files matching these globs, e.g. `1 Projects/clientx/**`
+        #[arg(long, num_args = 1..)]
+        paths: Vec<String>,
+        /// Write the changes (default: print a preview)
+        #[arg(long)]
+        apply: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Merge a note into another and archive it
+    Merge {
+        /// Note that survives, relative to the Notes root
+        keep: String,
+        /// Note merged into it, then moved to the archive
+        other: String,
+        /// How to combine the two bodies
+        #[arg(long, value_enum, default_value = "concat")]
+        mode: merge::Mode,
+        /// Write the changes (default: print a preview)
+        #[arg(long)]
+        apply: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Move a finished project into the archive
+    Archive {
+        /// Project folder or note, relative to the Notes root
+        project: String,
+        /// Write the changes (default: print a preview)
+        #[arg(long)]
+        apply: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Check the vault for broken links and other problems
+    Doctor {
+        /// Also report misspelled words
+        #[arg(long)]
+        spelling: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Check notes against markdown style rules
+    Lint {
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Search notes, printing each matching line as path:line
+    Search {
+        /// What to search for
+        query: String,
+        /// How the query is matched
+        #[arg(long, value_enum, default_value = "words")]
+        mode: search::Mode,
+        /// Only search this folder, relative to the Notes root
+        #[arg(long = "in")]
+        scope: Option<String>,
+        /// Most notes to show
+        #[arg(long, default_value = "20")]
+        limit: usize,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// List notes, optionally filtered, for scripts and other tools
+    Ls {
+        /// Only notes with this tag; repeat to require several
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Only notes modified on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        modified_since: Option<String>,
+        /// Only notes in this category: projects, areas, resources or archive
+        #[arg(long)]
+        category: Option<String>,
+        /// Print JSON with each note's title, category, tags and modification time
+        #[arg(long)]
+        json: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// List open tasks, grouped by the note they're in
+    Tasks {
+        /// Only tasks with this tag, in the task or its note's frontmatter; repeat to require several
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Only tasks due by then: overdue, today, week, or a YYYY-MM-DD date
+        #[arg(long)]
+        due: Option<String>,
+        /// Print JSON with each note's path, project and tasks
+        #[arg(long)]
+        json: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Report note counts, words, links and growth over time
+    Stats {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Override Notes root directory
+        #[arg(long)]
+        notes_dir: Option<PathBuf>,
+    },
+    /// Hash a password, read from stdin, for `serve --auth` or `[[users]]`
+    HashPassword,
+    /// Make an API token and print it with the `[[tokens]]` entry for the config
+    Token {
+        /// Who the token acts as, in access lists and the audit log
+        name: String,
+        /// Let the token change notes, not just read them
+        #[arg(long)]
+        editor: bool,
+    },
+    /// Show changes made through the web server
+    Audit {
+        /// Only show the most recent entries
+        #[arg(long)]
+        limit: Option<usize>,
         /// Override Notes root directory
         #[arg(long)]
         notes_dir: Option<PathBuf>,
@@ -27,17 +350,384 @@ enum Commands {
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    let mut config = Config::load(cli.config.as_deref())?;
+    // `read_only` only governs the server; commands run here change the vault as asked
+    if !matches!(cli.command, Commands::Serve { .. }) {
+        config.read_only = false;
+    }
 
     match cli.command {
-        Commands::Serve { port, notes_dir } => {
+        Commands::Init { git, notes_dir } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            init::run(&root, git)?;
+        }
+        Commands::New {
+            title,
+            dir,
+            template,
+            open,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let note = templates::NewNote {
+                title: &title,
+                dir: &dir,
+                template: template.as_deref(),
+            };
+            let relative = templates::create(&root, &note, chrono::Local::now().naive_local())?;
+            let path = root.join(&relative);
+            println!("{}", path.display());
+            if open {
+                open_in_editor(&path)?;
+            }
+        }
+        Commands::Daily {
+            date,
+            open,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let date = match date {
+                Some(date) => due::parse_date(&date).with_context(|| format!("{date} isn't a YYYY-MM-DD date"))?,
+                None => chrono::Local::now().date_naive(),
+            };
+            let (relative, _) = journal::create(&root, &config.journal, date)?;
+            let path = root.join(&relative);
+            println!("{}", path.display());
+            if open {
+                open_in_editor(&path)?;
+            }
+        }
+        Commands::Capture { message, notes_dir } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let text = if !message.is_empty() {
+                message.join("\n\n")
+            } else if std::io::stdin().is_terminal() {
+                bail!("pipe text into `para capture` or pass it with -m");
+            } else {
+                std::io::read_to_string(std::io::stdin())?
+            };
+            let relative = capture::run(&root, &text, chrono::Local::now().naive_local())?;
+            println!("{}", root.join(relative).display());
+        }
+        Commands::Serve {
+            port,
+            host,
+            insecure_lan,
+            auth,
+            read_only,
+            writable,
+            sanitize,
+            dev,
+            notes_dir,
+            vault,
+            mount,
+            public_url,
+            open,
+            listen,
+            tls_cert,
+            tls_key,
+            base_path,
+            log_level,
+            log_json,
+            access_log,
+            cors_origin,
+        } => {
+            serve::init_logging(&log_level, log_json)?;
+            let vaults = serve_vaults(&config, notes_dir, vault.as_deref())?;
+            for vault in &vaults {
+                if !vault.path.is_dir() {
+                    bail!(
+                        "{} doesn't exist; run `para init` to create a vault there",
+                        vault.path.display()
+                    );
+                }
+            }
+            if read_only {
+                config.read_only = true;
+            } else if writable {
+                config.read_only = false;
+            }
+            for spec in &auth {
+                config.users.push(auth::parse_user(spec)?);
+            }
+            let listen = match listen {
+                Some(spec) => serve::Listen::parse(&spec)?,
+                None => serve::Listen::Tcp(SocketAddr::new(host, port)),
+            };
+            // Who reaches a socket is up to its permissions and the proxy in front
+            let unix = matches!(listen, serve::Listen::Unix(_));
+            if !unix && !host.is_loopback() && config.users.is_empty() && config.tokens.is_empty() {
+                if !insecure_lan {
+                    bail!(
+                        "--host {host} would let anyone on the network read and edit your notes; \
+                         require a login with --auth or [[users]] in the config, or pass --insecure-lan"
+                    );
+                }
+                tracing::warn!("Serving on {host} without a login; anyone on the network can read and edit your notes");
+            }
+            for user in config.users.iter().filter(|user| !auth::is_hash(&user.password)) {
+                tracing::warn!(
+                    "User {}'s password is stored as plain text, not a hash; replace it with one from `para hash-password`",
+                    user.name
+                );
+            }
+            for token in config.tokens.iter().filter(|token| token.id.is_empty()) {
+                tracing::warn!(
+                    "Token {} has no id, so every bearer login is checked against it; replace it with one from `para token`",
+                    token.name
+                );
+            }
+            let sanitize = sanitize.unwrap_or(unix || !host.is_loopback());
+            let tls = tls_cert.zip(tls_key).map(|(cert, key)| serve::Tls { cert, key });
+            serve::run_server(
+                vaults,
+                mount,
+                listen,
+                sanitize,
+                dev,
+                public_url,
+                open,
+                tls,
+                base_path,
+                access_log,
+                cors_origin,
+                config,
+            )
+            .await?;
+        }
+        Commands::Export {
+            format,
+            out,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            export::run(&root, &config, format, out.as_deref())?;
+        }
+        Commands::Refactor {
+            find,
+            replace,
+            paths,
+            apply,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let refactor = refactor::Refactor {
+                find: &find,
+                replace: &replace,
+                paths: &paths,
+                apply,
+            };
+            refactor::run(&root, &config, &refactor)?;
+        }
+        Commands::Merge {
+            keep,
+            other,
+            mode,
+            apply,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let merge = merge::Merge {
+                keep: &keep,
+                other: &other,
+                mode,
+                apply,
+            };
+            merge::run(&root, &config, &merge)?;
+        }
+        Commands::Archive {
+            project,
+            apply,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            archive::run(&root, &config, &project, apply)?;
+        }
+        Commands::Doctor {
+            spelling,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let issues = doctor::run(&root, &config, &doctor::Checks { spelling })?;
+            if issues > 0 {
+                eprintln!("{issues} issue(s) found");
+                std::process::exit(1);
+            }
+        }
+        Commands::Lint { notes_dir } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let files = lint::vault(&root, &config);
+            let mut count = 0;
+            for file in &files {
+                for issue in &file.issues {
+                    println!("{}:{}: {} {}", file.path, issue.line, issue.rule, issue.message);
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                eprintln!("{count} issue(s) found");
+                std::process::exit(1);
+            }
+        }
+        Commands::Search {
+            query,
+            mode,
+            scope,
+            limit,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            search::run(&root, &config, &query, mode, scope.as_deref(), limit)?;
+        }
+        Commands::Ls {
+            tag,
+            modified_since,
+            category,
+            json,
+            notes_dir,
+        } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let modified_since = modified_since
+                .map(|date| due::parse_date(&date).with_context(|| format!("{date} isn't a YYYY-MM-DD date")))
+                .transpose()?;
+            let category = category
+                .map(|name| {
+                    para::Category::from_dir_name(&name).with_context(|| {
+                        format!("{name} isn't a category; use projects, areas, resources or archive")
+                    })
+                })
+                .transpose()?;
+            let filter = ls::Filter {
+                tags: &tag,
+                modified_since,
+                category,
+            };
+            ls::run(&root, &config, &filter, json)?;
+        }
+        Commands::Tasks {
+            tag,
+            due,
+            json,
+            notes_dir,
+        } => {
             let root = resolve_notes_dir(notes_dir)?;
-            serve::run_server(root, port).await?;
+            let due = due
+                .map(|due| {
+                    tasks::Due::parse(&due)
+                        .with_context(|| format!("{due} isn't a due filter; use overdue, today, week or a YYYY-MM-DD date"))
+                })
+                .transpose()?;
+            let filter = tasks::Filter { tags: &tag, due };
+            tasks::run(&root, &config, &filter, chrono::Local::now().date_naive(), json)?;
+        }
+        Commands::Stats { json, notes_dir } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            stats::run(&root, &config, json)?;
+        }
+        Commands::HashPassword => {
+            if std::io::stdin().is_terminal() {
+                eprint!("Password: ");
+            }
+            let mut password = String::new();
+            std::io::stdin().read_line(&mut password)?;
+            let password = password.trim_end_matches(['\r', '\n']);
+            if password.is_empty() {
+                bail!("no password given");
+            }
+            println!("{}", auth::hash_password(password)?);
+        }
+        Commands::Token { name, editor } => {
+            let (token, id, hash) = auth::new_token()?;
+            println!("Token (shown once; send it as `Authorization: Bearer <token>`):");
+            println!("{token}");
+            println!();
+            println!("Add to the config:");
+            println!("[[tokens]]");
+            println!("name = {}", toml::Value::from(name));
+            println!("id = \"{id}\"");
+            println!("hash = \"{hash}\"");
+            if editor {
+                println!("role = \"editor\"");
+            }
+        }
+        Commands::Audit { limit, notes_dir } => {
+            let root = resolve_notes_dir(notes_dir)?;
+            let entries = audit::read(&root);
+            let skip = limit.map_or(0, |n| entries.len().saturating_sub(n));
+            for entry in &entries[skip..] {
+                let mut line = format!(
+                    "{} {} {} {}",
+                    entry.time,
+                    entry.user.as_deref().unwrap_or("-"),
+                    entry.action.as_str(),
+                    entry.path
+                );
+                if let Some(detail) = &entry.detail {
+                    line.push_str(&format!(" ({detail})"));
+                }
+                println!("{line}");
+            }
         }
     }
 
     Ok(())
 }
 
+/// Open a note in the user's editor and wait for it to close.
+fn open_in_editor(path: &std::path::Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .context("set $VISUAL or $EDITOR to open notes")?;
+    std::process::Command::new(&editor)
+        .arg(path)
+        .status()
+        .with_context(|| format!("failed to run {editor}"))?;
+    Ok(())
+}
+
+/// The vaults for `para serve`, with the one it opens first. `--notes-dir`,
+/// or a config without `[[vaults]]`, serves a single vault.
+fn serve_vaults(config: &Config, notes_dir: Option<PathBuf>, name: Option<&str>) -> Result<Vec<Vault>> {
+    if notes_dir.is_some() || config.vaults.is_empty() {
+        if let Some(name) = name {
+            bail!("no vault named {name}; list vaults under [[vaults]] in the config");
+        }
+        let path = resolve_notes_dir(notes_dir)?;
+        let name = serve::vault_name(&path);
+        return Ok(vec![Vault { name, path }]);
+    }
+
+    let mut vaults: Vec<Vault> = config
+        .vaults
+        .iter()
+        .map(|vault| {
+            let path = match vault.path.strip_prefix("~") {
+                Ok(rest) => PathBuf::from(std::env::var("HOME")?).join(rest),
+                Err(_) => vault.path.clone(),
+            };
+            Ok(Vault {
+                name: vault.name.clone(),
+                path,
+            })
+        })
+        .collect::<Result<_>>()?;
+    for (i, vault) in vaults.iter().enumerate() {
+        if vaults[..i].iter().any(|other| other.name == vault.name) {
+            bail!("two vaults are named {}", vault.name);
+        }
+    }
+    if let Some(name) = name {
+        let index = vaults
+            .iter()
+            .position(|vault| vault.name == name)
+            .with_context(|| format!("no vault named {name} under [[vaults]]"))?;
+        let first = vaults.remove(index);
+        vaults.insert(0, first);
+    }
+    Ok(vaults)
+}
+
 fn resolve_notes_dir(notes_dir: Option<PathBuf>) -> Result<PathBuf> {
     if let Some(path) = notes_dir {
         return Ok(path);
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::frontmatter;
+use crate::links;
+use crate::note;
+use crate::para::{self, Category};
+use crate::vault;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Mode {
+    /// Append the other note's body after the surviving one
+    Concat,
+    /// Combine sections with the same heading, appending the rest
+    Interleave,
+}
+
+pub struct Merge<'a> {
+    /// Relative path of the note that survives.
+    pub keep: &'a str,
+    /// Relative path of the note merged into it and archived.
+    pub other: &'a str,
+    pub mode: Mode,
+    /// Write the changes instead of only printing them.
+    pub apply: bool,
+}
+
+/// Merge `other` into `keep`: bodies are combined, frontmatter unioned, links
+/// to `other` redirected to `keep`, and `other` moved into the archive.
+pub fn run(notes_dir: &Path, config: &Config, merge: &Merge) -> Result<()> {
+    let keep = merge.keep.trim_start_matches('/');
+    let other = merge.other.trim_start_matches('/');
+    if keep == other {
+        bail!("can't merge a note into itself");
+    }
+    let keep_content = std::fs::read_to_string(notes_dir.join(keep))
+        .with_context(|| format!("failed to read {keep}"))?;
+    let other_content = std::fs::read_to_string(notes_dir.join(other))
+        .with_context(|| format!("failed to read {other}"))?;
+
+    let (mut keep_meta, keep_body) = frontmatter::parse(&keep_content);
+    let (other_meta, other_body) = frontmatter::parse(&other_content);
+    keep_meta.merge(&other_meta);
+    let body = match merge.mode {
+        Mode::Concat => format!("{}\n\n{}", keep_body.trim_end(), other_body.trim_start()),
+        Mode::Interleave => interleave(keep_body, other_body),
+    };
+    let merged = format!("{}{}\n", keep_meta.to_block(), body.trim_end());
+
+    let Some(archive_dir) = para::category_dir(notes_dir, Category::Archive) else {
+        bail!("no archive folder found to move {other} into");
+    };
+    let file_name = Path::new(other)
+        .file_name()
+        .context("merged note has no file name")?;
+    let archived = vault::relative(notes_dir, &archive_dir.join(file_name));
+    if notes_dir.join(&archived).exists() {
+        bail!("{archived} already exists");
+    }
+
+    if !merge.apply {
+        let relinked = links::retarget(notes_dir, config, other, keep, false)?;
+        println!("--- {keep}");
+        print!("{merged}");
+        println!();
+        for path in &relinked {
+            println!("relink {path}");
+        }
+        println!("archive {other} -> {archived}");
+        println!("(dry run; pass --apply to write)");
+        return Ok(());
+    }
+
+    std::fs::write(notes_dir.join(keep), merged)
+        .with_context(|| format!("failed to write {keep}"))?;
+    let relinked = links::retarget(notes_dir, config, other, keep, true)?;
+    std::fs::rename(notes_dir.join(other), notes_dir.join(&archived))?;
+    // Fix relative links inside the archived note itself
+    links::rewrite(
+        notes_dir,
+        config,
+        &[(other.to_string(), archived.clone())],
+        true,
+    )?;
+
+    println!(
+        "Merged {other} into {keep}, updated links in {} note(s), archived as {archived}",
+        relinked.len()
+    );
+    Ok(())
+}
+
+/// A heading line (if any) and the lines up to the next heading.
+struct Section<'a> {
+    heading: Option<&'a str>,
+    lines: Vec<&'a str>,
+}
+
+/// Split a body at its headings, ignoring `#` lines inside code blocks.
+fn sections(body: &str) -> Vec<Section<'_>> {
+    let mut sections = vec![Section {
+        heading: None,
+        lines: Vec::new(),
+    }];
+    let mut in_code = false;
+    for line in body.lines() {
+        if note::is_fence(line) {
+            in_code = !in_code;
+        }
+        let is_heading =
+            !in_code && line.starts_with('#') && line.trim_start_matches('#').starts_with(' ');
+        if is_heading {
+            sections.push(Section {
+                heading: Some(line),
+                lines: Vec::new(),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            section.lines.push(line);
+        }
+    }
+    sections
+}
+
+fn heading_key(heading: Option<&str>) -> Option<String> {
+    heading.map(|h| h.trim_start_matches('#').trim().to_lowercase())
+}
+
+/// Append each section of `other` to the section of `keep` with the same
+/// heading text; sections only `other` has go at the end.
+fn interleave(keep: &str, other: &str) -> String {
+    let mut merged = sections(keep);
+    for section in sections(other) {
+        let key = heading_key(section.heading);
+        match merged.iter_mut().find(|s| heading_key(s.heading) == key) {
+            Some(existing) => {
+                let mut incoming: Vec<&str> = section
+                    .lines
+                    .into_iter()
+                    .skip_while(|l| l.trim().is_empty())
+                    .collect();
+                while incoming.last().is_some_and(|l| l.trim().is_empty()) {
+                    incoming.pop();
+                }
+                if incoming.is_empty() {
+                    continue;
+                }
+                while existing.lines.last().is_some_and(|l| l.trim().is_empty()) {
+                    existing.lines.pop();
+                }
+                if !existing.lines.is_empty() {
+                    existing.lines.push("");
+                }
+                existing.lines.extend(incoming);
+                existing.lines.push("");
+            }
+            None => {
+                if let Some(last) = merged.last_mut()
+                    && last.lines.last().is_some_and(|l| !l.trim().is_empty())
+                {
+                    last.lines.push("");
+                }
+                merged.push(section);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for section in merged {
+        if let Some(heading) = section.heading {
+            out.push_str(heading);
+            out.push('\n');
+        }
+        for line in section.lines {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
@@ -0,0 +1,101 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// What `para serve` reports at `/metrics`, for every vault it serves.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    websocket_clients: IntGaugeVec,
+    watcher_events: IntCounterVec,
+    indexed_notes: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new("para_http_requests_total", "HTTP requests answered"),
+            &["vault", "route", "method", "status"],
+        )
+        .unwrap();
+        let latency = HistogramVec::new(
+            HistogramOpts::new(
+                "para_http_request_duration_seconds",
+                "Time to answer an HTTP request, up to its headers",
+            ),
+            &["vault", "route", "method"],
+        )
+        .unwrap();
+        let websocket_clients = IntGaugeVec::new(
+            Opts::new("para_websocket_clients", "Pages connected for live reload"),
+            &["vault"],
+        )
+        .unwrap();
+        let watcher_events = IntCounterVec::new(
+            Opts::new("para_watcher_events_total", "File changes the watcher has handled"),
+            &["vault"],
+        )
+        .unwrap();
+        let indexed_notes = IntGaugeVec::new(
+            Opts::new("para_indexed_notes", "Notes in the search index"),
+            &["vault"],
+        )
+        .unwrap();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(websocket_clients.clone())).unwrap();
+        registry.register(Box::new(watcher_events.clone())).unwrap();
+        registry.register(Box::new(indexed_notes.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            latency,
+            websocket_clients,
+            watcher_events,
+            indexed_notes,
+        }
+    }
+
+    /// Count a request to `route`, the pattern it matched rather than the
+    /// path, so note names stay out of the metrics.
+    pub fn request(&self, vault: &str, route: &str, method: &str, status: u16, elapsed: Duration) {
+        self.requests
+            .with_label_values(&[vault, route, method, &status.to_string()])
+            .inc();
+        self.latency
+            .with_label_values(&[vault, route, method])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn websocket_opened(&self, vault: &str) {
+        self.websocket_clients.with_label_values(&[vault]).inc();
+    }
+
+    pub fn websocket_closed(&self, vault: &str) {
+        self.websocket_clients.with_label_values(&[vault]).dec();
+    }
+
+    pub fn watcher_event(&self, vault: &str) {
+        self.watcher_events.with_label_values(&[vault]).inc();
+    }
+
+    pub fn set_indexed_notes(&self, vault: &str, notes: u64) {
+        self.indexed_notes
+            .with_label_values(&[vault])
+            .set(notes.try_into().unwrap_or(i64::MAX));
+    }
+
+    /// Everything in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = Vec::new();
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut out);
+        String::from_utf8(out).unwrap_or_default()
+    }
+}
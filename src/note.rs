@@ -0,0 +1,213 @@
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::attachments::percent_decode;
+use crate::frontmatter::{self, Frontmatter};
+use crate::search;
+
+pub(crate) static WIKILINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!?\[\[([^\]|#]*)(#[^\]|]*)?(?:\|[^\]]*)?\]\]").unwrap());
+pub(crate) static MD_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[[^\]]*\]\(\s*<?([^)>\s]+)>?(?:\s+\x22[^\x22]*\x22)?\s*\)").unwrap()
+});
+static INLINE_TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\s)#([\p{L}\p{N}_][\p{L}\p{N}_/-]*)").unwrap());
+/// `📅 2024-07-01`, `@due(2024-07-01)`, or `due: 2024-07-01` inside a task.
+static TASK_DUE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:📅\s*|@due\(\s*|\bdue::?\s*)(\d{4}-\d{2}-\d{2})").unwrap()
+});
+static TASK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*[-*+] \[([ xX])\] (.*)$").unwrap());
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `[[Note]]` or `![[Note]]`, resolved by name.
+    Wiki,
+    /// `[text](path.md)`, resolved relative to the linking note.
+    Markdown,
+}
+
+/// An outgoing link to another note, as written.
+#[derive(Clone)]
+pub struct Link {
+    pub target: String,
+    pub kind: LinkKind,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Task {
+    /// 1-based line number in the file, frontmatter included.
+    pub line: usize,
+    pub text: String,
+    pub done: bool,
+    /// Due date as written (`YYYY-MM-DD`), if the task has one.
+    pub due: Option<String>,
+}
+
+impl Task {
+    /// Inline `#tags` written in the task itself.
+    pub fn tags(&self) -> Vec<String> {
+        INLINE_TAG_RE
+            .captures_iter(&self.text)
+            .map(|caps| caps[1].trim_end_matches('/').to_string())
+            .filter(|tag| !tag.chars().all(|c| c.is_ascii_digit()))
+            .collect()
+    }
+}
+
+/// A markdown note broken into the parts other features query.
+pub struct Note {
+    /// Path relative to the notes root.
+    pub path: String,
+    pub frontmatter: Frontmatter,
+    pub body: String,
+    pub tags: Vec<String>,
+    pub links: Vec<Link>,
+    pub tasks: Vec<Task>,
+}
+
+impl Note {
+    pub fn parse(path: String, content: &str) -> Note {
+        let (frontmatter, body) = frontmatter::parse(content);
+        let prose = strip_code(body);
+
+        let mut tags: Vec<String> = frontmatter
+            .get_list("tags")
+            .into_iter()
+            .map(|t| t.trim_start_matches('#').to_string())
+            .collect();
+        for caps in INLINE_TAG_RE.captures_iter(&prose) {
+            let tag = caps[1].trim_end_matches('/');
+            // Pure numbers are issue references and headings, not tags
+            if tag.chars().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            tags.push(tag.to_string());
+        }
+        let mut seen = std::collections::HashSet::new();
+        tags.retain(|t| seen.insert(t.to_lowercase()));
+
+        let mut links = Vec::new();
+        for caps in WIKILINK_RE.captures_iter(&prose) {
+            let target = caps[1].trim();
+            if !target.is_empty() {
+                links.push(Link {
+                    target: target.to_string(),
+                    kind: LinkKind::Wiki,
+                });
+            }
+        }
+        for caps in MD_LINK_RE.captures_iter(&prose) {
+            let target = caps[1].split('#').next().unwrap_or("");
+            if target.contains("://") || target.starts_with("mailto:") {
+                continue;
+            }
+            if target.to_lowercase().ends_with(".md") {
+                links.push(Link {
+                    target: percent_decode(target),
+                    kind: LinkKind::Markdown,
+                });
+            }
+        }
+
+        let mut tasks = Vec::new();
+        let mut in_code = false;
+        for (i, line) in content.lines().enumerate() {
+            if is_fence(line) {
+                in_code = !in_code;
+                continue;
+            }
+            if in_code {
+                continue;
+            }
+            if let Some(caps) = TASK_RE.captures(line) {
+                let text = caps[2].trim();
+                tasks.push(Task {
+                    line: i + 1,
+                    text: text.to_string(),
+                    done: &caps[1] != " ",
+                    due: TASK_DUE_RE.captures(text).map(|c| c[1].to_string()),
+                });
+            }
+        }
+
+        Note {
+            path,
+            frontmatter,
+            body: body.to_string(),
+            tags,
+            links,
+            tasks,
+        }
+    }
+
+    /// Read and parse a note from disk.
+    pub fn load(notes_dir: &std::path::Path, path: &std::path::Path) -> Option<Note> {
+        let content = std::fs::read_to_string(path).ok()?;
+        Some(Note::parse(
+            crate::vault::relative(notes_dir, path),
+            &content,
+        ))
+    }
+}
+
+/// What a note is called on its page and in the sidebar: its `title:`, else
+/// its first `# heading`, else its file name.
+pub fn title(path: &Path, content: &str) -> String {
+    let (frontmatter, body) = frontmatter::parse(content);
+    frontmatter
+        .get_str("title")
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .or_else(|| first_heading(body))
+        .unwrap_or_else(|| search::name(path))
+}
+
+/// The [`title`] of a note on disk.
+pub fn read_title(path: &Path) -> String {
+    std::fs::read_to_string(path)
+        .map(|content| title(path, &content))
+        .unwrap_or_else(|_| search::name(path))
+}
+
+/// The text of the first level-one ATX heading outside code blocks.
+fn first_heading(body: &str) -> Option<String> {
+    let mut in_code = false;
+    for line in body.lines() {
+        if is_fence(line) {
+            in_code = !in_code;
+        } else if !in_code && let Some(text) = line.strip_prefix("# ") {
+            let text = text.trim().trim_end_matches('#').trim_end();
+            if !text.is_empty() {
+                return Some(text.to_string());
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+/// Remove fenced code blocks and inline code spans so their contents aren't
+/// mistaken for tags or links. Line structure is preserved.
+pub(crate) fn strip_code(body: &str) -> String {
+    static INLINE_CODE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`[^`\n]*`").unwrap());
+
+    let mut out = String::with_capacity(body.len());
+    let mut in_code = false;
+    for line in body.lines() {
+        if is_fence(line) {
+            in_code = !in_code;
+        } else if !in_code {
+            out.push_str(&INLINE_CODE_RE.replace_all(line, ""));
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,139 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::Result;
+
+use crate::serve::{UBUNTU_MONO_BOLD, UBUNTU_MONO_REGULAR};
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const MARGIN: f32 = 80.0;
+const TITLE_SIZE: f32 = 72.0;
+const VAULT_SIZE: f32 = 36.0;
+const MAX_TITLE_LINES: usize = 4;
+
+const BACKGROUND: [u8; 3] = [0xff, 0xff, 0xff];
+const TITLE_COLOR: [u8; 3] = [0x0f, 0x0c, 0x0a];
+const SUBTITLE_COLOR: [u8; 3] = [0x2f, 0x29, 0x25];
+const ACCENT_COLOR: [u8; 3] = [0xff, 0x66, 0x00];
+
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        let mut pixels = Vec::with_capacity((WIDTH * HEIGHT * 3) as usize);
+        for _ in 0..WIDTH * HEIGHT {
+            pixels.extend_from_slice(&BACKGROUND);
+        }
+        Self { pixels }
+    }
+
+    fn blend(&mut self, x: i32, y: i32, color: [u8; 3], coverage: f32) {
+        if x < 0 || y < 0 || x >= WIDTH as i32 || y >= HEIGHT as i32 {
+            return;
+        }
+        let idx = ((y as u32 * WIDTH + x as u32) * 3) as usize;
+        let alpha = coverage.clamp(0.0, 1.0);
+        for (i, channel) in color.iter().enumerate() {
+            let existing = self.pixels[idx + i] as f32;
+            self.pixels[idx + i] = (existing + (*channel as f32 - existing) * alpha).round() as u8;
+        }
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: [u8; 3]) {
+        for py in y..(y + h).min(HEIGHT) {
+            for px in x..(x + w).min(WIDTH) {
+                self.blend(px as i32, py as i32, color, 1.0);
+            }
+        }
+    }
+
+    fn draw_text(&mut self, font: &FontRef, size: f32, x: f32, baseline: f32, text: &str, color: [u8; 3]) {
+        let scaled = font.as_scaled(PxScale::from(size));
+        let mut caret = x;
+        for c in text.chars() {
+            let mut glyph = scaled.scaled_glyph(c);
+            glyph.position = point(caret, baseline);
+            caret += scaled.h_advance(glyph.id);
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    self.blend(
+                        bounds.min.x as i32 + gx as i32,
+                        bounds.min.y as i32 + gy as i32,
+                        color,
+                        coverage,
+                    );
+                });
+            }
+        }
+    }
+
+    fn into_png(self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut out, WIDTH, HEIGHT);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&self.pixels)?;
+        }
+        Ok(out)
+    }
+}
+
+/// Render a social preview card for a note as a PNG.
+pub fn render_card(title: &str, vault: &str) -> Result<Vec<u8>> {
+    let bold = FontRef::try_from_slice(UBUNTU_MONO_BOLD)?;
+    let regular = FontRef::try_from_slice(UBUNTU_MONO_REGULAR)?;
+
+    let mut canvas = Canvas::new();
+    canvas.fill_rect(0, HEIGHT - 16, WIDTH, 16, ACCENT_COLOR);
+
+    let title_font = bold.as_scaled(PxScale::from(TITLE_SIZE));
+    let line_height = title_font.height() + title_font.line_gap();
+    let max_chars = ((WIDTH as f32 - MARGIN * 2.0) / title_font.h_advance(title_font.glyph_id('M')))
+        .floor() as usize;
+
+    let mut baseline = MARGIN + title_font.ascent();
+    for line in wrap_title(title, max_chars) {
+        canvas.draw_text(&bold, TITLE_SIZE, MARGIN, baseline, &line, TITLE_COLOR);
+        baseline += line_height;
+    }
+
+    canvas.fill_rect(MARGIN as u32, HEIGHT - 150, 80, 6, ACCENT_COLOR);
+    canvas.draw_text(&regular, VAULT_SIZE, MARGIN, HEIGHT as f32 - 80.0, vault, SUBTITLE_COLOR);
+
+    canvas.into_png()
+}
+
+fn wrap_title(title: &str, max_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in title.split_whitespace() {
+        let word_len = word.chars().count();
+        let current_len = current.chars().count();
+        if current.is_empty() {
+            current = word.chars().take(max_chars).collect();
+        } else if current_len + 1 + word_len <= max_chars {
+            current.push(' ');
+            current.push_str(word);
+        } else {
+            lines.push(std::mem::take(&mut current));
+            current = word.chars().take(max_chars).collect();
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > MAX_TITLE_LINES {
+        lines.truncate(MAX_TITLE_LINES);
+        let last = lines.last_mut().unwrap();
+        let keep = last.chars().count().min(max_chars.saturating_sub(3));
+        *last = format!("{}...", last.chars().take(keep).collect::<String>());
+    }
+
+    lines
+}
@@ -0,0 +1,100 @@
+use std::cmp::Ordering;
+use std::fs::DirEntry;
+use std::path::{Path, PathBuf};
+
+use crate::config::SortOrder;
+use crate::frontmatter;
+
+/// Sort directory entries for display.
+///
+/// A `.para-order` file in the directory (one file or folder name per line)
+/// puts the listed entries first, in that order. Otherwise entries with an
+/// `order:` or `weight:` frontmatter key (read from a folder's README.md) come
+/// first by ascending weight. Everything else follows the folder's configured
+/// sort order.
+///
+/// Entries marked `pinned: true` are floated above all of the above.
+pub fn sort_entries(dir: &Path, entries: &mut Vec<DirEntry>, sort: SortOrder) {
+    sort_unpinned(dir, entries, sort);
+
+    let (mut pinned, rest): (Vec<_>, Vec<_>) = entries.drain(..).partition(is_pinned);
+    pinned.extend(rest);
+    *entries = pinned;
+}
+
+/// Whether a note (or a folder, via its README.md) has `pinned: true`.
+pub fn is_pinned(entry: &DirEntry) -> bool {
+    entry_note(entry).is_some_and(|note| frontmatter::read(&note).get_bool("pinned") == Some(true))
+}
+
+fn sort_unpinned(dir: &Path, entries: &mut Vec<DirEntry>, sort: SortOrder) {
+    if let Some(order) = read_order_file(dir) {
+        let position = |e: &DirEntry| {
+            let name = e.file_name().to_string_lossy().to_string();
+            order
+                .iter()
+                .position(|o| *o == name || name.strip_suffix(".md") == Some(o.as_str()))
+        };
+        entries.sort_by(|a, b| match (position(a), position(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => fallback_cmp(a, b, sort),
+        });
+        return;
+    }
+
+    let mut keyed: Vec<(Option<f64>, DirEntry)> =
+        entries.drain(..).map(|e| (entry_weight(&e), e)).collect();
+    keyed.sort_by(|(wa, a), (wb, b)| {
+        let by_weight = match (wa, wb) {
+            (Some(x), Some(y)) => x.partial_cmp(y).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        by_weight.then_with(|| fallback_cmp(a, b, sort))
+    });
+    entries.extend(keyed.into_iter().map(|(_, e)| e));
+}
+
+fn fallback_cmp(a: &DirEntry, b: &DirEntry, sort: SortOrder) -> Ordering {
+    match sort {
+        SortOrder::Name => a.file_name().cmp(&b.file_name()),
+        SortOrder::NameDesc => b.file_name().cmp(&a.file_name()),
+        SortOrder::Modified => {
+            let modified = |e: &DirEntry| e.metadata().and_then(|m| m.modified()).ok();
+            modified(b)
+                .cmp(&modified(a))
+                .then_with(|| a.file_name().cmp(&b.file_name()))
+        }
+    }
+}
+
+fn read_order_file(dir: &Path) -> Option<Vec<String>> {
+    let content = std::fs::read_to_string(dir.join(".para-order")).ok()?;
+    Some(
+        content
+            .lines()
+            .map(|l| l.trim().trim_end_matches('/').to_string())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect(),
+    )
+}
+
+/// The note holding an entry's frontmatter: the file itself, or a folder's README.md.
+fn entry_note(entry: &DirEntry) -> Option<PathBuf> {
+    let path = entry.path();
+    if entry.file_type().is_ok_and(|t| t.is_dir()) {
+        Some(path.join("README.md"))
+    } else if path.extension().is_some_and(|ext| ext == "md") {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn entry_weight(entry: &DirEntry) -> Option<f64> {
+    let fm = frontmatter::read(&entry_note(entry)?);
+    fm.get_f64("order").or_else(|| fm.get_f64("weight"))
+}
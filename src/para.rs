@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+/// The four top-level PARA categories.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+    Projects,
+    Areas,
+    Resources,
+    Archive,
+}
+
+impl Category {
+    /// In PARA order.
+    pub const ALL: [Category; 4] = [
+        Category::Projects,
+        Category::Areas,
+        Category::Resources,
+        Category::Archive,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Projects => "Projects",
+            Category::Areas => "Areas",
+            Category::Resources => "Resources",
+            Category::Archive => "Archive",
+        }
+    }
+
+    /// The category a note (relative to the notes root) is filed under, from
+    /// its top-level folder.
+    pub fn of(relative: &str) -> Option<Self> {
+        let (top, _) = relative.split_once('/')?;
+        Self::from_dir_name(top)
+    }
+
+    /// The category a top-level folder name stands for.
+    pub fn from_dir_name(name: &str) -> Option<Self> {
+        // Accept "4 Archive", "4. Archive", "04-archive", "Archive", ...
+        let name = name
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-' || c == '_')
+            .trim()
+            .to_lowercase();
+        match name.as_str() {
+            "projects" => Some(Category::Projects),
+            "areas" => Some(Category::Areas),
+            "resources" => Some(Category::Resources),
+            "archive" | "archives" => Some(Category::Archive),
+            _ => None,
+        }
+    }
+}
+
+/// Find the top-level directory for a category, e.g. `4 Archive/`.
+pub fn category_dir(notes_dir: &Path, category: Category) -> Option<PathBuf> {
+    let mut entries: Vec<_> = std::fs::read_dir(notes_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .collect();
+    entries.sort_by_key(|e| e.file_name());
+
+    entries
+        .into_iter()
+        .find(|e| Category::from_dir_name(&e.file_name().to_string_lossy()) == Some(category))
+        .map(|e| e.path())
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDate;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::due;
+use crate::frontmatter;
+use crate::para::{self, Category};
+use crate::vault;
+
+/// Where a project stands, from its `status:` frontmatter.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Active,
+    Waiting,
+    Someday,
+    Done,
+    /// No `status:`, or one para doesn't know.
+    Unset,
+}
+
+impl Status {
+    /// In the order the dashboard shows them.
+    pub const ALL: [Status; 5] = [
+        Status::Active,
+        Status::Waiting,
+        Status::Someday,
+        Status::Done,
+        Status::Unset,
+    ];
+
+    fn parse(status: &str) -> Status {
+        match status.trim().to_lowercase().as_str() {
+            "active" => Status::Active,
+            "waiting" => Status::Waiting,
+            "someday" => Status::Someday,
+            "done" | "completed" => Status::Done,
+            _ => Status::Unset,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Status::Active => "Active",
+            Status::Waiting => "Waiting",
+            Status::Someday => "Someday",
+            Status::Done => "Done",
+            Status::Unset => "No status",
+        }
+    }
+}
+
+pub struct ProjectStatus {
+    pub name: String,
+    /// Path relative to the notes root, with a trailing `/` for folders.
+    pub path: String,
+    pub status: Status,
+    pub due: Option<NaiveDate>,
+}
+
+/// Every project under the projects category, with the `status:` and `due:`
+/// from its note or its folder's README.md. Sorted by status, then by due
+/// date (undated last), then by name.
+pub fn collect(notes_dir: &Path, config: &Config) -> Vec<ProjectStatus> {
+    let Some(projects_dir) = para::category_dir(notes_dir, Category::Projects) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&projects_dir) else {
+        return Vec::new();
+    };
+
+    let mut projects = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        if name.starts_with(['.', '_']) || name == "README.md" || config.is_private(notes_dir, &path) {
+            continue;
+        }
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        let note = if is_dir {
+            path.join("README.md")
+        } else if name.ends_with(".md") {
+            path.clone()
+        } else {
+            continue;
+        };
+
+        let meta = frontmatter::read(&note);
+        let relative = vault::relative(notes_dir, &path);
+        projects.push(ProjectStatus {
+            name: name.trim_end_matches(".md").to_string(),
+            path: if is_dir { format!("{relative}/") } else { relative },
+            status: meta.get_str("status").map_or(Status::Unset, |s| Status::parse(&s)),
+            due: meta.get_str("due").and_then(|d| due::parse_date(&d)),
+        });
+    }
+
+    projects.sort_by(|a, b| {
+        a.status
+            .cmp(&b.status)
+            .then_with(|| (a.due.is_none(), a.due).cmp(&(b.due.is_none(), b.due)))
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    projects
+}
@@ -0,0 +1,82 @@
+use regex::Regex;
+use serde_yaml_ng::Value;
+use std::sync::LazyLock;
+
+use crate::frontmatter::Frontmatter;
+use crate::serve::{encode_path, html_escape};
+
+/// `2024-03-11`, optionally with a time.
+static DATE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2})?)?$").unwrap());
+/// A value that is just a `[[link]]`, as Obsidian writes links in properties.
+static WIKILINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[\[([^\]|#]+)(?:#[^\]|]*)?(?:\|([^\]]+))?\]\]$").unwrap());
+
+/// A collapsible table of a note's frontmatter, or nothing if it has none.
+/// Tags link to their tag page, `[[links]]` to their note, URLs out, and
+/// dates are marked up as `<time>`.
+pub fn render(frontmatter: &Frontmatter) -> String {
+    let mut rows = String::new();
+    for (key, value) in frontmatter.iter() {
+        let cell = if matches!(key, "tags" | "tag") {
+            frontmatter
+                .get_list(key)
+                .iter()
+                .map(|tag| {
+                    let tag = tag.trim_start_matches('#');
+                    format!(
+                        "<a class=\"tag\" href=\"{}\">#{}</a>",
+                        html_escape(&encode_path(&format!("/tags/{tag}"))),
+                        html_escape(tag)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else {
+            value_html(value)
+        };
+        rows.push_str(&format!("<tr><th>{}</th><td>{cell}</td></tr>\n", html_escape(key)));
+    }
+    if rows.is_empty() {
+        return String::new();
+    }
+    format!(
+        "<details class=\"properties\" open>\n<summary>Properties</summary>\n<table>\n{rows}</table>\n</details>\n"
+    )
+}
+
+fn value_html(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(checked) => format!(
+            "<input type=\"checkbox\" disabled{}>",
+            if *checked { " checked" } else { "" }
+        ),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => scalar_html(s),
+        Value::Sequence(items) => items.iter().map(value_html).collect::<Vec<_>>().join(", "),
+        Value::Mapping(_) | Value::Tagged(_) => format!(
+            "<code>{}</code>",
+            html_escape(serde_yaml_ng::to_string(value).unwrap_or_default().trim())
+        ),
+    }
+}
+
+fn scalar_html(s: &str) -> String {
+    let s = s.trim();
+    if let Some(caps) = WIKILINK_RE.captures(s) {
+        let target = caps[1].trim();
+        let label = caps.get(2).map_or(target, |m| m.as_str().trim());
+        format!(
+            "<a class=\"wikilink\" href=\"{}\">{}</a>",
+            html_escape(&format!("/resolve?name={}", encode_path(target))),
+            html_escape(label)
+        )
+    } else if s.starts_with("https://") || s.starts_with("http://") {
+        format!("<a href=\"{0}\">{0}</a>", html_escape(s))
+    } else if DATE_RE.is_match(s) {
+        format!("<time datetime=\"{0}\">{0}</time>", html_escape(s))
+    } else {
+        html_escape(s)
+    }
+}
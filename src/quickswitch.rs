@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard};
+
+use crate::config::Config;
+use crate::search;
+use crate::vault;
+
+/// Most notes offered by the switcher.
+const MAX_RESULTS: usize = 20;
+
+/// Most notes suggested for a path that wasn't found.
+const MAX_SIMILAR: usize = 5;
+
+/// Scores for fuzzy matching, in the spirit of fzf: every matched character
+/// counts, runs of them and characters starting a word count extra, matches
+/// in the file name beat matches in its folders, and skipped characters
+/// cost a little.
+const MATCH: i32 = 16;
+const CONSECUTIVE: i32 = 8;
+const WORD_START: i32 = 8;
+const IN_FILE_NAME: i32 = 4;
+const GAP: i32 = 1;
+
+/// Paths of every note, relative to the notes root, so the switcher doesn't
+/// walk the vault on each keystroke. Refreshed from the file watcher.
+pub struct NotePaths {
+    notes_dir: PathBuf,
+    paths: RwLock<Vec<String>>,
+}
+
+/// A note matching the switcher's query.
+pub struct Match {
+    pub path: String,
+    /// Indices of the path's characters that matched, for highlighting.
+    pub positions: Vec<usize>,
+}
+
+impl NotePaths {
+    pub fn new(notes_dir: &Path, config: &Config) -> Self {
+        let paths = NotePaths {
+            notes_dir: notes_dir.to_path_buf(),
+            paths: RwLock::new(Vec::new()),
+        };
+        paths.refresh(config);
+        paths
+    }
+
+    /// Walk the vault again after notes were added, moved or removed.
+    pub fn refresh(&self, config: &Config) {
+        let found = vault::files(&self.notes_dir, config)
+            .iter()
+            .filter(|p| search::is_searchable(p))
+            .map(|p| vault::relative(&self.notes_dir, p))
+            .collect();
+        *self.paths.write().unwrap() = found;
+    }
+
+    /// Every note's path, relative to the notes root. Private ones are
+    /// included.
+    pub fn paths(&self) -> RwLockReadGuard<'_, Vec<String>> {
+        self.paths.read().unwrap()
+    }
+
+    /// The notes best matching `query`, leaving out any `config`'s viewer
+    /// can't see.
+    pub fn matching(&self, config: &Config, query: &str) -> Vec<Match> {
+        let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut scored: Vec<(i32, Match)> = self
+            .paths
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|path| {
+                let (score, positions) = fuzzy_match(&query, path)?;
+                Some((
+                    score,
+                    Match {
+                        path: path.clone(),
+                        positions,
+                    },
+                ))
+            })
+            .collect();
+        scored.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.1.path.len().cmp(&b.1.path.len()))
+                .then_with(|| a.1.path.cmp(&b.1.path))
+        });
+        scored
+            .into_iter()
+            .map(|(_, m)| m)
+            .filter(|m| !config.is_private(&self.notes_dir, &self.notes_dir.join(&m.path)))
+            .take(MAX_RESULTS)
+            .collect()
+    }
+
+    /// Notes a request for the missing `path` may have meant, closest first:
+    /// ones with the same file name elsewhere, then ones whose name differs
+    /// by a few typos or contains it, then fuzzy matches on the name.
+    pub fn similar(&self, config: &Config, path: &str) -> Vec<String> {
+        let wanted = stem(path).to_lowercase();
+        if wanted.is_empty() {
+            return Vec::new();
+        }
+        let query: Vec<char> = wanted.chars().filter(|c| !c.is_whitespace()).collect();
+        let allowed = (wanted.chars().count() / 3).max(2);
+        let mut scored: Vec<(usize, i32, &String)> = Vec::new();
+        let paths = self.paths.read().unwrap();
+        for candidate in paths.iter() {
+            let name = stem(candidate).to_lowercase();
+            let distance = edit_distance(&wanted, &name);
+            let rank = if distance <= allowed {
+                (distance, 0)
+            } else if name.contains(&wanted) {
+                (allowed + 1, 0)
+            } else if let Some((score, _)) = fuzzy_match(&query, &name) {
+                (allowed + 2, -score)
+            } else {
+                continue;
+            };
+            scored.push((rank.0, rank.1, candidate));
+        }
+        scored.sort_by_key(|&(distance, score, path)| (distance, score, path.len(), path));
+        scored
+            .into_iter()
+            .map(|(_, _, path)| path)
+            .filter(|path| !config.is_private(&self.notes_dir, &self.notes_dir.join(path)))
+            .take(MAX_SIMILAR)
+            .cloned()
+            .collect()
+    }
+}
+
+/// A path's file name without its extension.
+fn stem(path: &str) -> &str {
+    Path::new(path.trim_end_matches('/'))
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("")
+}
+
+/// Characters inserted, removed or replaced to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Match `query`'s characters in order anywhere in `path`, ignoring case.
+/// Returns the best score and the positions that achieve it, or `None` if
+/// the characters don't all appear.
+fn fuzzy_match(query: &[char], path: &str) -> Option<(i32, Vec<usize>)> {
+    let text: Vec<char> = path.chars().collect();
+    let (m, n) = (query.len(), text.len());
+    if m > n {
+        return None;
+    }
+    let name_start = path.rfind('/').map_or(0, |i| path[..=i].chars().count());
+    let bonus = |j: usize| {
+        let word_start = j == 0 || matches!(text[j - 1], '/' | ' ' | '-' | '_' | '.');
+        MATCH
+            + if word_start { WORD_START } else { 0 }
+            + if j >= name_start { IN_FILE_NAME } else { 0 }
+    };
+    let eq = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+
+    // ends[i][j]: best score matching query[..=i] with query[i] at text[j].
+    // best[i][j]: best score matching query[..=i] somewhere in text[..=j],
+    // less the gap since.
+    const NONE: i32 = i32::MIN / 2;
+    let mut ends = vec![vec![NONE; n]; m];
+    let mut best = vec![vec![NONE; n]; m];
+    for i in 0..m {
+        for j in i..n {
+            if eq(query[i], text[j]) {
+                ends[i][j] = if i == 0 {
+                    bonus(j)
+                } else if j > 0 {
+                    let run = ends[i - 1][j - 1].saturating_add(CONSECUTIVE);
+                    run.max(best[i - 1][j - 1]).saturating_add(bonus(j))
+                } else {
+                    NONE
+                };
+            }
+            let carried = if j > 0 { best[i][j - 1] - GAP } else { NONE };
+            best[i][j] = ends[i][j].max(carried);
+        }
+    }
+
+    let (mut j, score) = (0..n)
+        .map(|j| (j, ends[m - 1][j]))
+        .max_by_key(|&(j, score)| (score, std::cmp::Reverse(j)))?;
+    if score <= NONE / 2 {
+        return None;
+    }
+
+    // Walk back through the choices that produced the best score.
+    let mut positions = vec![j];
+    for i in (1..m).rev() {
+        let before = ends[i][j] - bonus(j);
+        j = if ends[i - 1][j - 1].saturating_add(CONSECUTIVE) == before {
+            j - 1
+        } else {
+            (i - 1..j)
+                .rev()
+                .find(|&k| ends[i - 1][k] - GAP * (j - 1 - k) as i32 == before)?
+        };
+        positions.push(j);
+    }
+    positions.reverse();
+    Some((score, positions))
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::comments;
+use crate::config::Config;
+use crate::frontmatter;
+use crate::note;
+use crate::vault;
+
+/// Longest snippet shown under a note, in characters.
+const SNIPPET_LEN: usize = 200;
+
+/// When each note was last modified, so listing the latest edits doesn't
+/// stat the whole vault on every request. Kept current from the file
+/// watcher.
+pub struct ModifiedTimes {
+    notes_dir: PathBuf,
+    times: RwLock<HashMap<String, SystemTime>>,
+}
+
+/// A recently modified note.
+pub struct RecentNote {
+    /// Path relative to the notes root.
+    pub path: String,
+    pub title: String,
+    pub modified: DateTime<Local>,
+    /// The start of the note's text, without frontmatter or headings.
+    pub snippet: String,
+}
+
+impl ModifiedTimes {
+    pub fn new(notes_dir: &Path, config: &Config) -> Self {
+        let times = ModifiedTimes {
+            notes_dir: notes_dir.to_path_buf(),
+            times: RwLock::new(HashMap::new()),
+        };
+        times.refresh(config);
+        times
+    }
+
+    /// Walk the vault again after notes were added, moved or removed.
+    pub fn refresh(&self, config: &Config) {
+        let found = vault::files(&self.notes_dir, config)
+            .into_iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "md"))
+            .filter_map(|p| {
+                let modified = p.metadata().and_then(|m| m.modified()).ok()?;
+                Some((vault::relative(&self.notes_dir, &p), modified))
+            })
+            .collect();
+        *self.times.write().unwrap() = found;
+    }
+
+    /// Note that `path` was written to. Paths the vault doesn't list, like
+    /// hidden or private files, are ignored.
+    pub fn touch(&self, path: &Path) {
+        let relative = vault::relative(&self.notes_dir, path);
+        let mut times = self.times.write().unwrap();
+        if let Some(time) = times.get_mut(&relative)
+            && let Ok(modified) = path.metadata().and_then(|m| m.modified())
+        {
+            *time = modified;
+        }
+    }
+
+    /// The `limit` most recently modified notes `config`'s viewer can see,
+    /// newest first.
+    pub fn latest(&self, config: &Config, limit: usize) -> Vec<RecentNote> {
+        let mut times: Vec<(String, SystemTime)> = self
+            .times
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(path, time)| (path.clone(), *time))
+            .collect();
+        times.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        times
+            .into_iter()
+            .filter(|(path, _)| !config.is_private(&self.notes_dir, &self.notes_dir.join(path)))
+            .filter_map(|(path, time)| {
+                let file = self.notes_dir.join(&path);
+                let content = std::fs::read_to_string(&file).ok()?;
+                Some(RecentNote {
+                    title: note::title(&file, &content),
+                    modified: DateTime::from(time),
+                    snippet: snippet(&content),
+                    path,
+                })
+            })
+            .take(limit)
+            .collect()
+    }
+}
+
+/// The first lines of prose in a note, cut at a word near [`SNIPPET_LEN`].
+fn snippet(content: &str) -> String {
+    let content = comments::strip(content);
+    let body = frontmatter::parse(&content).1;
+    let mut in_code = false;
+    let text = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            if line.starts_with("```") || line.starts_with("~~~") {
+                in_code = !in_code;
+                return false;
+            }
+            !in_code && !line.is_empty() && !line.starts_with('#')
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if text.chars().count() <= SNIPPET_LEN {
+        return text;
+    }
+    let cut: String = text.chars().take(SNIPPET_LEN).collect();
+    match cut.rfind(' ') {
+        Some(space) => format!("{}…", &cut[..space]),
+        None => format!("{cut}…"),
+    }
+}
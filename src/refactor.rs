@@ -0,0 +1,107 @@
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::vault;
+
+pub struct Refactor<'a> {
+    pub find: &'a str,
+    pub replace: &'a str,
+    /// Globs (relative to the notes root) limiting which files are touched.
+    pub paths: &'a [String],
+    /// Write the changes instead of only printing them.
+    pub apply: bool,
+}
+
+/// Replace `find` (a regex, matched line by line) with `replace` in the
+/// contents of every matching note and in file names, printing a diff of
+/// each change.
+pub fn run(notes_dir: &Path, config: &Config, refactor: &Refactor) -> Result<()> {
+    let find = Regex::new(refactor.find).context("invalid --find pattern")?;
+    let globs: Vec<Regex> = refactor.paths.iter().map(|g| vault::glob(g)).collect();
+
+    let mut changed_files = 0;
+    let mut changed_lines = 0;
+    let mut writes = Vec::new();
+    let mut renames = Vec::new();
+
+    for path in vault::files(notes_dir, config) {
+        let relative = vault::relative(notes_dir, &path);
+        if !globs.is_empty() && !globs.iter().any(|g| g.is_match(&relative)) {
+            continue;
+        }
+
+        if path.extension().is_some_and(|e| e == "md")
+            && let Ok(content) = std::fs::read_to_string(&path)
+        {
+            let mut output = String::with_capacity(content.len());
+            let mut diff = Vec::new();
+            for (i, line) in content.split_inclusive('\n').enumerate() {
+                let replaced = find.replace_all(line, refactor.replace);
+                if replaced != line {
+                    diff.push(format!("{}:", i + 1));
+                    diff.push(format!("- {}", line.trim_end_matches(['\r', '\n'])));
+                    diff.push(format!("+ {}", replaced.trim_end_matches(['\r', '\n'])));
+                }
+                output.push_str(&replaced);
+            }
+            if !diff.is_empty() {
+                println!("--- {relative}");
+                for line in &diff {
+                    println!("{line}");
+                }
+                println!();
+                changed_files += 1;
+                changed_lines += diff.len() / 3;
+                writes.push((path.clone(), output));
+            }
+        }
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let new_name = find.replace_all(&name, refactor.replace);
+        if new_name != name {
+            renames.push((path.clone(), path.with_file_name(new_name.as_ref())));
+        }
+    }
+
+    for (from, to) in &renames {
+        println!(
+            "rename {} -> {}",
+            vault::relative(notes_dir, from),
+            vault::relative(notes_dir, to)
+        );
+    }
+
+    if refactor.apply {
+        // Check every target first so a clash doesn't leave a half-done refactor
+        if let Some((from, to)) = renames.iter().find(|(_, to)| to.exists()) {
+            bail!(
+                "not renaming {}: {} already exists",
+                vault::relative(notes_dir, from),
+                vault::relative(notes_dir, to)
+            );
+        }
+        for (path, content) in &writes {
+            std::fs::write(path, content)
+                .with_context(|| format!("failed to write {}", vault::relative(notes_dir, path)))?;
+        }
+        for (from, to) in &renames {
+            std::fs::rename(from, to)?;
+        }
+    }
+
+    println!(
+        "{changed_lines} line(s) in {changed_files} file(s), {} rename(s){}",
+        renames.len(),
+        if refactor.apply {
+            ""
+        } else {
+            " (dry run; pass --apply to write)"
+        }
+    );
+    Ok(())
+}
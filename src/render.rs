@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use crate::asciidoc::AsciiDoc;
+use crate::source::Source;
+use crate::tables::Csv;
+
+/// Turns a kind of file other than a markdown note into a page, chosen by
+/// the file's extension.
+pub trait Renderer: Sync {
+    /// The page body for the file `name` holding `content`.
+    fn render(&self, name: &str, content: &str) -> String;
+
+    /// `Content-Type` of the file itself, served from `/raw/{path}`.
+    fn content_type(&self) -> &'static str {
+        "text/plain; charset=utf-8"
+    }
+}
+
+static RENDERERS: [(&str, &dyn Renderer); 2] = [("csv", &Csv), ("adoc", &AsciiDoc)];
+
+/// The renderer for a file, if it isn't a note and gets rendered at all.
+/// Text and source files not claimed by another renderer are shown as code.
+pub fn for_path(path: &Path) -> Option<&'static dyn Renderer> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if ext == "md" {
+        return None;
+    }
+    RENDERERS
+        .iter()
+        .find(|(known, _)| *known == ext)
+        .map(|(_, renderer)| *renderer)
+        .or_else(|| Source::handles(path).then_some(&Source as &dyn Renderer))
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Local, NaiveDate};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::capture;
+use crate::config::Config;
+use crate::note::{Note, Task};
+use crate::para::{self, Category};
+use crate::vault;
+
+/// Days back from today that count as this week.
+const WEEK: i64 = 7;
+
+/// What a weekly review goes through.
+pub struct Review {
+    /// Notes edited in the last week, newest first.
+    pub touched: Vec<Touched>,
+    /// Every project, by name.
+    pub projects: Vec<Project>,
+    /// Notes in the inbox waiting to be filed, oldest first.
+    pub inbox: Vec<Touched>,
+}
+
+pub struct Touched {
+    /// Path relative to the notes root.
+    pub path: String,
+    pub modified: NaiveDate,
+}
+
+/// A folder or note directly under the projects category.
+pub struct Project {
+    pub name: String,
+    /// Path relative to the notes root, with a trailing `/` for folders.
+    pub path: String,
+    /// The latest edit to any note in it.
+    pub last_edit: Option<NaiveDate>,
+    /// Open tasks, with the note each is in.
+    pub tasks: Vec<(String, Task)>,
+}
+
+impl Project {
+    /// No edits in the last `days` days.
+    pub fn is_stale(&self, today: NaiveDate, days: u32) -> bool {
+        self.last_edit
+            .is_none_or(|date| (today - date).num_days() > i64::from(days))
+    }
+}
+
+/// Gather the review from note modification times and tasks.
+pub fn collect(notes_dir: &Path, config: &Config, today: NaiveDate) -> Review {
+    let projects_dir = para::category_dir(notes_dir, Category::Projects);
+    let inbox_dir = capture::inbox(notes_dir);
+    let mut touched = Vec::new();
+    let mut inbox = Vec::new();
+    let mut projects: BTreeMap<String, Project> = BTreeMap::new();
+
+    for path in vault::files(notes_dir, config) {
+        if path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+        let relative = vault::relative(notes_dir, &path);
+        let Some(modified) = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .map(|time| DateTime::<Local>::from(time).date_naive())
+        else {
+            continue;
+        };
+        if (today - modified).num_days() < WEEK {
+            touched.push(Touched {
+                path: relative.clone(),
+                modified,
+            });
+        }
+        if path.starts_with(&inbox_dir) && path.file_name().is_none_or(|name| name != "README.md") {
+            inbox.push(Touched {
+                path: relative.clone(),
+                modified,
+            });
+        }
+
+        let Some(projects_dir) = &projects_dir else {
+            continue;
+        };
+        let Ok(inside) = path.strip_prefix(projects_dir) else {
+            continue;
+        };
+        let mut parts = inside.components();
+        let Some(first) = parts.next() else {
+            continue;
+        };
+        let name = first.as_os_str().to_string_lossy().to_string();
+        // The category's own README isn't a project
+        if parts.clone().next().is_none() && name == "README.md" {
+            continue;
+        }
+        let is_dir = parts.next().is_some();
+        let project = projects.entry(name.clone()).or_insert_with(|| {
+            let top = vault::relative(notes_dir, &projects_dir.join(&name));
+            Project {
+                name: name.trim_end_matches(".md").to_string(),
+                path: if is_dir { format!("{top}/") } else { top },
+                last_edit: None,
+                tasks: Vec::new(),
+            }
+        });
+        project.last_edit = project.last_edit.max(Some(modified));
+        if let Some(note) = Note::load(notes_dir, &path) {
+            project
+                .tasks
+                .extend(note.tasks.into_iter().filter(|t| !t.done).map(|t| (relative.clone(), t)));
+        }
+    }
+
+    touched.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.path.cmp(&b.path)));
+    inbox.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+    let mut projects: Vec<Project> = projects.into_values().collect();
+    projects.sort_by_key(|p| p.name.to_lowercase());
+    Review {
+        touched,
+        projects,
+        inbox,
+    }
+}
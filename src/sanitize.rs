@@ -0,0 +1,85 @@
+use ammonia::Builder;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+/// Ammonia's safe defaults, plus what rendered notes need to look right:
+/// classes and ids for styling and heading links, task list checkboxes,
+/// table alignment, folded callouts, footnotes and attachment links, and
+/// what para adds around them: the archive button, external link targets,
+/// property dates, lazy link card icons and inline diagram images.
+static CLEANER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(["input", "section", "button"])
+        .add_tag_attributes("input", ["type", "checked", "disabled", "data-line", "data-version"])
+        .add_tag_attributes("button", ["data-path"])
+        .add_tag_attributes("a", ["aria-hidden", "download", "hx-boost", "target", "rel"])
+        .add_tag_attributes("img", ["loading"])
+        .add_tag_attributes("time", ["datetime"])
+        .add_tag_attributes("details", ["open"])
+        .add_tag_attributes("th", ["align"])
+        .add_tag_attributes("td", ["align"])
+        .add_generic_attributes([
+            "class",
+            "id",
+            "data-callout",
+            "data-footnotes",
+            "data-footnote-ref",
+            "data-footnote-backref",
+        ])
+        .link_rel(None)
+        // Rendered diagrams are inlined as `data:image/...` images; no other
+        // element or attribute gets to use a `data:` URL
+        .add_url_schemes(["data"])
+        .attribute_filter(|element, attribute, value| {
+            let is_data = value.trim_start().get(..5).is_some_and(|s| s.eq_ignore_ascii_case("data:"));
+            if !matches!(attribute, "href" | "src" | "cite") || !is_data {
+                return Some(Cow::Borrowed(value));
+            }
+            (element == "img" && attribute == "src" && value.starts_with("data:image/"))
+                .then_some(Cow::Borrowed(value))
+        });
+    builder
+});
+
+/// Strip scripts, event handlers, `hx-*` attributes and anything else that
+/// could run code from rendered note HTML.
+pub fn clean(html: &str) -> String {
+    CLEANER.clean(html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_scripts_and_handlers() {
+        assert_eq!(clean("<p>hi<script>alert(1)</script></p>"), "<p>hi</p>");
+        assert_eq!(clean("<img src=\"x.png\" onerror=\"alert(1)\">"), "<img src=\"x.png\">");
+        assert_eq!(clean("<a href=\"javascript:alert(1)\">x</a>"), "<a>x</a>");
+        assert_eq!(clean("<div hx-get=\"/delete\">x</div>"), "<div>x</div>");
+    }
+
+    #[test]
+    fn keeps_data_urls_for_images_only() {
+        let image = "<img class=\"diagram\" src=\"data:image/svg+xml;base64,PHN2Zz4=\">";
+        assert_eq!(clean(image), image);
+        assert_eq!(clean("<img src=\"data:text/html,hi\">"), "<img>");
+        assert_eq!(clean("<a href=\"data:text/html,hi\">x</a>"), "<a>x</a>");
+        assert_eq!(clean("<a href=\"data:image/png;base64,AAAA\">x</a>"), "<a>x</a>");
+    }
+
+    #[test]
+    fn keeps_what_para_adds() {
+        for html in [
+            "<button class=\"archive-project\" data-path=\"1-Projects/p.md\">Archive project</button>",
+            "<a href=\"https://example.com\" target=\"_blank\" rel=\"noopener\" class=\"external\">x</a>",
+            "<time datetime=\"2026-01-02\">2026-01-02</time>",
+            "<img class=\"link-card-icon\" src=\"https://example.com/favicon.ico\" alt=\"\" loading=\"lazy\">",
+            "<input type=\"checkbox\" checked=\"\" disabled=\"\" data-line=\"3\" data-version=\"abc\">",
+            "<a href=\"_attachments/a.pdf\" download=\"\" hx-boost=\"false\">a.pdf</a>",
+        ] {
+            assert_eq!(clean(html), html);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use serde_yaml_ng::Value;
+
+use crate::config::{FieldKind, FieldRule, SchemaConfig};
+use crate::frontmatter::Frontmatter;
+
+/// Check a note's frontmatter against its folder's schema, returning one
+/// message per problem.
+pub fn validate(frontmatter: &Frontmatter, schema: &SchemaConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    for field in &schema.required {
+        if frontmatter.get(field).is_none_or(|v| v.is_null()) {
+            problems.push(format!("`{field}` is required"));
+        }
+    }
+
+    for (field, rule) in &schema.fields {
+        let Some(value) = frontmatter.get(field).filter(|v| !v.is_null()) else {
+            continue;
+        };
+        match rule {
+            FieldRule::Kind(kind) if !matches_kind(value, *kind) => {
+                problems.push(format!("`{field}` should be {}", describe(*kind)));
+            }
+            FieldRule::OneOf(allowed) => {
+                let matches = frontmatter
+                    .get_str(field)
+                    .is_some_and(|v| allowed.contains(&v));
+                if !matches {
+                    problems.push(format!("`{field}` should be one of {}", allowed.join(", ")));
+                }
+            }
+            FieldRule::Kind(_) => {}
+        }
+    }
+    problems
+}
+
+fn matches_kind(value: &Value, kind: FieldKind) -> bool {
+    match kind {
+        FieldKind::String => value.is_string(),
+        FieldKind::Number => value.is_number(),
+        FieldKind::Bool => value.is_bool(),
+        FieldKind::Date => value.as_str().is_some_and(is_date),
+        FieldKind::List => value.is_sequence(),
+    }
+}
+
+fn describe(kind: FieldKind) -> &'static str {
+    match kind {
+        FieldKind::String => "text",
+        FieldKind::Number => "a number",
+        FieldKind::Bool => "true or false",
+        FieldKind::Date => "a date (YYYY-MM-DD)",
+        FieldKind::List => "a list",
+    }
+}
+
+/// Whether `s` is a plausible `YYYY-MM-DD` date.
+fn is_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.trim().split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    let number = |part: &str, len: usize| -> Option<u32> {
+        (part.len() == len && part.chars().all(|c| c.is_ascii_digit()))
+            .then(|| part.parse().ok())
+            .flatten()
+    };
+    matches!(
+        (number(year, 4), number(month, 2), number(day, 2)),
+        (Some(_), Some(1..=12), Some(1..=31))
+    )
+}
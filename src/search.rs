@@ -0,0 +1,356 @@
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::io::IsTerminal;
+use std::path::Path;
+
+use crate::comments;
+use crate::config::Config;
+use crate::index::SearchIndex;
+use crate::vault;
+
+/// Matching lines shown per note.
+const MAX_MATCHES: usize = 3;
+/// Lines of context around each match.
+const CONTEXT: usize = 1;
+/// Most notes suggested while typing in the search box.
+const MAX_SUGGESTIONS: usize = 10;
+/// Notes shown per page of results.
+pub const PER_PAGE: usize = 20;
+
+/// What a match adds to a note's score when scanning, by where it is: a
+/// match in the name beats one in a heading, which beats one in the body.
+const NAME_SCORE: f32 = 10.0;
+const HEADING_SCORE: f32 = 3.0;
+const BODY_SCORE: f32 = 1.0;
+
+/// A note matching a query, with a few lines around each match.
+pub struct FileMatches {
+    /// Path relative to the notes root.
+    pub path: String,
+    /// Relevance, higher first. Only comparable within one search.
+    pub score: f32,
+    /// Lines to show in order; `None` marks skipped lines between excerpts.
+    pub lines: Vec<Option<String>>,
+}
+
+/// One page of a search's results, best first.
+pub struct Results {
+    pub matches: Vec<FileMatches>,
+    /// Matching notes across every page.
+    pub total: usize,
+}
+
+impl Results {
+    pub fn pages(&self) -> usize {
+        self.total.div_ceil(PER_PAGE).max(1)
+    }
+}
+
+/// How a query is matched, chosen with `?mode=` on `/search`.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Ranked search for words and quoted phrases.
+    #[default]
+    Words,
+    /// A case-insensitive regex, checked line by line.
+    Regex,
+    /// The exact text, ignoring case.
+    Literal,
+    /// Like `Words`, but tolerating a typo in each word.
+    Fuzzy,
+    /// Notes related in meaning, when an embeddings endpoint is configured.
+    Semantic,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 5] = [
+        Mode::Words,
+        Mode::Regex,
+        Mode::Literal,
+        Mode::Fuzzy,
+        Mode::Semantic,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Words => "words",
+            Mode::Regex => "regex",
+            Mode::Literal => "literal",
+            Mode::Fuzzy => "fuzzy",
+            Mode::Semantic => "semantic",
+        }
+    }
+}
+
+pub trait Searcher {
+    /// Page `page` (from 1) of the notes matching `query`, optionally
+    /// limited to a folder relative to the notes root.
+    fn search(&self, query: &str, scope: Option<&str>, page: usize) -> Results;
+
+    /// Notes whose name contains `query`, for suggestions while typing.
+    fn suggest(&self, query: &str) -> Vec<String>;
+
+    /// Pattern for marking what matched in the returned lines.
+    fn highlight(&self, query: &str) -> Option<Regex> {
+        query_regex(query)
+    }
+}
+
+/// Scans every note on each search, matching the query line by line. Private
+/// folders are skipped. Serves the regex and literal modes, and every mode
+/// when the search index couldn't be built.
+pub struct VaultSearcher<'a> {
+    pub notes_dir: &'a Path,
+    pub config: &'a Config,
+    pub mode: Mode,
+}
+
+impl Searcher for VaultSearcher<'_> {
+    fn search(&self, query: &str, scope: Option<&str>, page: usize) -> Results {
+        let Some(re) = self.highlight(query) else {
+            return Results { matches: Vec::new(), total: 0 };
+        };
+        let scope = scope.map(|s| format!("{}/", s.trim_end_matches('/')));
+
+        let mut scored: Vec<(String, f32)> = vault::files(self.notes_dir, self.config)
+            .iter()
+            .filter(|p| is_searchable(p))
+            .filter_map(|path| {
+                let relative = vault::relative(self.notes_dir, path);
+                if scope.as_ref().is_some_and(|s| !relative.starts_with(s.as_str())) {
+                    return None;
+                }
+                let content = std::fs::read_to_string(path).ok()?;
+                let score = score(path, &comments::strip(&content), &re);
+                (score > 0.0).then_some((relative, score))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let total = scored.len();
+        let matches = on_page(scored, page)
+            .into_iter()
+            .map(|(path, score)| {
+                let content =
+                    std::fs::read_to_string(self.notes_dir.join(&path)).unwrap_or_default();
+                FileMatches {
+                    lines: excerpts(&comments::strip(&content), &re),
+                    path,
+                    score,
+                }
+            })
+            .collect();
+        Results { matches, total }
+    }
+
+    fn suggest(&self, query: &str) -> Vec<String> {
+        let query = query.trim().to_lowercase();
+        let paths = vault::files(self.notes_dir, self.config)
+            .iter()
+            .filter(|p| is_searchable(p))
+            .filter(|p| name(p).to_lowercase().contains(&query))
+            .map(|p| vault::relative(self.notes_dir, p))
+            .collect();
+        rank_suggestions(&query, paths)
+    }
+
+    fn highlight(&self, query: &str) -> Option<Regex> {
+        match self.mode {
+            Mode::Regex => mode_regex(query, Mode::Regex).ok(),
+            Mode::Literal => mode_regex(query, Mode::Literal).ok(),
+            Mode::Words | Mode::Fuzzy | Mode::Semantic => query_regex(query),
+        }
+    }
+}
+
+/// The regex a regex or literal mode query matches with. Errors describe
+/// what's wrong with an invalid regex.
+pub fn mode_regex(query: &str, mode: Mode) -> Result<Regex, regex::Error> {
+    let pattern = match mode {
+        Mode::Literal => regex::escape(query),
+        _ => query.to_string(),
+    };
+    RegexBuilder::new(&pattern).case_insensitive(true).build()
+}
+
+/// How well a note matches: every matching line counts, headings more than
+/// body text, and a matching name most of all.
+fn score(path: &Path, content: &str, re: &Regex) -> f32 {
+    let name_score = if re.is_match(&name(path)) { NAME_SCORE } else { 0.0 };
+    content
+        .lines()
+        .filter(|line| re.is_match(line))
+        .map(|line| if is_document_heading(path, line) { HEADING_SCORE } else { BODY_SCORE })
+        .sum::<f32>()
+        + name_score
+}
+
+/// Whether search covers a file: markdown notes and AsciiDoc documents.
+pub fn is_searchable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e == "md" || e == "adoc")
+}
+
+/// Whether a markdown line is an ATX heading (`# Title` through `###### Title`).
+pub fn is_heading(line: &str) -> bool {
+    marked_heading(line, '#')
+}
+
+/// Whether a line of the document at `path` is a heading, written the
+/// AsciiDoc way (`== Title`) in `.adoc` files and the markdown way elsewhere.
+pub fn is_document_heading(path: &Path, line: &str) -> bool {
+    if path.extension().is_some_and(|e| e == "adoc") {
+        marked_heading(line, '=')
+    } else {
+        is_heading(line)
+    }
+}
+
+/// Whether a line opens with one to six `marker`s and then a space.
+fn marked_heading(line: &str, marker: char) -> bool {
+    let level = line.len() - line.trim_start_matches(marker).len();
+    (1..=6).contains(&level) && line[level..].chars().next().is_none_or(char::is_whitespace)
+}
+
+/// The items on page `page` (from 1).
+pub fn on_page<T>(items: Vec<T>, page: usize) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(page.saturating_sub(1) * PER_PAGE)
+        .take(PER_PAGE)
+        .collect()
+}
+
+/// Order suggestions: names starting with the query first, then shorter
+/// names, then by path.
+pub fn rank_suggestions(query: &str, mut paths: Vec<String>) -> Vec<String> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    paths.sort_by_cached_key(|path| {
+        let name = name(Path::new(path)).to_lowercase();
+        (!name.starts_with(query), name.len(), path.clone())
+    });
+    paths.truncate(MAX_SUGGESTIONS);
+    paths
+}
+
+/// A note's name: its file name without the extension.
+pub fn name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Case-insensitive regex for a query. Queries that aren't valid regexes are
+/// matched literally.
+pub fn query_regex(query: &str) -> Option<Regex> {
+    if query.is_empty() {
+        return None;
+    }
+    RegexBuilder::new(query)
+        .case_insensitive(true)
+        .build()
+        .or_else(|_| {
+            RegexBuilder::new(&regex::escape(query))
+                .case_insensitive(true)
+                .build()
+        })
+        .ok()
+}
+
+/// The first few matching lines with their context, merging excerpts that
+/// touch and marking the gaps between the rest.
+pub fn excerpts(content: &str, re: &Regex) -> Vec<Option<String>> {
+    let lines: Vec<&str> = content.lines().collect();
+    let hits: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| re.is_match(line))
+        .map(|(i, _)| i)
+        .take(MAX_MATCHES)
+        .collect();
+
+    let mut out = Vec::new();
+    let mut shown_to = None;
+    for hit in hits {
+        let start = hit.saturating_sub(CONTEXT);
+        let end = (hit + CONTEXT).min(lines.len() - 1);
+        let from = match shown_to {
+            Some(last) if start <= last + 1 => last + 1,
+            Some(_) => {
+                out.push(None);
+                start
+            }
+            None => start,
+        };
+        for line in lines.iter().take(end + 1).skip(from) {
+            out.push(Some(line.to_string()));
+        }
+        shown_to = Some(end);
+    }
+    out
+}
+
+/// Search from the command line, printing `path:line: text` for each
+/// matching line of the best notes first, with matches highlighted when
+/// writing to a terminal. Notes that match as a whole but on no single line
+/// are listed by path alone.
+pub fn run(notes_dir: &Path, config: &Config, query: &str, mode: Mode, scope: Option<&str>, limit: usize) -> Result<()> {
+    let index = match mode {
+        Mode::Words | Mode::Fuzzy => SearchIndex::build(notes_dir, config).ok(),
+        Mode::Regex | Mode::Literal => None,
+        Mode::Semantic => bail!("semantic search is only available in `para serve`"),
+    };
+    if matches!(mode, Mode::Regex | Mode::Literal)
+        && let Err(e) = mode_regex(query, mode)
+    {
+        bail!("{e}");
+    }
+    let searcher: Box<dyn Searcher> = match &index {
+        Some(index) => Box::new(index.searcher(config, mode == Mode::Fuzzy)),
+        None => Box::new(VaultSearcher { notes_dir, config, mode }),
+    };
+    let Some(re) = searcher.highlight(query) else {
+        bail!("nothing to search for");
+    };
+
+    let mut paths = Vec::new();
+    let mut page = 1;
+    loop {
+        let results = searcher.search(query, scope, page);
+        let pages = results.pages();
+        paths.extend(results.matches.into_iter().map(|m| m.path));
+        if paths.len() >= limit || page >= pages {
+            break;
+        }
+        page += 1;
+    }
+    paths.truncate(limit);
+    if paths.is_empty() {
+        bail!("no notes match {query:?}");
+    }
+
+    let color = std::io::stdout().is_terminal();
+    for path in &paths {
+        let content = std::fs::read_to_string(notes_dir.join(path)).unwrap_or_default();
+        let content = comments::strip(&content);
+        let mut any = false;
+        for (i, line) in content.lines().enumerate().filter(|(_, line)| re.is_match(line)) {
+            let line = if color {
+                re.replace_all(line, "\x1b[1;31m$0\x1b[0m").into_owned()
+            } else {
+                line.to_string()
+            };
+            println!("{path}:{}: {}", i + 1, line.trim());
+            any = true;
+        }
+        if !any {
+            println!("{path}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,363 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use crate::comments;
+use crate::config::{Config, SemanticConfig};
+use crate::frontmatter;
+use crate::search::{self, FileMatches, Results, Searcher};
+use crate::vault;
+
+/// Most notes listed for a semantic search; every note is related to a query
+/// to some degree, so only the closest are worth showing.
+const MAX_RESULTS: usize = 50;
+/// Characters of each note sent for embedding, to stay inside the model's
+/// context window.
+const MAX_CHARS: usize = 4000;
+/// Notes embedded per request while building the cache.
+const BATCH: usize = 16;
+/// Lines from the top of each note shown in the results.
+const PREVIEW_LINES: usize = 3;
+
+/// Turns text into vectors whose distance reflects how related the texts
+/// are.
+pub trait Embedder: Send + Sync {
+    /// Identifies the model, so vectors from another one aren't mixed in.
+    fn model(&self) -> &str;
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// Embeds through an OpenAI-compatible `/v1/embeddings` endpoint, such as a
+/// local Ollama.
+pub struct ApiEmbedder {
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl ApiEmbedder {
+    pub fn new(config: &SemanticConfig) -> Self {
+        Self {
+            url: config.url.clone(),
+            model: config.model.clone(),
+            api_key: (!config.api_key_env.is_empty())
+                .then(|| std::env::var(&config.api_key_env).ok())
+                .flatten(),
+            agent: ureq::Agent::config_builder()
+                .timeout_global(Some(Duration::from_secs(60)))
+                .build()
+                .into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<Embedding>,
+}
+
+#[derive(Deserialize)]
+struct Embedding {
+    embedding: Vec<f32>,
+}
+
+impl Embedder for ApiEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let body = serde_json::json!({ "model": self.model, "input": texts });
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        if let Some(key) = &self.api_key {
+            request = request.header("Authorization", &format!("Bearer {key}"));
+        }
+        let text = request
+            .send(serde_json::to_string(&body)?)
+            .with_context(|| format!("Failed to reach {}", self.url))?
+            .body_mut()
+            .read_to_string()?;
+        let response: EmbeddingResponse =
+            serde_json::from_str(&text).context("Unexpected embeddings response")?;
+        if response.data.len() != texts.len() {
+            bail!("Asked for {} embeddings, got {}", texts.len(), response.data.len());
+        }
+        Ok(response.data.into_iter().map(|e| e.embedding).collect())
+    }
+}
+
+/// Work for the background thread.
+enum Job {
+    /// Embed every note the cache is missing.
+    All,
+    /// Re-embed a note that changed on disk.
+    Note(PathBuf),
+}
+
+/// Vectors on disk, keyed by a hash of the text they were made from, so
+/// restarts only embed notes that changed.
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    model: String,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+/// Embeddings of every note, for finding notes related to a query rather
+/// than ones sharing its words. Notes are embedded in the background, so
+/// results fill in as the cache is built.
+pub struct SemanticIndex {
+    notes_dir: PathBuf,
+    embedder: Box<dyn Embedder>,
+    cache_path: PathBuf,
+    cache: RwLock<Cache>,
+    /// The hash of each note's text, by path relative to the notes root.
+    notes: RwLock<HashMap<String, String>>,
+    queue: Mutex<Sender<Job>>,
+    /// A pass over every note is queued or running.
+    syncing: AtomicBool,
+}
+
+impl SemanticIndex {
+    /// Load the cache and start embedding the vault in the background.
+    pub fn start(notes_dir: &Path, config: &Config, embedder: Box<dyn Embedder>) -> Arc<Self> {
+        let cache_path = notes_dir.join(".para").join("cache").join("embeddings.json");
+        let cache: Cache = std::fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .filter(|c: &Cache| c.model == embedder.model())
+            .unwrap_or_else(|| Cache {
+                model: embedder.model().to_string(),
+                vectors: HashMap::new(),
+            });
+
+        let (tx, rx) = mpsc::channel::<Job>();
+        let index = Arc::new(Self {
+            notes_dir: notes_dir.to_path_buf(),
+            embedder,
+            cache_path,
+            cache: RwLock::new(cache),
+            notes: RwLock::new(HashMap::new()),
+            queue: Mutex::new(tx),
+            syncing: AtomicBool::new(false),
+        });
+
+        let worker = index.clone();
+        let config = config.clone();
+        std::thread::spawn(move || {
+            while let Ok(job) = rx.recv() {
+                let result = match job {
+                    Job::All => worker.embed_all(&config),
+                    Job::Note(path) => {
+                        let hidden = vault::relative(&worker.notes_dir, &path)
+                            .split('/')
+                            .any(|part| part.starts_with('.'));
+                        if hidden || config.is_private(&worker.notes_dir, &path) {
+                            continue;
+                        }
+                        worker.embed_notes(&[path])
+                    }
+                };
+                if let Err(e) = result {
+                    tracing::warn!("Semantic search: {e:#}");
+                }
+            }
+        });
+        index.sync();
+        index
+    }
+
+    /// Re-embed a note after it changed on disk.
+    pub fn queue(&self, path: &Path) {
+        if search::is_searchable(path) {
+            let _ = self.queue.lock().unwrap().send(Job::Note(path.to_path_buf()));
+        }
+    }
+
+    /// Queue a pass over every note, unless one is already waiting.
+    fn sync(&self) {
+        if !self.syncing.swap(true, Ordering::SeqCst) {
+            let _ = self.queue.lock().unwrap().send(Job::All);
+        }
+    }
+
+    /// Embed every note the cache is missing, a batch at a time. Stops at
+    /// the first failure; the next search tries again.
+    fn embed_all(&self, config: &Config) -> Result<()> {
+        let files: Vec<PathBuf> = vault::files(&self.notes_dir, config)
+            .into_iter()
+            .filter(|p| search::is_searchable(p))
+            .collect();
+        let result = files.chunks(BATCH).try_for_each(|batch| self.embed_notes(batch));
+        self.syncing.store(false, Ordering::SeqCst);
+        result
+    }
+
+    pub fn searcher<'a>(&'a self, config: &'a Config) -> SemanticSearcher<'a> {
+        SemanticSearcher { index: self, config }
+    }
+
+    /// Record the current text of each note, embedding any the cache
+    /// doesn't have. Notes that no longer exist are dropped.
+    fn embed_notes(&self, paths: &[PathBuf]) -> Result<()> {
+        let mut missing = Vec::new();
+        for path in paths {
+            let relative = vault::relative(&self.notes_dir, path);
+            let Ok(content) = std::fs::read_to_string(path) else {
+                self.notes.write().unwrap().remove(&relative);
+                continue;
+            };
+            let text = embedding_text(path, &content);
+            let hash = hash(&text);
+            if !self.cache.read().unwrap().vectors.contains_key(&hash) {
+                missing.push((hash.clone(), text));
+            }
+            self.notes.write().unwrap().insert(relative, hash);
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let texts: Vec<String> = missing.iter().map(|(_, text)| text.clone()).collect();
+        let vectors = self.embedder.embed(&texts)?;
+        {
+            let mut cache = self.cache.write().unwrap();
+            for ((hash, _), vector) in missing.into_iter().zip(vectors) {
+                cache.vectors.insert(hash, normalize(vector));
+            }
+        }
+        self.save();
+        Ok(())
+    }
+
+    /// Write the cache, keeping only vectors for notes that still exist.
+    fn save(&self) {
+        let notes = self.notes.read().unwrap();
+        let cache = self.cache.read().unwrap();
+        let kept = Cache {
+            model: cache.model.clone(),
+            vectors: notes
+                .values()
+                .filter_map(|hash| Some((hash.clone(), cache.vectors.get(hash)?.clone())))
+                .collect(),
+        };
+        if let Some(parent) = self.cache_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string(&kept) {
+            let _ = std::fs::write(&self.cache_path, json);
+        }
+    }
+
+    /// Notes closest to `query`, best first, with their similarity.
+    fn nearest(&self, config: &Config, query: &str, scope: Option<&str>) -> Result<Vec<(String, f32)>> {
+        let query = normalize(
+            self.embedder
+                .embed(&[query.to_string()])?
+                .pop()
+                .context("No embedding returned for the query")?,
+        );
+        let scope = scope.map(|s| format!("{}/", s.trim_end_matches('/')));
+        let cache = self.cache.read().unwrap();
+        let notes = self.notes.read().unwrap();
+        if notes.is_empty() || notes.values().any(|hash| !cache.vectors.contains_key(hash)) {
+            self.sync();
+        }
+        let mut scored: Vec<(String, f32)> = notes
+            .iter()
+            .filter(|(path, _)| scope.as_ref().is_none_or(|s| path.starts_with(s.as_str())))
+            .filter_map(|(path, hash)| {
+                let vector = cache.vectors.get(hash)?;
+                let similarity = vector.iter().zip(&query).map(|(a, b)| a * b).sum();
+                Some((path.clone(), similarity))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(scored
+            .into_iter()
+            .filter(|(path, _)| !config.is_private(&self.notes_dir, &self.notes_dir.join(path)))
+            .take(MAX_RESULTS)
+            .collect())
+    }
+}
+
+/// Answers searches from a [`SemanticIndex`], closest first.
+pub struct SemanticSearcher<'a> {
+    index: &'a SemanticIndex,
+    config: &'a Config,
+}
+
+impl Searcher for SemanticSearcher<'_> {
+    fn search(&self, query: &str, scope: Option<&str>, page: usize) -> Results {
+        let nearest = match self.index.nearest(self.config, query, scope) {
+            Ok(nearest) => nearest,
+            Err(e) => {
+                tracing::error!("Semantic search failed: {e:#}");
+                return Results { matches: Vec::new(), total: 0 };
+            }
+        };
+        let total = nearest.len();
+        let matches = search::on_page(nearest, page)
+            .into_iter()
+            .map(|(path, score)| {
+                let content =
+                    std::fs::read_to_string(self.index.notes_dir.join(&path)).unwrap_or_default();
+                let lines = frontmatter::parse(&comments::strip(&content))
+                    .1
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .take(PREVIEW_LINES)
+                    .map(|line| Some(line.to_string()))
+                    .collect();
+                FileMatches { path, score, lines }
+            })
+            .collect();
+        Results { matches, total }
+    }
+
+    fn suggest(&self, _query: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Related notes needn't share any words with the query.
+    fn highlight(&self, _query: &str) -> Option<regex::Regex> {
+        None
+    }
+}
+
+/// What's embedded for a note: its name and the start of its body.
+fn embedding_text(path: &Path, content: &str) -> String {
+    let content = comments::strip(content);
+    let body = frontmatter::parse(&content).1;
+    let mut text = format!("{}\n\n{body}", search::name(path));
+    if let Some((cut, _)) = text.char_indices().nth(MAX_CHARS) {
+        text.truncate(cut);
+    }
+    text
+}
+
+/// Scale to unit length, so similarity is a plain dot product.
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= length);
+    }
+    vector
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust releases, so
+/// cache keys survive upgrades.
+fn hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
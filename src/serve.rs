@@ -1,63 +1,624 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
-    body::Body,
+    body::{Body, Bytes, HttpBody},
     extract::{
         ws::{Message, WebSocket},
-        Path, Query, State, WebSocketUpgrade,
+        ConnectInfo, DefaultBodyLimit, MatchedPath, Multipart, Path, Query, Request, State, WebSocketUpgrade,
     },
-    http::{header, StatusCode},
+    http::{header, Method, StatusCode, Uri},
+    middleware::{self, Next},
     response::{Html, IntoResponse, Json, Redirect, Response},
+    Form,
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
-use comrak::{markdown_to_html, Options};
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::Datelike;
+use comrak::{format_html, parse_document, Anchorizer, Arena, Options};
 use futures::{SinkExt, StreamExt};
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use notify::event::{ModifyKind, RenameMode};
 use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Command;
-use std::sync::Arc;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path as FsPath, PathBuf};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
+use tokio_util::io::ReaderStream;
+use tracing::Instrument;
+use tower::ServiceExt;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::timeout::TimeoutLayer;
+
+use crate::access_log::{self, AccessLog};
+use crate::activity::Activity;
+use crate::archive::{self, ArchivedItem, MONTH_NAMES};
+use crate::attachments::{self, Attachment};
+use crate::audit::{self, Action};
+use crate::auth;
+use crate::callouts;
+use crate::comments;
+use crate::config::{self, Config, LinkConfig, OnMove, RateLimitConfig, Role, Theme, Vault};
+use crate::diagrams::Diagrams;
+use crate::due::{self, Bucket, DueItem};
+use crate::frontmatter;
+use crate::graph::{self, Graph};
+use crate::index::SearchIndex;
+use crate::journal;
+use crate::links::{self, LinkIndex, LinkIndexes, Resolution};
+use crate::lint::{self, FileIssues};
+use crate::metrics::METRICS;
+use crate::note::{self, Link, LinkKind, Note};
+use crate::og;
+use crate::projects::{self, ProjectStatus, Status};
+use crate::properties;
+use crate::quickswitch::NotePaths;
+use crate::review::{self, Review};
+use crate::recent::{ModifiedTimes, RecentNote};
+use crate::render;
+use crate::order;
+use crate::para::{self, Category};
+use crate::schema;
+use crate::semantic::{ApiEmbedder, SemanticIndex};
+use crate::sanitize;
+use crate::search::{self, FileMatches, Mode, Results, Searcher, VaultSearcher};
+use crate::spelling::Speller;
+use crate::tags;
+use crate::tasks::{self, NoteTasks, Toggled, Toggles};
+use crate::thumbs;
+use crate::toc;
+use crate::trash;
+use crate::unfurl::LinkPreviews;
+use crate::uploads;
+use crate::vault;
 
 const PARA_CSS: &str = include_str!("../assets/para.css");
 const PARA_JS: &str = include_str!("../assets/main.js");
 const EDITOR_JS: &str = include_str!("../assets/editor.js");
 const HTMX_JS: &str = include_str!("../assets/htmx.min.js");
 const MERMAID_JS: &str = include_str!("../assets/mermaid.min.js");
-const UBUNTU_MONO_REGULAR: &[u8] = include_bytes!("../assets/fonts/UbuntuMono-Regular.ttf");
+const GRAPH_JS: &str = include_str!("../assets/graph.js");
+pub(crate) const UBUNTU_MONO_REGULAR: &[u8] = include_bytes!("../assets/fonts/UbuntuMono-Regular.ttf");
 const UBUNTU_MONO_ITALIC: &[u8] = include_bytes!("../assets/fonts/UbuntuMono-Italic.ttf");
-const UBUNTU_MONO_BOLD: &[u8] = include_bytes!("../assets/fonts/UbuntuMono-Bold.ttf");
+pub(crate) const UBUNTU_MONO_BOLD: &[u8] = include_bytes!("../assets/fonts/UbuntuMono-Bold.ttf");
 const UBUNTU_MONO_BOLD_ITALIC: &[u8] = include_bytes!("../assets/fonts/UbuntuMono-BoldItalic.ttf");
+/// The fonts para.css loads from `/fonts/`, by file name.
+pub(crate) const FONTS: [(&str, &[u8]); 4] = [
+    ("UbuntuMono-Regular.ttf", UBUNTU_MONO_REGULAR),
+    ("UbuntuMono-Italic.ttf", UBUNTU_MONO_ITALIC),
+    ("UbuntuMono-Bold.ttf", UBUNTU_MONO_BOLD),
+    ("UbuntuMono-BoldItalic.ttf", UBUNTU_MONO_BOLD_ITALIC),
+];
+
+/// The stylesheet and scripts pages load from `/assets/`.
+pub(crate) static ASSETS: LazyLock<Assets> = LazyLock::new(|| Assets {
+    css: Asset::new("para", "css", PARA_CSS),
+    htmx: Asset::new("htmx", "js", HTMX_JS),
+    mermaid: Asset::new("mermaid", "js", MERMAID_JS),
+    graph: Asset::new("graph", "js", GRAPH_JS),
+    main: Asset::new("main", "js", PARA_JS),
+    editor: Asset::new("editor", "js", EDITOR_JS),
+});
+
+/// Cookie naming the vault a visitor last switched to.
+const VAULT_COOKIE: &str = "para-vault";
+/// Notes `/recent` lists unless `?limit=` says otherwise.
+const RECENT_LIMIT: usize = 30;
+/// Root-relative URLs in the attributes that navigate or load something.
+static ROOT_URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(\s(?:href|src|action|hx-get|hx-post|data-src)=")(/[^"]*)""#).unwrap());
+
+static EXTERNAL_ANCHOR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<a ((?:[^>]*\s)?href="https?://[^"]*"[^>]*)>"#).unwrap());
+static TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(/?)([a-zA-Z][a-zA-Z0-9]*)[^>]*>").unwrap());
+static ENTITY_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"&#?[a-zA-Z0-9]+;").unwrap());
+
+/// Query on a note opened from search results, marking what matched.
+#[derive(Deserialize)]
+struct HighlightParams {
+    highlight: Option<String>,
+    #[serde(default)]
+    mode: Mode,
+}
 
 #[derive(Deserialize)]
 struct SearchParams {
     q: Option<String>,
+    #[serde(default)]
+    mode: Mode,
+    /// Page of results, from 1.
+    page: Option<usize>,
 }
 
 #[derive(Deserialize)]
 struct SaveRequest {
     path: String,
     content: String,
+    /// Version of the note the editor started from, as served in the `ETag`
+    /// of `/raw`. Saves are refused when the file has changed since.
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ResolveParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct PasteParams {
+    note: String,
+}
+
+#[derive(Deserialize)]
+struct TrashRequest {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct RelinkRequest {
+    from: String,
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct ArchiveRequest {
+    /// The project, relative to the notes root.
+    path: String,
+}
+
+#[derive(Serialize)]
+struct ArchiveResponse {
+    success: bool,
+    error: Option<String>,
+    /// Where the project went, relative to the notes root.
+    path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ToggleTaskRequest {
+    /// The note, relative to the notes root.
+    path: String,
+    /// 1-based line of the task.
+    line: usize,
+    /// The note's version when the page was rendered.
+    version: String,
+}
+
+#[derive(Serialize)]
+struct ToggleTaskResponse {
+    success: bool,
+    error: Option<String>,
+    /// Whether the task is done now.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    done: Option<bool>,
+    /// The note's version after the change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UploadResponse {
+    success: bool,
+    error: Option<String>,
+    /// Stored files, relative to the notes root.
+    paths: Vec<String>,
+    /// Links to the stored files relative to the note, one per line.
+    markdown: String,
 }
 
 #[derive(Serialize)]
 struct SaveResponse {
     success: bool,
     error: Option<String>,
+    /// Version of the file on disk after a save, or at the time of a conflict.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    /// Current contents of the file when it changed under the editor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflict: Option<String>,
+}
+
+/// Pushed to open pages over the websocket.
+#[derive(Clone)]
+enum LiveEvent {
+    Reload(String),
+    /// A note or folder was moved outside para and `links` notes still point
+    /// at the old location.
+    Moved {
+        from: String,
+        to: String,
+        links: usize,
+    },
 }
 
 struct AppState {
     notes_dir: PathBuf,
-    reload_tx: broadcast::Sender<String>,
+    /// This vault's name.
+    vault: String,
+    /// Every vault being served, this one included.
+    vaults: Arc<Vec<Vault>>,
+    /// Served under `/{vault}/` beside the others, rather than switched to.
+    mounted: bool,
+    reload_tx: broadcast::Sender<LiveEvent>,
+    public_url: Option<String>,
+    /// Origins allowed with `--cors-origin`, whose pages may change the vault.
+    cors_origins: Vec<String>,
+    config: Config,
+    link_previews: Arc<LinkPreviews>,
+    diagrams: Diagrams,
+    /// Strip unsafe HTML from rendered notes.
+    sanitize: bool,
+    /// Served with `--dev`: error pages show what went wrong.
+    dev: bool,
+    /// Loaded the first time a page with spelling enabled is rendered.
+    speller: OnceLock<Option<Speller>>,
+    /// `None` if the index couldn't be built, in which case searches scan
+    /// the vault instead.
+    index: Option<Arc<SearchIndex>>,
+    /// Set when an embeddings endpoint is configured.
+    semantic: Option<Arc<SemanticIndex>>,
+    note_paths: Arc<NotePaths>,
+    modified_times: Arc<ModifiedTimes>,
+    link_indexes: Arc<LinkIndexes>,
+    /// Set while the file watcher is running.
+    watching: Arc<AtomicBool>,
+    /// Unset when `[rate_limit]` turns limiting off.
+    rate_limiter: Option<Arc<DefaultKeyedRateLimiter<IpAddr>>>,
+}
+
+impl AppState {
+    fn speller(&self) -> Option<&Speller> {
+        self.speller
+            .get_or_init(|| match Speller::load(&self.notes_dir, &self.config.spelling) {
+                Ok(speller) => Some(speller),
+                Err(e) => {
+                    tracing::warn!("Spellcheck disabled: {e:#}");
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    /// Searches the vault as `config`'s viewer sees it.
+    fn searcher<'a>(
+        &'a self,
+        notes_dir: &'a FsPath,
+        config: &'a Config,
+        mode: Mode,
+    ) -> Box<dyn Searcher + 'a> {
+        match (&self.index, &self.semantic, mode) {
+            (_, Some(semantic), Mode::Semantic) => Box::new(semantic.searcher(config)),
+            (Some(index), _, Mode::Words | Mode::Fuzzy) => {
+                Box::new(index.searcher(config, mode == Mode::Fuzzy))
+            }
+            _ => Box::new(VaultSearcher { notes_dir, config, mode }),
+        }
+    }
+}
+
+/// Send the server's log to stderr, as text or one JSON object per line. A
+/// bare `level` like `debug` applies to para's own messages, leaving the
+/// libraries it uses at `warn`; directives like `para=debug,tantivy=info`
+/// are taken as they are.
+pub fn init_logging(level: &str, json: bool) -> Result<()> {
+    let directives = if level.contains('=') {
+        level.to_string()
+    } else {
+        format!("warn,para={level}")
+    };
+    let filter = tracing_subscriber::EnvFilter::try_new(&directives)
+        .with_context(|| format!("invalid --log-level {level}"))?;
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    if json {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+    Ok(())
+}
+
+/// Where the server takes connections.
+pub enum Listen {
+    Tcp(SocketAddr),
+    /// A Unix domain socket, for a reverse proxy on the same machine, with
+    /// the file's permissions deciding who may connect.
+    Unix(PathBuf),
+}
+
+impl Listen {
+    /// `--listen unix:/run/para.sock`.
+    pub fn parse(spec: &str) -> Result<Listen> {
+        match spec.strip_prefix("unix:") {
+            Some(path) if !path.is_empty() => Ok(Listen::Unix(PathBuf::from(path))),
+            _ => anyhow::bail!("--listen takes unix:/path/to.sock; use --host and --port for TCP"),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+/// Listen on a Unix socket at `path`, replacing a socket left behind by an
+/// earlier run.
+#[cfg(unix)]
+fn bind_unix(path: &FsPath) -> Result<Listener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        std::fs::remove_file(path).with_context(|| format!("failed to remove the old socket {}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("failed to listen on {}", path.display()))?;
+    Ok(Listener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_path: &FsPath) -> Result<Listener> {
+    anyhow::bail!("--listen unix: needs a system with Unix sockets")
+}
+
+/// A certificate and its private key, both PEM files, to serve HTTPS with.
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Serve `vaults`, switching between them from the navbar when there's more
+/// than one, or with `mount`, side by side under `/{name}/`. The first is
+/// the one a new visitor sees.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_server(
+    vaults: Vec<Vault>,
+    mount: bool,
+    listen: Listen,
+    sanitize: bool,
+    dev: bool,
+    public_url: Option<String>,
+    open: bool,
+    tls: Option<Tls>,
+    base_path: Option<String>,
+    access_log: Option<PathBuf>,
+    cors_origins: Vec<String>,
+    config: Config,
+) -> Result<()> {
+    let root = base_path
+        .as_deref()
+        .map(|path| path.trim_matches('/'))
+        .filter(|path| !path.is_empty())
+        .map(|path| format!("/{path}"))
+        .unwrap_or_default();
+    let tls = match tls {
+        Some(tls) => Some(RustlsConfig::from_pem_file(&tls.cert, &tls.key).await.with_context(|| {
+            format!(
+                "failed to load the TLS certificate {} and key {}",
+                tls.cert.display(),
+                tls.key.display()
+            )
+        })?),
+        None => None,
+    };
+    let vaults = Arc::new(vaults);
+    let mut routers: Vec<(String, Router)> = vaults
+        .iter()
+        .map(|vault| {
+            let router = vault_router(
+                vault,
+                vaults.clone(),
+                mount,
+                sanitize,
+                dev,
+                public_url.clone(),
+                cors_origins.clone(),
+                config.clone(),
+            );
+            (vault.name.clone(), router)
+        })
+        .collect();
+    let app = if mount {
+        let bases: Arc<Vec<String>> = Arc::new(routers.iter().map(|(name, _)| mount_base(name)).collect());
+        let home = format!("{root}{}/", bases[0]);
+        let mut app = Router::new()
+            .route("/", get(move || async move { Redirect::to(&home) }))
+            .route("/fonts/{*path}", get(handle_fonts))
+            .route("/healthz", get(handle_healthz));
+        // Behind a login, metrics are only under each vault's path, which checks it
+        if config.users.is_empty() && config.tokens.is_empty() {
+            app = app.route("/metrics", get(handle_metrics));
+        }
+        for (name, router) in routers {
+            let mount = Arc::new(Mount {
+                root: root.clone(),
+                base: mount_base(&name),
+                bases: bases.clone(),
+            });
+            app = app.nest_service(&mount.base.clone(), router.layer(middleware::from_fn_with_state(mount, mount_urls)));
+        }
+        app
+    } else if routers.len() == 1 {
+        routers.remove(0).1
+    } else {
+        Router::new()
+            .fallback(dispatch_vault)
+            .with_state(Arc::new(routers))
+    };
+    let app = if root.is_empty() {
+        app
+    } else {
+        let app = if mount {
+            app
+        } else {
+            let mount = Arc::new(Mount {
+                root: root.clone(),
+                base: String::new(),
+                bases: Arc::new(Vec::new()),
+            });
+            app.layer(middleware::from_fn_with_state(mount, mount_urls))
+        };
+        Router::new().nest_service(&root, app)
+    };
+    // Audio and video are compressed already, and scrubbed through by range
+    let compress = DefaultPredicate::new()
+        .and(NotForContentType::const_new("audio/"))
+        .and(NotForContentType::const_new("video/"));
+    let mut app = app.layer(CompressionLayer::new().compress_when(compress));
+    if !cors_origins.is_empty() {
+        app = app.layer(cors(&cors_origins)?);
+    }
+    let limits = &config.limits;
+    app = app.layer(RequestBodyLimitLayer::new(limits.max_body_bytes));
+    if limits.response_timeout_secs > 0 {
+        let timeout = Duration::from_secs(limits.response_timeout_secs);
+        app = app.layer(TimeoutLayer::with_status_code(StatusCode::REQUEST_TIMEOUT, timeout));
+    }
+    if limits.max_concurrent > 0 {
+        // Shared by every route, where a plain concurrency limit would give
+        // each its own
+        app = app.layer(GlobalConcurrencyLimitLayer::new(limits.max_concurrent));
+    }
+    if let Some(path) = access_log {
+        let log = Arc::new(AccessLog::open(&path)?);
+        app = app.layer(middleware::from_fn_with_state(log, log_access));
+    }
+
+    let (listener, url) = match &listen {
+        Listen::Tcp(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            let port = listener.local_addr()?.port();
+            let scheme = if tls.is_some() { "https" } else { "http" };
+            let url = if addr.ip().is_unspecified() || addr.ip().is_loopback() {
+                format!("{scheme}://localhost:{port}{root}")
+            } else {
+                format!("{scheme}://{}{root}", SocketAddr::new(addr.ip(), port))
+            };
+            tracing::info!("Serving notes at {url}");
+            if addr.ip().is_unspecified()
+                && let Some(ip) = lan_ip()
+            {
+                tracing::info!("On your network at {scheme}://{}{root}", SocketAddr::new(ip, port));
+            }
+            (Listener::Tcp(listener), url)
+        }
+        Listen::Unix(path) => {
+            let listener = bind_unix(path)?;
+            let url = format!("http://localhost{root}");
+            tracing::info!("Serving notes at {url} through unix:{}", path.display());
+            (listener, url)
+        }
+    };
+    if mount {
+        for vault in vaults.iter() {
+            tracing::info!("{url}{}/ -> {}", mount_base(&vault.name), vault.path.display());
+        }
+    } else if vaults.len() > 1 {
+        let names: Vec<&str> = vaults.iter().map(|vault| vault.name.as_str()).collect();
+        tracing::info!("Vaults: {}", names.join(", "));
+    }
+    tracing::info!("Live reload enabled - watching for file changes");
+    if sanitize {
+        tracing::info!("Sanitizing note HTML");
+    }
+    if config.read_only {
+        tracing::info!("Read-only: changes to the vault are refused");
+    }
+    if open {
+        open_browser(&url);
+    }
+
+    match (listener, tls) {
+        (Listener::Tcp(listener), Some(tls)) => {
+            axum_server::from_tcp_rustls(listener.into_std()?, tls)?
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await?
+        }
+        (Listener::Tcp(listener), None) => {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?
+        }
+        // Clients through the socket have no address to log or rate-limit
+        #[cfg(unix)]
+        (Listener::Unix(listener), _) => axum::serve(listener, app.into_make_service()).await?,
+    }
+
+    Ok(())
+}
+
+/// Whether a change to `path` should reload open pages: it has one of
+/// `[files] reload_extensions` and isn't hidden, like para's own caches
+/// under `.para/`.
+fn reloads(notes_dir: &FsPath, config: &Config, path: &FsPath) -> bool {
+    let listed = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| config.files.reload_extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)));
+    listed
+        && !vault::relative(notes_dir, path)
+            .split('/')
+            .any(|part| part.starts_with('.'))
 }
 
-pub async fn run_server(notes_dir: PathBuf, port: u16) -> Result<()> {
-    let (reload_tx, _) = broadcast::channel::<String>(16);
+/// Everything one vault needs served: its search index, file watcher and
+/// routes.
+#[allow(clippy::too_many_arguments)]
+fn vault_router(
+    vault: &Vault,
+    vaults: Arc<Vec<Vault>>,
+    mounted: bool,
+    sanitize: bool,
+    dev: bool,
+    public_url: Option<String>,
+    cors_origins: Vec<String>,
+    config: Config,
+) -> Router {
+    let notes_dir = vault.path.clone();
+    let (reload_tx, _) = broadcast::channel::<LiveEvent>(16);
+
+    let index = match SearchIndex::build(&notes_dir, &config) {
+        Ok(index) => {
+            tracing::info!(vault = %vault.name, "Indexed {} notes for search", index.num_notes());
+            METRICS.set_indexed_notes(&vault.name, index.num_notes());
+            Some(Arc::new(index))
+        }
+        Err(e) => {
+            tracing::error!(vault = %vault.name, "Failed to build search index, falling back to scanning: {e}");
+            None
+        }
+    };
+
+    let note_paths = Arc::new(NotePaths::new(&notes_dir, &config));
+    let modified_times = Arc::new(ModifiedTimes::new(&notes_dir, &config));
+    let link_indexes = Arc::new(LinkIndexes::new(&notes_dir));
+    let semantic = (!config.semantic.url.is_empty()).then(|| {
+        SemanticIndex::start(&notes_dir, &config, Box::new(ApiEmbedder::new(&config.semantic)))
+    });
 
     // Start file watcher
     let watcher_tx = reload_tx.clone();
     let watch_dir = notes_dir.clone();
+    let watch_config = config.clone();
+    let watch_index = index.clone();
+    let watch_paths = note_paths.clone();
+    let watch_times = modified_times.clone();
+    let watch_links = link_indexes.clone();
+    let watch_semantic = semantic.clone();
+    let watching = Arc::new(AtomicBool::new(false));
+    let watch_running = watching.clone();
+    let watch_vault = vault.name.clone();
     std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
@@ -78,189 +639,2183 @@ pub async fn run_server(notes_dir: PathBuf, port: u16) -> Result<()> {
             watcher
                 .watch(&watch_dir, RecursiveMode::Recursive)
                 .expect("Failed to watch directory");
+            watch_running.store(true, Ordering::Relaxed);
 
             while let Some(event) = rx.recv().await {
+                METRICS.watcher_event(&watch_vault);
+                let structural = matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+                );
+                if structural || event.paths.iter().any(|p| p.ends_with(config::DIR_CONFIG_FILE)) {
+                    config::forget_dir_configs();
+                }
+                if let EventKind::Modify(ModifyKind::Name(RenameMode::Both)) = event.kind
+                    && let [from, to] = event.paths.as_slice()
+                {
+                    handle_move(&watch_dir, &watch_config, &watcher_tx, from, to);
+                }
                 if matches!(
                     event.kind,
                     EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
                 ) {
-                    let is_md = event.paths.iter().any(|p| {
-                        p.extension().is_some_and(|ext| ext == "md")
-                    });
-                    if is_md {
-                        let path = event
-                            .paths
-                            .first()
-                            .and_then(|p| p.to_str())
-                            .unwrap_or("unknown")
-                            .to_string();
-                        let _ = watcher_tx.send(path);
+                    if structural {
+                        watch_paths.refresh(&watch_config);
+                        watch_times.refresh(&watch_config);
+                    } else {
+                        event.paths.iter().for_each(|path| watch_times.touch(path));
+                    }
+                    watch_links.refresh();
+                    if let Some(semantic) = &watch_semantic {
+                        event.paths.iter().for_each(|path| semantic.queue(path));
+                    }
+                    if let Some(index) = &watch_index {
+                        for path in &event.paths {
+                            if let Err(e) = index.update(&watch_config, path) {
+                                tracing::warn!(vault = %watch_vault, "Failed to index {}: {e}", path.display());
+                            }
+                        }
+                        METRICS.set_indexed_notes(&watch_vault, index.num_notes());
+                    }
+                    if let Some(path) = event.paths.iter().find(|p| reloads(&watch_dir, &watch_config, p)) {
+                        let path = path.to_str().unwrap_or("unknown").to_string();
+                        let _ = watcher_tx.send(LiveEvent::Reload(path));
                     }
                 }
             }
+            watch_running.store(false, Ordering::Relaxed);
         });
     });
 
+    let link_previews = LinkPreviews::new(&notes_dir);
+    let diagrams = Diagrams::new(&notes_dir);
+    let rate_limiter = rate_limiter(&config.rate_limit);
+    let upload_limit = config.limits.max_body_bytes;
+
     let state = Arc::new(AppState {
         notes_dir,
+        vault: vault.name.clone(),
+        vaults,
+        mounted,
         reload_tx,
+        public_url,
+        cors_origins,
+        config,
+        link_previews,
+        diagrams,
+        sanitize,
+        dev,
+        speller: OnceLock::new(),
+        index,
+        semantic,
+        note_paths,
+        modified_times,
+        link_indexes,
+        watching,
+        rate_limiter,
+    });
+
+    Router::new()
+        .route("/", get(handle_root))
+        .route("/search", get(handle_search))
+        .route("/search/suggest", get(handle_search_suggest))
+        .route("/quickswitch", get(handle_quickswitch))
+        .route("/archive", get(handle_archive))
+        .route("/attachments", get(handle_attachments))
+        .route("/resolve", get(handle_resolve))
+        .route("/broken-links", get(handle_broken_links))
+        .route("/graph", get(handle_graph))
+        .route("/graph.json", get(handle_graph_json))
+        .route("/reports/orphans", get(handle_orphans))
+        .route("/lint", get(handle_lint))
+        .route("/tags/{*tag}", get(handle_tag))
+        .route("/audit", get(handle_audit))
+        .route("/recent", get(handle_recent))
+        .route("/upcoming", get(handle_upcoming))
+        .route("/tasks", get(handle_tasks))
+        .route("/review", get(handle_review))
+        .route("/projects", get(handle_projects))
+        .route("/journal/{date}", get(handle_journal))
+        .route("/today", get(handle_today))
+        .route("/random", get(handle_random))
+        .route("/save", post(handle_save))
+        .route("/rename", post(handle_rename))
+        .route(
+            "/upload",
+            post(handle_upload).layer(DefaultBodyLimit::max(upload_limit)),
+        )
+        .route(
+            "/paste-image",
+            post(handle_paste_image).layer(DefaultBodyLimit::max(upload_limit)),
+        )
+        .route("/relink", post(handle_relink))
+        .route("/toggle-task", post(handle_toggle_task))
+        .route("/archive-project", post(handle_archive_project))
+        .route("/raw/{*path}", get(handle_raw))
+        .route("/edit/{*path}", get(handle_edit))
+        .route("/ws", get(handle_websocket))
+        .route("/fonts/{*path}", get(handle_fonts))
+        .route("/assets/{file}", get(handle_asset))
+        .route("/og/{*path}", get(handle_og))
+        .route("/thumb/{*path}", get(handle_thumb))
+        .route("/trash", get(handle_trash))
+        .route("/trash/restore", post(handle_trash_restore))
+        .route("/trash/purge", post(handle_trash_purge))
+        .route("/switch-vault/{name}", get(handle_switch_vault))
+        .route("/metrics", get(handle_metrics))
+        .route("/{*path}", get(handle_path).put(handle_put).delete(handle_delete))
+        .layer(middleware::from_fn_with_state(state.clone(), error_pages))
+        .layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        // For load balancers and uptime monitors, which don't sign in
+        .route("/healthz", get(handle_healthz))
+        .route("/readyz", get(handle_readyz))
+        .layer(middleware::from_fn_with_state(state.clone(), track_requests))
+        .with_state(state)
+}
+
+tokio::task_local! {
+    /// What went wrong in the request being handled, as recorded by
+    /// [`internal`] for [`error_pages`] to show.
+    static FAILURE: RefCell<Option<anyhow::Error>>;
+}
+
+/// Log an error a handler can't recover from and answer 500, keeping the
+/// error for the error page.
+fn internal(error: impl Into<anyhow::Error>) -> StatusCode {
+    let error = error.into();
+    tracing::error!("{error:#}");
+    let _ = FAILURE.try_with(|failure| *failure.borrow_mut() = Some(error));
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Give server errors without a body of their own a page in the usual
+/// layout, for browsers. With `--dev` it names the failing path and the
+/// chain of errors behind it; otherwise it only apologises.
+async fn error_pages(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let is_htmx = request.headers().contains_key("hx-request");
+    let wants_html = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let config = request.extensions().get::<Arc<Config>>().cloned();
+    let (response, failure) = FAILURE
+        .scope(RefCell::new(None), async move {
+            let response = next.run(request).await;
+            (response, FAILURE.with(|failure| failure.take()))
+        })
+        .await;
+
+    let status = response.status();
+    if !status.is_server_error() || response.headers().contains_key(header::CONTENT_TYPE) || !(wants_html || is_htmx) {
+        return response;
+    }
+    let Some(config) = config else {
+        return response;
+    };
+    let mut content = format!(
+        "<h1>{}</h1>\n",
+        html_escape(status.canonical_reason().unwrap_or("Server error"))
+    );
+    if state.dev {
+        content.push_str(&format!("<p>While serving <code>{}</code>:</p>\n", html_escape(&path)));
+        match failure {
+            Some(error) => {
+                content.push_str("<ol class=\"error-chain\">\n");
+                for cause in error.chain() {
+                    content.push_str(&format!("<li>{}</li>\n", html_escape(&cause.to_string())));
+                }
+                content.push_str("</ol>\n");
+            }
+            None => content.push_str("<p>The handler gave no details; see the server log.</p>\n"),
+        }
+    } else {
+        content.push_str("<p>Something went wrong on the server. The error has been logged.</p>\n");
+    }
+    let title = status.canonical_reason().unwrap_or("Server error");
+    let mut page = build_response(&state, &config, title, &content, "", is_htmx, None, config.theme);
+    *page.status_mut() = status;
+    page
+}
+
+/// Log each request in its own span, and count and time it for `/metrics`.
+async fn track_requests(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let method = request.method().clone();
+    let span = tracing::info_span!("request", vault = %state.vault, %method, path = %request.uri().path());
+    let start = Instant::now();
+    let response = next.run(request).instrument(span.clone()).await;
+    let elapsed = start.elapsed();
+    let status = response.status().as_u16();
+    span.in_scope(|| tracing::info!(status, duration_ms = elapsed.as_secs_f64() * 1000.0, "answered"));
+    METRICS.request(&state.vault, &route, method.as_str(), status, elapsed);
+    response
+}
+
+/// Where a mounted vault is in the URL space, and where every mount is.
+struct Mount {
+    /// `--base-path` the whole server sits under behind a proxy, or empty.
+    root: String,
+    /// `/name`, percent-encoded, or empty when serving without `--mount`.
+    base: String,
+    bases: Arc<Vec<String>>,
+}
+
+impl Mount {
+    /// `url` under this mount, or `None` if it isn't root-relative or
+    /// already points into a mount and there's no base path to add.
+    fn prefix(&self, url: &str) -> Option<String> {
+        if !url.starts_with('/') || url.starts_with("//") {
+            return None;
+        }
+        let mounted = self.bases.iter().any(|base| {
+            url.strip_prefix(base.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?', '#']))
+        });
+        if !mounted {
+            Some(format!("{}{}{url}", self.root, self.base))
+        } else if !self.root.is_empty() {
+            Some(format!("{}{url}", self.root))
+        } else {
+            None
+        }
+    }
+
+    /// Where the mount's pages start: what scripts prefix their requests
+    /// with.
+    fn url_base(&self) -> String {
+        format!("{}{}", self.root, self.base)
+    }
+
+    fn prefix_urls(&self, html: &str) -> String {
+        ROOT_URL_RE
+            .replace_all(html, |caps: &regex::Captures| {
+                let url = self.prefix(&caps[2]).unwrap_or_else(|| caps[2].to_string());
+                format!("{}{url}\"", &caps[1])
+            })
+            .into_owned()
+    }
+}
+
+/// The prefix a vault is mounted at.
+fn mount_base(name: &str) -> String {
+    format!("/{}", encode_path(name))
+}
+
+/// Move a mounted vault's pages under its prefix: root-relative links and
+/// redirects gain it, and the page learns it as `data-base` for scripts.
+/// Links already under a mount are left alone, so notes can link across
+/// mounts with `/work/...`.
+async fn mount_urls(State(mount): State<Arc<Mount>>, request: Request, next: Next) -> Response {
+    let is_htmx = request.headers().contains_key("hx-request");
+    let (mut parts, body) = next.run(request).await.into_parts();
+    if let Some(location) = parts
+        .headers
+        .get(header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|location| mount.prefix(location))
+        .and_then(|location| location.parse().ok())
+    {
+        parts.headers.insert(header::LOCATION, location);
+    }
+    let is_html = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return Response::from_parts(parts, body);
+    }
+
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let html = mount.prefix_urls(&String::from_utf8_lossy(&bytes));
+    let html = if html.contains("<html lang=\"en\"") {
+        html.replacen("<html lang=\"en\"", &format!("<html lang=\"en\" data-base=\"{}\"", mount.url_base()), 1)
+    } else if is_htmx {
+        // Content swapped into a page that may be another mount's
+        format!("<script>document.documentElement.dataset.base='{}';</script>{html}", mount.url_base())
+    } else {
+        html
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(html))
+}
+
+/// Hand a request to the vault named in its `para-vault` cookie, or to the
+/// first vault.
+async fn dispatch_vault(State(routers): State<Arc<Vec<(String, Router)>>>, request: Request) -> Response {
+    let name = request
+        .headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix(VAULT_COOKIE)?.strip_prefix('='))
+        .map(attachments::percent_decode);
+    let (_, router) = routers
+        .iter()
+        .find(|(vault, _)| Some(vault) == name.as_ref())
+        .unwrap_or(&routers[0]);
+    match router.clone().oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
+
+/// Open another vault: remember it in a cookie and go to its root.
+async fn handle_switch_vault(State(state): State<Arc<AppState>>, Path(name): Path<String>) -> Response {
+    if state.mounted || !state.vaults.iter().any(|vault| vault.name == name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let cookie = format!(
+        "{VAULT_COOKIE}={}; Path=/; Max-Age=31536000; SameSite=Lax",
+        encode_path(&name)
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
+
+/// The navbar's vault picker, when there are vaults to pick from.
+fn render_vault_switcher(state: &AppState) -> String {
+    if state.mounted || state.vaults.len() < 2 {
+        return String::new();
+    }
+    let options: String = state
+        .vaults
+        .iter()
+        .map(|vault| {
+            let selected = if vault.name == state.vault { " selected" } else { "" };
+            format!("<option{selected}>{}</option>", html_escape(&vault.name))
+        })
+        .collect();
+    format!("\n        <select class=\"vault-switcher\" aria-label=\"Vault\">{options}</select>")
+}
+
+/// This machine's address on the local network: the one it would send from
+/// to reach the internet. Connecting a UDP socket sends nothing.
+fn lan_ip() -> Option<IpAddr> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_loopback() && !ip.is_unspecified()).then_some(ip)
+}
+
+/// Open `url` in the default browser. Failing to is only worth a warning;
+/// the URL is printed either way.
+fn open_browser(url: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let opened = command
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+    if let Err(e) = opened {
+        tracing::warn!("Couldn't open a browser: {e}");
+    }
+}
+
+/// Sign users in with HTTP basic auth, or clients with an API token, when
+/// accounts or tokens are configured, and hand handlers the config as that
+/// user sees it. Read-only users can only read, and other sites' pages can't
+/// change anything.
+async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) && cross_site(&state, &request) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let viewer = if state.config.users.is_empty() && state.config.tokens.is_empty() {
+        None
+    } else {
+        let authorization = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let bearer = authorization.starts_with("Bearer ");
+        let user = if bearer {
+            auth::authenticate_token(&state.config.tokens, authorization)
+        } else {
+            auth::authenticate(&state.config.users, authorization).cloned()
+        };
+        match user {
+            Some(user) => Some(user),
+            None => {
+                let challenge = if bearer { "Bearer realm=\"para\"" } else { "Basic realm=\"para\"" };
+                return (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, challenge)]).into_response();
+            }
+        }
+    };
+
+    if !matches!(*request.method(), Method::GET | Method::HEAD)
+        && (state.config.read_only || viewer.as_ref().is_some_and(|u| u.role != Role::Editor))
+    {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    request
+        .extensions_mut()
+        .insert(Arc::new(state.config.for_viewer(viewer.as_ref())));
+    let mut response = next.run(request).await;
+    if let Some(viewer) = viewer {
+        response.extensions_mut().insert(RemoteUser(viewer.name));
+    }
+    response
+}
+
+/// Whether `request` was sent by a page on another site: its `Origin` is
+/// neither this server (as the client or `--public-url` names it) nor one
+/// of the `--cors-origin`s, or, from browsers that leave `Origin` out, its
+/// `Sec-Fetch-Site` says so. Clients other than browsers send neither.
+fn cross_site(state: &AppState, request: &Request) -> bool {
+    let headers = request.headers();
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return headers
+            .get("sec-fetch-site")
+            .is_some_and(|site| site != "same-origin" && site != "none");
+    };
+    let Ok(origin) = origin.to_str() else {
+        return true;
+    };
+    if state
+        .cors_origins
+        .iter()
+        .any(|allowed| allowed == "*" || allowed.trim_end_matches('/') == origin)
+    {
+        return false;
+    }
+    if state
+        .public_url
+        .as_deref()
+        .and_then(|url| url.parse::<Uri>().ok())
+        .is_some_and(|url| url.scheme().zip(url.authority()).is_some_and(|(scheme, authority)| {
+            origin == format!("{scheme}://{authority}")
+        }))
+    {
+        return false;
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| request.uri().authority().map(|authority| authority.as_str()));
+    origin.split_once("://").map(|(_, origin_host)| origin_host) != host
+}
+
+/// Let pages from `origins` (or anywhere, for `*`) call the server from the
+/// browser, sending a login or token in `Authorization`.
+fn cors(origins: &[String]) -> Result<CorsLayer> {
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| {
+                header::HeaderValue::from_str(origin.trim_end_matches('/'))
+                    .with_context(|| format!("invalid --cors-origin {origin}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::HEAD, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, header::IF_MATCH])
+        .expose_headers([header::ETAG]))
+}
+
+/// A limiter for each client's address, forgetting clients that have gone
+/// quiet every minute.
+fn rate_limiter(config: &RateLimitConfig) -> Option<Arc<DefaultKeyedRateLimiter<IpAddr>>> {
+    let burst = NonZeroU32::new(config.burst)?;
+    let quota = Quota::with_period(Duration::from_millis(config.refill_ms))?.allow_burst(burst);
+    let limiter = Arc::new(RateLimiter::keyed(quota));
+    let sweep = Arc::downgrade(&limiter);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let Some(limiter) = sweep.upgrade() else { break };
+            limiter.retain_recent();
+        }
     });
+    Some(limiter)
+}
+
+/// Turn away clients searching, changing the vault or trying logins faster
+/// than `[rate_limit]` allows: searches and password hashes are slow, so a
+/// flood of them could take the host down, and logins could be guessed.
+/// Anything but `GET` and `HEAD` counts as a change, so new endpoints that
+/// write are limited too. A login that already worked costs nothing.
+async fn rate_limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let new_login = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| value.to_str().map_or(true, |value| !auth::is_known(value)));
+    let limited = request.uri().path().starts_with("/search")
+        || !matches!(*request.method(), Method::GET | Method::HEAD)
+        || new_login;
+    if let (true, Some(limiter), Some(ConnectInfo(addr))) = (
+        limited,
+        &state.rate_limiter,
+        request.extensions().get::<ConnectInfo<SocketAddr>>(),
+    ) && let Err(not_until) = limiter.check_key(&addr.ip())
+    {
+        let wait = not_until.wait_time_from(DefaultClock::default().now());
+        let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Who a response was for, as the access log records them.
+#[derive(Clone)]
+struct RemoteUser(String);
+
+/// Write a line to the access log for every request.
+async fn log_access(State(log): State<Arc<AccessLog>>, request: Request, next: Next) -> Response {
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let headers = request.headers();
+    let referer = headers.get(header::REFERER).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let user_agent = headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let line = format!(
+        "{} {} {:?}",
+        request.method(),
+        request.uri().path_and_query().map_or("/", |p| p.as_str()),
+        request.version()
+    );
+    let time = chrono::Local::now();
+
+    let response = next.run(request).await;
+    log.write(&access_log::Entry {
+        client,
+        user: response.extensions().get::<RemoteUser>().map(|user| user.0.as_str()),
+        time,
+        request: &line,
+        status: response.status().as_u16(),
+        bytes: response.body().size_hint().exact(),
+        referer: referer.as_deref(),
+        user_agent: user_agent.as_deref(),
+    });
+    response
+}
+
+async fn handle_root(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    serve_path(&state, &config, &state.notes_dir, "", None, is_htmx, None).await
+}
+
+async fn handle_search(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let query = params.q.unwrap_or_default();
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    let theme = config.theme;
+
+    if query.is_empty() {
+        let content = "<p>Enter a search term above.</p>";
+        return Ok(build_response(&state, &config, "Search", content, &query, is_htmx, None, theme));
+    }
+
+    let modes = render_search_modes(&query, params.mode, state.semantic.is_some());
+    if params.mode == Mode::Semantic && state.semantic.is_none() {
+        let content = format!(
+            "<h1>Semantic search is off</h1>\n{modes}<p>Set <code>[semantic] url</code> to an embeddings endpoint to turn it on.</p>"
+        );
+        return Ok(build_response(&state, &config, "Search", &content, &query, is_htmx, None, theme));
+    }
+    if let Mode::Regex = params.mode
+        && let Err(e) = search::mode_regex(&query, Mode::Regex)
+    {
+        let content = format!(
+            "<h1>Invalid regex</h1>\n{modes}<pre>{}</pre>",
+            html_escape(&e.to_string())
+        );
+        return Ok(build_response(&state, &config, "Search", &content, &query, is_htmx, None, theme));
+    }
+
+    let page = params.page.unwrap_or(1).max(1);
+    let searcher = state.searcher(&notes_canonical, &config, params.mode);
+    let results = searcher.search(&query, None, page);
+
+    if results.total == 0 {
+        let content = format!("<h1>No results for \"{}\"</h1>\n{modes}", html_escape(&query));
+        return Ok(build_response(&state, &config, "Search", &content, &query, is_htmx, None, theme));
+    }
+
+    let content = format!(
+        "{}{modes}{}{}",
+        search_heading(&query),
+        render_search_results(&results.matches, &query, params.mode, searcher.highlight(&query).as_ref()),
+        render_pagination("/search", &query, params.mode, page, &results)
+    );
+    Ok(build_response(
+        &state,
+        &config,
+        &format!("Search: {}", query),
+        &content,
+        &query,
+        is_htmx,
+        None,
+        theme,
+    ))
+}
+
+/// Notes whose name contains the query, as a fragment for the search box's
+/// dropdown.
+async fn handle_search_suggest(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Html<String>, StatusCode> {
+    let query = params.q.unwrap_or_default();
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    let paths = state
+        .searcher(&notes_canonical, &config, Mode::Words)
+        .suggest(&query);
+    Ok(Html(render_suggestions(&paths)))
+}
+
+/// Notes whose path fuzzily matches the query, as a fragment for the quick
+/// switcher.
+async fn handle_quickswitch(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<SearchParams>,
+) -> Html<String> {
+    let matches = state.note_paths.matching(&config, &params.q.unwrap_or_default());
+    let mut html = String::new();
+    for (i, m) in matches.iter().enumerate() {
+        let mut label = String::new();
+        let mut marking = false;
+        for (j, c) in m.path.chars().enumerate() {
+            let matched = m.positions.contains(&j);
+            if matched != marking {
+                label.push_str(if matched { "<mark>" } else { "</mark>" });
+                marking = matched;
+            }
+            label.push_str(&html_escape(&c.to_string()));
+        }
+        if marking {
+            label.push_str("</mark>");
+        }
+        let selected = if i == 0 { " class=\"selected\"" } else { "" };
+        html.push_str(&format!(
+            "<li{selected}><a href=\"{}\">{label}</a></li>\n",
+            html_escape(&encode_path(&format!("/{}", m.path)))
+        ));
+    }
+    Html(html)
+}
+
+async fn handle_archive(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<SearchParams>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let query = params.q.unwrap_or_default();
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    let theme = config.theme;
+
+    let Some(archive_dir) = para::category_dir(&notes_canonical, Category::Archive) else {
+        let content = "<h1>Archive</h1>\n<p>No archive folder found.</p>";
+        return Ok(build_response(&state, &config, "Archive", content, "", is_htmx, None, theme));
+    };
+
+    let mut content = format!(
+        r#"<h1>Archive</h1>
+<form class="scoped-search" action="/archive" method="get" hx-get="/archive" hx-target="main" hx-push-url="true">
+    <input type="text" name="q" placeholder="Search archive..." value="{}" />
+    <button type="submit">Search</button>
+</form>
+"#,
+        html_escape(&query)
+    );
+
+    if query.is_empty() {
+        let items: Vec<_> = archive::collect(&notes_canonical)
+            .into_iter()
+            .filter(|item| !config.is_private(&notes_canonical, &notes_canonical.join(&item.path)))
+            .collect();
+        content.push_str(&render_archive(&items));
+    } else {
+        let scope = archive_dir
+            .strip_prefix(&notes_canonical)
+            .unwrap_or(&archive_dir)
+            .to_string_lossy()
+            .into_owned();
+        let page = params.page.unwrap_or(1).max(1);
+        let searcher = state.searcher(&notes_canonical, &config, params.mode);
+        let results = searcher.search(&query, Some(&scope), page);
+        if results.total == 0 {
+            content.push_str(&format!("<p>No results for \"{}\"</p>", html_escape(&query)));
+        } else {
+            content.push_str(&search_heading(&query));
+            content.push_str(&render_search_results(
+                &results.matches,
+                &query,
+                params.mode,
+                searcher.highlight(&query).as_ref(),
+            ));
+            content.push_str(&render_pagination("/archive", &query, params.mode, page, &results));
+        }
+    }
+
+    Ok(build_response(&state, &config, "Archive", &content, "", is_htmx, None, theme))
+}
+
+async fn handle_attachments(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let items = attachments::collect(&notes_canonical, &config);
+    let content = render_attachments(&items);
+    Ok(build_response(&state, &config, "Attachments", &content, "", is_htmx, None, config.theme))
+}
+
+/// Target of wikilinks that don't point at exactly one note: redirects when
+/// the name resolves, otherwise lists the candidates.
+async fn handle_resolve(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<ResolveParams>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let theme = config.theme;
+
+    let index = state.link_indexes.get(&config);
+    let link = Link {
+        target: params.name.clone(),
+        kind: LinkKind::Wiki,
+    };
+    let name = html_escape(&params.name);
+    match index.lookup("", &link) {
+        Resolution::Found(path) => Ok(Redirect::to(&encode_path(&format!("/{path}"))).into_response()),
+        Resolution::Ambiguous(candidates) => {
+            let mut content = format!(
+                "<h1>{name}</h1>\n<p>{} notes are called \"{name}\":</p>\n<ul class=\"disambiguation\">\n",
+                candidates.len()
+            );
+            for path in &candidates {
+                content.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    html_escape(&encode_path(&format!("/{path}"))),
+                    html_escape(path)
+                ));
+            }
+            content.push_str("</ul>");
+            Ok(build_response(&state, &config, &params.name, &content, "", is_htmx, None, theme))
+        }
+        Resolution::Missing => {
+            let content = format!("<h1>{name}</h1>\n<p>No note is called \"{name}\".</p>");
+            let mut response = build_response(&state, &config, &params.name, &content, "", is_htmx, None, theme);
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            Ok(response)
+        }
+    }
+}
+
+async fn handle_graph(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Response {
+    let is_htmx = headers.contains_key("hx-request");
+    let content = "<h1>Graph</h1>\n<div class=\"link-graph\" data-src=\"/graph.json\"></div>";
+    build_response(&state, &config, "Graph", content, "", is_htmx, None, config.theme)
+}
+
+async fn handle_graph_json(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Json<Graph>, StatusCode> {
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    Ok(Json(graph::build(&notes_canonical, &config)))
+}
+
+#[derive(Deserialize)]
+struct OrphansParams {
+    /// Lowercased PARA category to limit the report to.
+    folder: Option<String>,
+}
+
+async fn handle_orphans(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<OrphansParams>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let folder = params.folder.filter(|f| !f.is_empty());
+    let orphans: Vec<String> = graph::build(&notes_canonical, &config)
+        .nodes
+        .into_iter()
+        .filter(|node| node.degree == 0)
+        .filter(|node| folder.is_none() || node.category == folder)
+        .map(|node| node.id)
+        .collect();
+    let content = render_orphans(&orphans, folder.as_deref());
+    Ok(build_response(&state, &config, "Orphaned notes", &content, "", is_htmx, None, config.theme))
+}
+
+fn render_orphans(orphans: &[String], folder: Option<&str>) -> String {
+    let filters: Vec<String> = std::iter::once(None)
+        .chain(Category::ALL.iter().map(|c| Some(c.name())))
+        .map(|name| {
+            let value = name.map(str::to_lowercase);
+            let class = if value.as_deref() == folder { " class=\"current\"" } else { "" };
+            let query = value.map(|v| format!("?folder={v}")).unwrap_or_default();
+            format!(
+                "<a href=\"/reports/orphans{query}\"{class}>{}</a>",
+                name.unwrap_or("All")
+            )
+        })
+        .collect();
+    let mut html = format!(
+        "<h1>Orphaned notes</h1>\n<p class=\"report-filter\">Folder: {}</p>\n<p>{} notes link nowhere and have no backlinks.</p>\n",
+        filters.join(" · "),
+        orphans.len()
+    );
+    if orphans.is_empty() {
+        return html;
+    }
+
+    html.push_str("<ul class=\"orphans\">\n");
+    for path in orphans {
+        html.push_str(&format!(
+            "<li><a href=\"{href}\">{path}</a></li>\n",
+            href = html_escape(&encode_path(&format!("/{path}"))),
+            path = html_escape(path)
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+async fn handle_broken_links(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let index = state.link_indexes.get(&config);
+    let notes: Vec<Note> = vault::files(&notes_canonical, &config)
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .filter_map(|p| Note::load(&notes_canonical, p))
+        .collect();
+    let content = render_broken_links(&notes, &index);
+    Ok(build_response(&state, &config, "Broken links", &content, "", is_htmx, None, config.theme))
+}
+
+fn render_broken_links(notes: &[Note], index: &LinkIndex) -> String {
+    let mut rows = String::new();
+    let mut count = 0;
+    for note in notes {
+        for link in &note.links {
+            let status = match index.lookup(&note.path, link) {
+                Resolution::Found(_) => continue,
+                Resolution::Missing => "missing".to_string(),
+                Resolution::Ambiguous(candidates) => format!(
+                    "<a href=\"/resolve?name={}\">ambiguous</a> ({} notes)",
+                    html_escape(&encode_path(&link.target)),
+                    candidates.len()
+                ),
+            };
+            count += 1;
+            rows.push_str(&format!(
+                "<tr><td><a href=\"{href}\">{path}</a></td><td><code>{target}</code></td><td>{status}</td></tr>\n",
+                href = html_escape(&encode_path(&format!("/{}", note.path))),
+                path = html_escape(&note.path),
+                target = html_escape(&link.target),
+                status = status
+            ));
+        }
+    }
+
+    let mut html = format!("<h1>Broken links</h1>\n<p>{count} links don't resolve to a single note.</p>\n");
+    if count > 0 {
+        html.push_str("<table class=\"broken-links\">\n<tr><th>Note</th><th>Link</th><th>Problem</th></tr>\n");
+        html.push_str(&rows);
+        html.push_str("</table>");
+    }
+    html
+}
+
+/// Open the daily note for a date, creating it first if needed.
+async fn handle_journal(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(date): Path<String>,
+) -> Result<Response, StatusCode> {
+    let date = due::parse_date(&date).ok_or(StatusCode::NOT_FOUND)?;
+    let relative = journal::daily_path(&config.journal, date);
+    if relative.split('/').any(|part| part == "..") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let path = state.notes_dir.join(&relative);
+    if config.is_private(&state.notes_dir, &path) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    if !path.exists() {
+        // Only users who could save the note may create it
+        let dir = path.parent().unwrap_or(&state.notes_dir);
+        if !config.for_dir(&state.notes_dir, dir).can_write() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let (_, created) = journal::create(&state.notes_dir, &config.journal, date).map_err(internal)?;
+        if created {
+            audit(&config, &state.notes_dir, Action::Create, &relative, None);
+        }
+    }
+    Ok(Redirect::to(&encode_path(&format!("/{relative}"))).into_response())
+}
+
+/// Open today's daily note.
+async fn handle_today() -> Redirect {
+    let today = chrono::Local::now().date_naive();
+    Redirect::to(&format!("/journal/{}", today.format("%Y-%m-%d")))
+}
+
+#[derive(Deserialize)]
+struct RandomParams {
+    /// A PARA category (`resources`) or a folder path to pick from.
+    folder: Option<String>,
+}
+
+/// Redirect to a note picked at random, from the whole vault or one folder.
+async fn handle_random(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<RandomParams>,
+) -> Result<Response, StatusCode> {
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    let folder = match params.folder.as_deref().map(|f| f.trim_matches('/')).filter(|f| !f.is_empty()) {
+        None => notes_canonical.clone(),
+        Some(folder) => Category::from_dir_name(folder)
+            .and_then(|category| para::category_dir(&notes_canonical, category))
+            .or_else(|| notes_canonical.join(folder).canonicalize().ok())
+            .filter(|dir| dir.starts_with(&notes_canonical) && dir.is_dir())
+            .ok_or(StatusCode::NOT_FOUND)?,
+    };
+
+    let notes: Vec<PathBuf> = vault::files(&notes_canonical, &config)
+        .into_iter()
+        .filter(|path| path.starts_with(&folder) && path.extension().is_some_and(|e| e == "md"))
+        .collect();
+    if notes.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    // A fresh RandomState is randomly seeded, which is all a pick needs
+    let pick = std::collections::hash_map::RandomState::new().hash_one(notes.len()) as usize % notes.len();
+    let relative = vault::relative(&notes_canonical, &notes[pick]);
+    Ok((StatusCode::FOUND, [(header::LOCATION, encode_path(&format!("/{relative}")))]).into_response())
+}
+
+/// Links to the daily notes either side of `date` (or the days either side,
+/// at the ends of the journal), to today, and to each day of its week,
+/// marking the days that have a note. `dates` are the days with a daily
+/// note, of which only those `visible` to the viewer count.
+fn render_journal_nav(
+    date: chrono::NaiveDate,
+    today: chrono::NaiveDate,
+    dates: &std::collections::BTreeSet<chrono::NaiveDate>,
+    visible: impl Fn(chrono::NaiveDate) -> bool,
+) -> String {
+    let link = |date: chrono::NaiveDate, label: String| {
+        format!(
+            "<a href=\"/journal/{}\">{}</a>",
+            date.format("%Y-%m-%d"),
+            html_escape(&label)
+        )
+    };
+    let prev = dates
+        .range(..date)
+        .rev()
+        .copied()
+        .find(|d| visible(*d))
+        .unwrap_or(date - chrono::Duration::days(1));
+    let next = dates
+        .range(date.succ_opt().unwrap_or(date)..)
+        .copied()
+        .find(|d| visible(*d))
+        .unwrap_or(date + chrono::Duration::days(1));
+
+    let mut week = String::new();
+    for day in date.week(chrono::Weekday::Mon).first_day().iter_days().take(7) {
+        let mut class = vec!["day"];
+        if day == date {
+            class.push("current");
+        }
+        if day == today {
+            class.push("today");
+        }
+        if dates.contains(&day) && visible(day) {
+            class.push("written");
+        }
+        week.push_str(&format!(
+            "<a class=\"{}\" href=\"/journal/{}\" title=\"{}\"><span class=\"weekday\">{}</span>{}</a>",
+            class.join(" "),
+            day.format("%Y-%m-%d"),
+            html_escape(&journal::display(day)),
+            day.format("%a"),
+            day.day()
+        ));
+    }
+    format!(
+        "<nav class=\"journal-nav\">{} <a href=\"/today\">Today</a> {}</nav>\n<nav class=\"journal-week\">{week}</nav>\n",
+        link(prev, format!("← {}", journal::display(prev))),
+        link(next, format!("{} →", journal::display(next)))
+    )
+}
+
+#[derive(Deserialize)]
+struct RecentParams {
+    limit: Option<usize>,
+}
+
+/// The notes modified last, newest first.
+async fn handle_recent(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<RecentParams>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes = state
+        .modified_times
+        .latest(&config, params.limit.unwrap_or(RECENT_LIMIT).max(1));
+    let content = render_recent(&notes);
+    Ok(build_response(&state, &config, "Recently modified", &content, "", is_htmx, None, config.theme))
+}
+
+async fn handle_upcoming(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let items = due::collect(&notes_canonical, &config);
+    let today = chrono::Local::now().date_naive();
+    let content = render_upcoming(&items, today);
+    Ok(build_response(&state, &config, "Upcoming", &content, "", is_htmx, None, config.theme))
+}
+
+/// Every open task in the vault, grouped by PARA category and project.
+async fn handle_tasks(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let filter = tasks::Filter { tags: &[], due: None };
+    let notes = tasks::collect(&notes_canonical, &config, &filter, chrono::Local::now().date_naive());
+    let content = render_tasks(&notes, &notes_canonical, &config);
+    Ok(build_response(&state, &config, "Tasks", &content, "", is_htmx, None, config.theme))
+}
+
+/// Projects grouped by their `status:`, soonest due first.
+async fn handle_projects(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let projects = projects::collect(&notes_canonical, &config);
+    let today = chrono::Local::now().date_naive();
+    let content = render_projects(&projects, today);
+    Ok(build_response(&state, &config, "Projects", &content, "", is_htmx, None, config.theme))
+}
+
+fn render_projects(projects: &[ProjectStatus], today: chrono::NaiveDate) -> String {
+    let mut html = String::from("<h1>Projects</h1>\n");
+    if projects.is_empty() {
+        html.push_str("<p>No projects yet. Add a folder or note under <code>1 Projects/</code>.</p>");
+        return html;
+    }
+
+    for status in Status::ALL {
+        let group: Vec<_> = projects.iter().filter(|p| p.status == status).collect();
+        if group.is_empty() {
+            continue;
+        }
+        html.push_str(&format!(
+            "<h2>{} ({})</h2>\n<ul class=\"projects status-{}\">\n",
+            status.label(),
+            group.len(),
+            status.label().to_lowercase().replace(' ', "-")
+        ));
+        for project in group {
+            let due = match project.due {
+                Some(due) => {
+                    let overdue = due < today && status != Status::Done;
+                    format!(
+                        " <span class=\"due-date{}\">due {}</span>",
+                        if overdue { " overdue" } else { "" },
+                        if due.year() == today.year() {
+                            due.format("%a %b %-d")
+                        } else {
+                            due.format("%b %-d, %Y")
+                        }
+                    )
+                }
+                None => String::new(),
+            };
+            html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a>{due}</li>\n",
+                html_escape(&encode_path(&format!("/{}", project.path))),
+                html_escape(&project.name)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    html
+}
+
+/// The weekly review: what changed, what's open, what's stalled, and what
+/// needs filing.
+async fn handle_review(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let today = chrono::Local::now().date_naive();
+    let review = review::collect(&notes_canonical, &config, today);
+    let content = render_review(&review, today, config.review.stale_days);
+    Ok(build_response(&state, &config, "Weekly review", &content, "", is_htmx, None, config.theme))
+}
+
+fn render_review(review: &Review, today: chrono::NaiveDate, stale_days: u32) -> String {
+    let link = |path: &str, label: &str| {
+        format!(
+            "<a href=\"{}\">{}</a>",
+            html_escape(&encode_path(&format!("/{path}"))),
+            html_escape(label)
+        )
+    };
+    let day = |date: chrono::NaiveDate| match (today - date).num_days() {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        2..7 => date.format("%A").to_string(),
+        _ => date.format("%b %-d, %Y").to_string(),
+    };
+    let mut html = String::from("<h1>Weekly review</h1>\n");
+
+    html.push_str(&format!("<h2>Inbox ({})</h2>\n", review.inbox.len()));
+    if review.inbox.is_empty() {
+        html.push_str("<p>Nothing to file.</p>\n");
+    } else {
+        html.push_str("<ul class=\"review\">\n");
+        for item in &review.inbox {
+            let name = item.path.rsplit('/').next().unwrap_or(&item.path).trim_end_matches(".md");
+            html.push_str(&format!(
+                "<li>{} <span class=\"review-date\">{}</span></li>\n",
+                link(&item.path, name),
+                day(item.modified)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str("<h2>Projects</h2>\n");
+    if review.projects.is_empty() {
+        html.push_str("<p>No projects.</p>\n");
+    } else {
+        html.push_str("<ul class=\"review\">\n");
+        for project in &review.projects {
+            let stale = if project.is_stale(today, stale_days) {
+                " <span class=\"review-stale\">stale</span>"
+            } else {
+                ""
+            };
+            let last = project
+                .last_edit
+                .map(|date| format!(" <span class=\"review-date\">last edited {}</span>", day(date)))
+                .unwrap_or_default();
+            let tasks = match project.tasks.len() {
+                0 => String::new(),
+                1 => " — 1 open task".to_string(),
+                n => format!(" — {n} open tasks"),
+            };
+            html.push_str(&format!("<li>{}{tasks}{last}{stale}", link(&project.path, &project.name)));
+            if !project.tasks.is_empty() {
+                html.push_str("\n<ul>\n");
+                for (path, task) in &project.tasks {
+                    let href = html_escape(&encode_path(&format!("/{path}")));
+                    html.push_str(&format!(
+                        "<li>{} <a class=\"due-source\" href=\"{href}\">{}:{}</a></li>\n",
+                        html_escape(&task.text),
+                        html_escape(path.rsplit('/').next().unwrap_or(path)),
+                        task.line
+                    ));
+                }
+                html.push_str("</ul>\n");
+            }
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n");
+    }
+
+    let stale: Vec<_> = review.projects.iter().filter(|p| p.is_stale(today, stale_days)).collect();
+    html.push_str(&format!("<h2>Stale projects ({})</h2>\n", stale.len()));
+    if stale.is_empty() {
+        html.push_str(&format!("<p>Every project was edited in the last {stale_days} days.</p>\n"));
+    } else {
+        html.push_str(&format!(
+            "<p>No edits in {stale_days} days. Finish, archive or revive them.</p>\n<ul class=\"review\">\n"
+        ));
+        for project in stale {
+            html.push_str(&format!(
+                "<li>{} <span class=\"review-date\">{}</span></li>\n",
+                link(&project.path, &project.name),
+                project
+                    .last_edit
+                    .map_or("never edited".to_string(), |date| format!("last edited {}", day(date)))
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str(&format!("<h2>This week ({})</h2>\n", review.touched.len()));
+    if review.touched.is_empty() {
+        html.push_str("<p>No notes edited in the last seven days.</p>\n");
+    } else {
+        html.push_str("<ul class=\"review\">\n");
+        for item in &review.touched {
+            html.push_str(&format!(
+                "<li>{} <span class=\"review-date\">{}</span></li>\n",
+                link(&item.path, &item.path),
+                day(item.modified)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    html
+}
+
+/// A year of activity as a GitHub-style grid, one column per week. Shading
+/// counts note edits; outlined days have a daily note.
+fn render_heatmap(activity: &Activity, today: chrono::NaiveDate) -> String {
+    let weekday = today.weekday().num_days_from_sunday() as i64;
+    let start = today - chrono::Duration::days(52 * 7 + weekday);
+
+    let mut cells = String::new();
+    let mut day = start;
+    while day <= today {
+        let stats = activity.day(day);
+        let level = match stats.edits {
+            0 => 0,
+            1 => 1,
+            2..=3 => 2,
+            4..=6 => 3,
+            _ => 4,
+        };
+        let title = format!(
+            "{}: {} edit{}{}",
+            day.format("%b %-d, %Y"),
+            stats.edits,
+            if stats.edits == 1 { "" } else { "s" },
+            if stats.journaled { ", daily note" } else { "" }
+        );
+        let class = if stats.journaled {
+            format!("day level-{level} journaled")
+        } else {
+            format!("day level-{level}")
+        };
+        if stats.journaled {
+            cells.push_str(&format!(
+                "<a class=\"{class}\" href=\"/journal/{}\" title=\"{title}\"></a>",
+                day.format("%Y-%m-%d")
+            ));
+        } else {
+            cells.push_str(&format!("<span class=\"{class}\" title=\"{title}\"></span>"));
+        }
+        day += chrono::Duration::days(1);
+    }
+
+    let current = activity.current_streak(today);
+    let longest = activity.longest_streak();
+    format!(
+        "<section class=\"activity\">\n<div class=\"heatmap\">{cells}</div>\n<p class=\"streak\">Journal streak: {current} day{} (longest {longest})</p>\n</section>\n",
+        if current == 1 { "" } else { "s" }
+    )
+}
+
+/// Tasks under a heading per category, then one per project, area or
+/// resource (the folder or note directly under the category), each with a
+/// checkbox that ticks it off and a link to its line in the source.
+fn render_tasks(notes: &[NoteTasks], notes_dir: &FsPath, config: &Config) -> String {
+    let mut html = String::from("<h1>Tasks</h1>\n");
+    if notes.is_empty() {
+        html.push_str("<p>No open tasks. Add <code>- [ ] something</code> to a note.</p>");
+        return html;
+    }
+
+    let categories = Category::ALL.iter().map(|c| Some(*c)).chain([None]);
+    for category in categories {
+        let in_category: Vec<&NoteTasks> = notes.iter().filter(|n| Category::of(&n.path) == category).collect();
+        if in_category.is_empty() {
+            continue;
+        }
+        html.push_str(&format!("<h2>{}</h2>\n", category.map_or("Other", |c| c.name())));
+
+        let mut current: Option<&str> = None;
+        for note in in_category {
+            // Notes outside the categories each get their own heading
+            let group = match note.path.match_indices('/').nth(1) {
+                Some((end, _)) if category.is_some() => &note.path[..end],
+                _ => note.path.as_str(),
+            };
+            if current != Some(group) {
+                if current.is_some() {
+                    html.push_str("</ul>\n");
+                }
+                let is_dir = group.len() < note.path.len();
+                let name = group.rsplit('/').next().unwrap_or(group).trim_end_matches(".md");
+                let href = encode_path(&format!("/{group}{}", if is_dir { "/" } else { "" }));
+                html.push_str(&format!(
+                    "<h3><a href=\"{}\">{}</a></h3>\n<ul class=\"tasks\">\n",
+                    html_escape(&href),
+                    html_escape(name)
+                ));
+                current = Some(group);
+            }
+
+            let path = notes_dir.join(&note.path);
+            let writable = config
+                .for_dir(notes_dir, path.parent().unwrap_or(notes_dir))
+                .can_write();
+            let version = tasks::mtime(&path).filter(|_| writable);
+            let file = note.path.rsplit('/').next().unwrap_or(&note.path);
+            for task in &note.tasks {
+                let checkbox = match &version {
+                    Some(version) => format!(
+                        "<input type=\"checkbox\" class=\"task-toggle\" data-path=\"{}\" data-line=\"{}\" data-version=\"{version}\"> ",
+                        html_escape(&format!("/{}", note.path)),
+                        task.line
+                    ),
+                    None => String::new(),
+                };
+                // The editor opens at the line; read-only notes open as pages
+                let source = if writable {
+                    format!(
+                        "<a class=\"task-source\" href=\"{}#L{line}\" hx-boost=\"false\">{}:{line}</a>",
+                        html_escape(&encode_path(&format!("/edit/{}", note.path))),
+                        html_escape(file),
+                        line = task.line
+                    )
+                } else {
+                    format!(
+                        "<a class=\"task-source\" href=\"{}\">{}:{}</a>",
+                        html_escape(&encode_path(&format!("/{}", note.path))),
+                        html_escape(file),
+                        task.line
+                    )
+                };
+                html.push_str(&format!(
+                    "<li>{checkbox}{} {source}</li>\n",
+                    html_escape(&task.text)
+                ));
+            }
+        }
+        html.push_str("</ul>\n");
+    }
+    html
+}
+
+fn render_recent(notes: &[RecentNote]) -> String {
+    let mut html = String::from("<h1>Recently modified</h1>\n");
+    if notes.is_empty() {
+        html.push_str("<p>No notes yet.</p>");
+        return html;
+    }
+
+    html.push_str("<ul class=\"recent\">\n");
+    for note in notes {
+        html.push_str(&format!(
+            "<li><a href=\"{href}\">{title}</a> <time datetime=\"{datetime}\">{time}</time> <span class=\"recent-path\">{path}</span>{snippet}</li>\n",
+            href = html_escape(&encode_path(&format!("/{}", note.path))),
+            title = html_escape(&note.title),
+            datetime = note.modified.to_rfc3339(),
+            time = note.modified.format("%b %-d, %Y %H:%M"),
+            path = html_escape(&note.path),
+            snippet = if note.snippet.is_empty() {
+                String::new()
+            } else {
+                format!("<p class=\"recent-snippet\">{}</p>", html_escape(&note.snippet))
+            }
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+fn render_upcoming(items: &[DueItem], today: chrono::NaiveDate) -> String {
+    let mut html = String::from("<h1>Upcoming</h1>\n");
+    if items.is_empty() {
+        html.push_str("<p>Nothing is due. Add <code>due: YYYY-MM-DD</code> to a note, or <code>📅 YYYY-MM-DD</code> to a task.</p>");
+        return html;
+    }
+
+    let mut current = None;
+    for item in items {
+        let bucket = Bucket::of(item.due, today);
+        if current != Some(bucket) {
+            if current.is_some() {
+                html.push_str("</ul>\n");
+            }
+            html.push_str(&format!("<h2>{}</h2>\n<ul class=\"upcoming\">\n", bucket.label()));
+            current = Some(bucket);
+        }
+        let href = html_escape(&encode_path(&format!("/{}", item.path)));
+        let source = match item.line {
+            Some(line) => format!(" <a class=\"due-source\" href=\"{href}\">{}:{line}</a>", html_escape(&item.path)),
+            None => String::new(),
+        };
+        let text = if item.line.is_some() {
+            html_escape(&item.text)
+        } else {
+            format!("<a href=\"{href}\">{}</a>", html_escape(&item.text))
+        };
+        html.push_str(&format!(
+            "<li class=\"due-{class}\"><span class=\"due-date\">{date}</span> {text}{source}</li>\n",
+            class = bucket.label().to_lowercase().replace(' ', "-"),
+            date = if item.due.year() == today.year() {
+                item.due.format("%a %b %-d")
+            } else {
+                item.due.format("%b %-d, %Y")
+            },
+            text = text,
+            source = source
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+async fn handle_audit(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let entries: Vec<_> = audit::read(&notes_canonical)
+        .into_iter()
+        .rev()
+        .filter(|entry| !config.is_private(&notes_canonical, &notes_canonical.join(&entry.path)))
+        .collect();
+    let content = render_audit(&entries);
+    Ok(build_response(&state, &config, "Audit log", &content, "", is_htmx, None, config.theme))
+}
+
+fn render_audit(entries: &[audit::Entry]) -> String {
+    let mut html = format!("<h1>Audit log</h1>\n<p>{} changes made through the server, newest first.</p>\n", entries.len());
+    if entries.is_empty() {
+        return html;
+    }
+
+    html.push_str("<table class=\"audit\">\n<tr><th>Time</th><th>User</th><th>Action</th><th>Path</th><th></th></tr>\n");
+    for entry in entries {
+        html.push_str(&format!(
+            "<tr><td>{time}</td><td>{user}</td><td>{action}</td><td><a href=\"{href}\">{path}</a></td><td>{detail}</td></tr>\n",
+            time = html_escape(&entry.time.replacen('T', " ", 1)),
+            user = html_escape(entry.user.as_deref().unwrap_or("-")),
+            action = entry.action.as_str(),
+            href = html_escape(&encode_path(&format!("/{}", entry.path))),
+            path = html_escape(&entry.path),
+            detail = html_escape(entry.detail.as_deref().unwrap_or(""))
+        ));
+    }
+    html.push_str("</table>");
+    html
+}
+
+async fn handle_lint(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let files = lint::vault(&notes_canonical, &config);
+    let content = render_lint(&files);
+    Ok(build_response(&state, &config, "Lint", &content, "", is_htmx, None, config.theme))
+}
+
+fn render_lint(files: &[FileIssues]) -> String {
+    let count: usize = files.iter().map(|f| f.issues.len()).sum();
+    let mut html = format!(
+        "<h1>Lint</h1>\n<p>{count} issues in {} notes.</p>\n",
+        files.len()
+    );
+    if count == 0 {
+        return html;
+    }
+
+    html.push_str("<table class=\"lint\">\n<tr><th>Note</th><th>Line</th><th>Rule</th><th>Problem</th></tr>\n");
+    for file in files {
+        let href = html_escape(&encode_path(&format!("/{}", file.path)));
+        for issue in &file.issues {
+            html.push_str(&format!(
+                "<tr><td><a href=\"{href}\">{path}</a></td><td>{line}</td><td><code>{rule}</code></td><td>{message}</td></tr>\n",
+                href = href,
+                path = html_escape(&file.path),
+                line = issue.line,
+                rule = issue.rule,
+                message = html_escape(&issue.message)
+            ));
+        }
+    }
+    html.push_str("</table>");
+    html
+}
+
+async fn handle_tag(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(tag): Path<String>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+
+    let tag = tag.trim_start_matches('#').trim_end_matches('/');
+    let groups = tags::by_category(&notes_canonical, &config, tag);
+    let content = render_tag(tag, &groups);
+    let title = format!("#{tag}");
+    Ok(build_response(&state, &config, &title, &content, "", is_htmx, None, config.theme))
+}
+
+fn render_tag(tag: &str, groups: &[(Option<Category>, Vec<String>)]) -> String {
+    let count: usize = groups.iter().map(|(_, paths)| paths.len()).sum();
+    let mut html = format!(
+        "<h1>#{}</h1>\n<p>{count} notes tagged.</p>\n",
+        html_escape(tag)
+    );
+    for (category, paths) in groups {
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<ul class=\"tagged\">\n",
+            category.map_or("Other", |c| c.name())
+        ));
+        for path in paths {
+            html.push_str(&format!(
+                "<li><a href=\"{href}\">{path}</a></li>\n",
+                href = html_escape(&encode_path(&format!("/{path}"))),
+                path = html_escape(path)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    html
+}
+
+async fn handle_path(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<String>,
+    Query(params): Query<HighlightParams>,
+) -> Response {
+    let is_htmx = headers.contains_key("hx-request");
+    let full_path = state.notes_dir.join(&path);
+
+    if full_path.is_dir() && !path.ends_with('/') {
+        return Redirect::permanent(&format!("/{path}/")).into_response();
+    }
+
+    // A note that moved answers at its old URL if it kept that in `aliases`
+    if path.ends_with(".md")
+        && !full_path.exists()
+        && let Some(target) = state.link_indexes.get(&config).alias(&path)
+    {
+        let location = encode_path(&format!("/{target}"));
+        return (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, location)]).into_response();
+    }
+
+    let highlight = params.highlight.as_deref().and_then(|term| {
+        state
+            .searcher(&state.notes_dir, &config, params.mode)
+            .highlight(term)
+    });
+    let range = headers.get(header::RANGE).and_then(|value| value.to_str().ok());
+    match serve_path(&state, &config, &full_path, "", highlight.as_ref(), is_htmx, range).await {
+        Ok(resp) => resp,
+        Err(StatusCode::NOT_FOUND) => not_found(&state, &config, &path, is_htmx),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// A page in the usual layout for a path with nothing there, suggesting
+/// notes with similar names.
+fn not_found(state: &AppState, config: &Config, path: &str, is_htmx: bool) -> Response {
+    let mut content = format!(
+        "<h1>Not found</h1>\n<p>Nothing is at <code>/{}</code>.</p>\n",
+        html_escape(path)
+    );
+    let similar = state.note_paths.similar(config, path);
+    if !similar.is_empty() {
+        content.push_str("<p>Did you mean:</p>\n<ul class=\"tagged\">\n");
+        for note in &similar {
+            content.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                html_escape(&encode_path(&format!("/{note}"))),
+                html_escape(note)
+            ));
+        }
+        content.push_str("</ul>\n");
+    }
+    content.push_str("<p><a href=\"/\">Back to the notes</a></p>");
+    let mut response = build_response(state, config, "Not found", &content, "", is_htmx, None, config.theme);
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response
+}
+
+/// The server is up and answering.
+async fn handle_healthz() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Whether the vault can be served: its folder is readable, its search index
+/// built and its file watcher running. 503 if any isn't.
+async fn handle_readyz(State(state): State<Arc<AppState>>) -> Response {
+    let notes_dir = std::fs::read_dir(&state.notes_dir).is_ok();
+    let index = state.index.is_some();
+    let watcher = state.watching.load(Ordering::Relaxed);
+    let ready = notes_dir && index && watcher;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "unavailable" },
+        "notes_dir": notes_dir,
+        "index": index,
+        "watcher": watcher,
+    });
+    (status, Json(body)).into_response()
+}
+
+/// Request, live reload, watcher and index figures in the Prometheus text
+/// format, for every vault served.
+async fn handle_metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        METRICS.render(),
+    )
+        .into_response()
+}
+
+async fn handle_fonts(Path(path): Path<String>) -> Response {
+    let Some((_, bytes)) = FONTS.iter().find(|(name, _)| *name == path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "font/ttf")
+        .header(header::CACHE_CONTROL, "public, max-age=604800")
+        .body(Body::from(*bytes))
+        .unwrap_or_else(|e| internal(e).into_response())
+}
+
+/// A stylesheet or script from [`ASSETS`]. Its name changes with its
+/// contents, so browsers may keep it for good.
+async fn handle_asset(Path(file): Path<String>) -> Response {
+    let Some(asset) = ASSETS.all().into_iter().find(|asset| asset.file == file) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, asset.content_type)
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        .body(Body::from(asset.body))
+        .unwrap_or_else(|e| internal(e).into_response())
+}
+
+async fn handle_og(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<String>,
+) -> Response {
+    let Some(note_path) = path.strip_suffix(".png") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let full_path = state.notes_dir.join(note_path);
+
+    // Security: ensure path is within notes_dir
+    let canonical = match full_path.canonicalize() {
+        Ok(p) => p,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let notes_canonical = match state.notes_dir.canonicalize() {
+        Ok(p) => p,
+        Err(e) => return internal(e).into_response(),
+    };
+    if !canonical.starts_with(&notes_canonical) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if config.is_private(&notes_canonical, &canonical) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    if canonical.extension().is_none_or(|ext| ext != "md") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let title = canonical
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Note");
+
+    match og::render_card(title, &vault_name(&notes_canonical)) {
+        Ok(bytes) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "image/png")
+            .header(header::CACHE_CONTROL, "public, max-age=3600")
+            .body(Body::from(bytes))
+            .unwrap(),
+        Err(e) => internal(e).into_response(),
+    }
+}
+
+async fn handle_thumb(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<String>,
+) -> Response {
+    let full_path = state.notes_dir.join(&path);
+
+    // Security: ensure path is within notes_dir
+    let canonical = match full_path.canonicalize() {
+        Ok(p) => p,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let notes_canonical = match state.notes_dir.canonicalize() {
+        Ok(p) => p,
+        Err(e) => return internal(e).into_response(),
+    };
+    if !canonical.starts_with(&notes_canonical) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if config.is_private(&notes_canonical, &canonical) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    // Formats we can't rasterize (e.g. SVG) are served as-is
+    if !thumbs::supports(&canonical) {
+        return Redirect::temporary(&encode_path(&format!("/{path}"))).into_response();
+    }
+
+    let result =
+        tokio::task::spawn_blocking(move || thumbs::thumbnail(&notes_canonical, &canonical)).await;
+    match result {
+        Ok(Ok((bytes, content_type))) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CACHE_CONTROL, "public, max-age=86400")
+            .body(Body::from(bytes))
+            .unwrap(),
+        Ok(Err(e)) => internal(e).into_response(),
+        Err(e) => internal(e).into_response(),
+    }
+}
+
+async fn handle_raw(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<String>,
+) -> Response {
+    let full_path = state.notes_dir.join(&path);
+
+    // Security: ensure path is within notes_dir
+    let canonical = match full_path.canonicalize() {
+        Ok(p) => p,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let notes_canonical = match state.notes_dir.canonicalize() {
+        Ok(p) => p,
+        Err(e) => return internal(e).into_response(),
+    };
+    if !canonical.starts_with(&notes_canonical) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if config.is_private(&notes_canonical, &canonical) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    // Only serve notes and files rendered as pages as raw
+    let content_type = if canonical.extension().is_some_and(|ext| ext == "md") {
+        "text/plain; charset=utf-8"
+    } else if let Some(renderer) = render::for_path(&canonical) {
+        renderer.content_type()
+    } else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    match std::fs::read_to_string(&canonical) {
+        Ok(content) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::ETAG, format!("\"{}\"", content_version(&content)))
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::from(content))
+            .unwrap(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Plain-text editor for a note's markdown, saved with `PUT /{path}`.
+async fn handle_edit(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<String>,
+) -> Result<Response, StatusCode> {
+    let is_htmx = headers.contains_key("hx-request");
+    let canonical = state
+        .notes_dir
+        .join(&path)
+        .canonicalize()
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    if !canonical.starts_with(&notes_canonical) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if config.is_private(&notes_canonical, &canonical) || canonical.extension().is_none_or(|ext| ext != "md") {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let dir_config = config.for_dir(&notes_canonical, canonical.parent().unwrap_or(&notes_canonical));
+    if !dir_config.can_write() {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
-    let app = Router::new()
-        .route("/", get(handle_root))
-        .route("/search", get(handle_search))
-        .route("/save", post(handle_save))
-        .route("/raw/{*path}", get(handle_raw))
-        .route("/ws", get(handle_websocket))
-        .route("/fonts/{*path}", get(handle_fonts))
-        .route("/{*path}", get(handle_path))
-        .with_state(state);
+    let content = std::fs::read_to_string(&canonical).map_err(internal)?;
+    let relative = vault::relative(&notes_canonical, &canonical);
+    let href = html_escape(&encode_path(&format!("/{relative}")));
+    let html = format!(
+        r#"<h1>Editing {name}</h1>
+<form class="raw-editor" data-path="{href}" data-version="{version}" hx-boost="false">
+    <textarea name="content" spellcheck="false">{content}</textarea>
+    <div class="raw-editor-actions">
+        <button type="submit">Save</button>
+        <a href="{href}">Cancel</a>
+        <span class="raw-editor-status"></span>
+    </div>
+</form>"#,
+        name = html_escape(&relative),
+        href = href,
+        version = content_version(&content),
+        content = html_escape(&content)
+    );
+    let title = format!(
+        "Edit {}",
+        canonical.file_stem().and_then(|s| s.to_str()).unwrap_or("Note")
+    );
+    Ok(build_response(&state, &config, &title, &html, "", is_htmx, None, dir_config.theme))
+}
 
-    let addr = format!("0.0.0.0:{port}");
-    println!("Serving notes at http://localhost:{port}");
-    println!("Live reload enabled - watching for file changes");
+/// Save a note's markdown from the request body. An `If-Match` header with
+/// the version from `/raw` or `/edit` guards against overwriting changes
+/// made elsewhere.
+async fn handle_put(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<String>,
+    content: String,
+) -> Response {
+    let payload = SaveRequest {
+        path,
+        content,
+        version: headers
+            .get(header::IF_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().trim_matches('"').to_string()),
+    };
+    let result = save_note(&state, &config, &payload);
+    let status = if result.success {
+        StatusCode::OK
+    } else if result.conflict.is_some() {
+        StatusCode::PRECONDITION_FAILED
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    let mut response = (status, Json(&result)).into_response();
+    if let Some(version) = &result.version
+        && let Ok(value) = format!("\"{version}\"").parse()
+    {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
+}
 
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+/// Move a note, attachment, or folder into the trash.
+async fn handle_delete(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Path(path): Path<String>,
+) -> StatusCode {
+    let Ok(canonical) = state.notes_dir.join(&path).canonicalize() else {
+        return StatusCode::NOT_FOUND;
+    };
+    let notes_canonical = match state.notes_dir.canonicalize() {
+        Ok(notes_canonical) => notes_canonical,
+        Err(e) => return internal(e),
+    };
+    // Hidden paths hold the trash, the audit log and the vault's history
+    if !canonical.starts_with(&notes_canonical)
+        || canonical == notes_canonical
+        || vault::is_hidden(&vault::relative(&notes_canonical, &canonical))
+    {
+        return StatusCode::FORBIDDEN;
+    }
+    if config.is_private(&notes_canonical, &canonical) {
+        return StatusCode::NOT_FOUND;
+    }
+    let parent = canonical.parent().unwrap_or(&notes_canonical);
+    if !config.for_dir(&notes_canonical, parent).can_write()
+        || (canonical.is_dir() && !config.for_dir(&notes_canonical, &canonical).can_write())
+    {
+        return StatusCode::FORBIDDEN;
+    }
 
-    Ok(())
+    let relative = vault::relative(&notes_canonical, &canonical);
+    match trash::trash(&notes_canonical, &relative) {
+        Ok(id) => {
+            audit(&config, &notes_canonical, Action::Delete, &relative, Some(format!("trash {id}")));
+            StatusCode::NO_CONTENT
+        }
+        Err(e) => internal(e),
+    }
 }
 
-async fn handle_root(
+async fn handle_trash(
     headers: axum::http::HeaderMap,
     State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
 ) -> Result<Response, StatusCode> {
     let is_htmx = headers.contains_key("hx-request");
-    serve_path(&state.notes_dir, &state.notes_dir, "", is_htmx).await
+    trash_page(&state, &config, is_htmx, None)
 }
 
-async fn handle_search(
+async fn handle_trash_restore(
     headers: axum::http::HeaderMap,
     State(state): State<Arc<AppState>>,
-    Query(params): Query<SearchParams>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<TrashRequest>,
 ) -> Result<Response, StatusCode> {
     let is_htmx = headers.contains_key("hx-request");
-    let query = params.q.unwrap_or_default();
     let notes_canonical = state
         .notes_dir
         .canonicalize()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let file_tree = render_file_tree(&notes_canonical, &notes_canonical)?;
-
-    if query.is_empty() {
-        let content = "<p>Enter a search term above.</p>";
-        return Ok(build_response("Search", content, &file_tree, &query, is_htmx, None));
-    }
-
-    let output = Command::new("rg")
-        .args([
-            "--color",
-            "never",
-            "--line-number",
-            "--max-count",
-            "3",
-            "-C",
-            "1",
-            "-i",
-            "--type",
-            "md",
-            &query,
-        ])
-        .current_dir(&state.notes_dir)
-        .output()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    if stdout.is_empty() {
-        let content = format!("<h1>No results for \"{}\"</h1>", html_escape(&query));
-        return Ok(build_response("Search", &content, &file_tree, &query, is_htmx, None));
-    }
-
-    let content = render_search_results(&stdout, &query);
-    Ok(build_response(
-        &format!("Search: {}", query),
-        &content,
-        &file_tree,
-        &query,
-        is_htmx,
-        None,
-    ))
+        .map_err(internal)?;
+    let item = trash_item(&notes_canonical, &config, &form.id)?;
+    match trash::restore(&notes_canonical, &item.id) {
+        Ok(path) => {
+            audit(&config, &notes_canonical, Action::Restore, &path, None);
+            Ok(Redirect::to("/trash").into_response())
+        }
+        Err(e) => trash_page(&state, &config, is_htmx, Some(&format!("Couldn't restore: {e:#}"))),
+    }
 }
 
-async fn handle_path(
+async fn handle_trash_purge(
     headers: axum::http::HeaderMap,
     State(state): State<Arc<AppState>>,
-    Path(path): Path<String>,
-) -> Response {
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<TrashRequest>,
+) -> Result<Response, StatusCode> {
     let is_htmx = headers.contains_key("hx-request");
-    let full_path = state.notes_dir.join(&path);
-
-    if full_path.is_dir() && !path.ends_with('/') {
-        return Redirect::permanent(&format!("/{path}/")).into_response();
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    let item = trash_item(&notes_canonical, &config, &form.id)?;
+    match trash::purge(&notes_canonical, &item.id) {
+        Ok(path) => {
+            audit(&config, &notes_canonical, Action::Purge, &path, Some(format!("trash {}", item.id)));
+            Ok(Redirect::to("/trash").into_response())
+        }
+        Err(e) => trash_page(&state, &config, is_htmx, Some(&format!("Couldn't delete: {e:#}"))),
     }
+}
 
-    match serve_path(&state.notes_dir, &full_path, "", is_htmx).await {
-        Ok(resp) => resp,
-        Err(status) => status.into_response(),
+/// Look up a trash item the viewer may change, by where it was deleted from.
+fn trash_item(notes_dir: &FsPath, config: &Config, id: &str) -> Result<trash::Item, StatusCode> {
+    let item = trash::list(notes_dir)
+        .into_iter()
+        .find(|item| item.id == id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let origin = notes_dir.join(&item.path);
+    if !config.for_dir(notes_dir, origin.parent().unwrap_or(notes_dir)).can_write() {
+        return Err(StatusCode::FORBIDDEN);
     }
+    Ok(item)
 }
 
-async fn handle_fonts(Path(path): Path<String>) -> Response {
-    let (bytes, content_type) = match path.as_str() {
-        "UbuntuMono-Regular.ttf" => (UBUNTU_MONO_REGULAR, "font/ttf"),
-        "UbuntuMono-Italic.ttf" => (UBUNTU_MONO_ITALIC, "font/ttf"),
-        "UbuntuMono-Bold.ttf" => (UBUNTU_MONO_BOLD, "font/ttf"),
-        "UbuntuMono-BoldItalic.ttf" => (UBUNTU_MONO_BOLD_ITALIC, "font/ttf"),
-        _ => return StatusCode::NOT_FOUND.into_response(),
-    };
-
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, content_type)
-        .body(Body::from(bytes))
-        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+fn trash_page(
+    state: &AppState,
+    config: &Config,
+    is_htmx: bool,
+    notice: Option<&str>,
+) -> Result<Response, StatusCode> {
+    let notes_canonical = state
+        .notes_dir
+        .canonicalize()
+        .map_err(internal)?;
+    let items: Vec<_> = trash::list(&notes_canonical)
+        .into_iter()
+        .filter(|item| {
+            let origin = notes_canonical.join(&item.path);
+            !config
+                .for_dir(&notes_canonical, origin.parent().unwrap_or(&notes_canonical))
+                .private
+        })
+        .collect();
+    let content = render_trash(&items, notice);
+    Ok(build_response(state, config, "Trash", &content, "", is_htmx, None, config.theme))
 }
 
-async fn handle_raw(
-    State(state): State<Arc<AppState>>,
-    Path(path): Path<String>,
-) -> Response {
-    let full_path = state.notes_dir.join(&path);
-
-    // Security: ensure path is within notes_dir
-    let canonical = match full_path.canonicalize() {
-        Ok(p) => p,
-        Err(_) => return StatusCode::NOT_FOUND.into_response(),
-    };
-    let notes_canonical = match state.notes_dir.canonicalize() {
-        Ok(p) => p,
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    };
-    if !canonical.starts_with(&notes_canonical) {
-        return StatusCode::FORBIDDEN.into_response();
+fn render_trash(items: &[trash::Item], notice: Option<&str>) -> String {
+    let mut html = String::from("<h1>Trash</h1>\n");
+    if let Some(notice) = notice {
+        html.push_str(&format!("<p class=\"notice\">{}</p>\n", html_escape(notice)));
     }
-
-    // Only serve .md files as raw
-    if !canonical.extension().is_some_and(|ext| ext == "md") {
-        return StatusCode::NOT_FOUND.into_response();
+    if items.is_empty() {
+        html.push_str("<p>The trash is empty.</p>");
+        return html;
     }
 
-    match std::fs::read_to_string(&canonical) {
-        Ok(content) => Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
-            .body(Body::from(content))
-            .unwrap(),
-        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    html.push_str("<table class=\"trash\">\n<tr><th>Deleted</th><th>From</th><th></th></tr>\n");
+    for item in items {
+        html.push_str(&format!(
+            r#"<tr><td>{deleted}</td><td>{path}{slash}</td><td class="trash-actions"><form method="post" action="/trash/restore"><input type="hidden" name="id" value="{id}"><button type="submit">Restore</button></form><form method="post" action="/trash/purge" hx-confirm="Delete {path} for good?"><input type="hidden" name="id" value="{id}"><button type="submit">Delete forever</button></form></td></tr>
+"#,
+            deleted = item.deleted(),
+            path = html_escape(&item.path),
+            slash = if item.is_dir { "/" } else { "" },
+            id = html_escape(&item.id)
+        ));
     }
+    html.push_str("</table>");
+    html
 }
 
 async fn handle_save(
     State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
     Json(payload): Json<SaveRequest>,
 ) -> Json<SaveResponse> {
-    let full_path = state.notes_dir.join(&payload.path.trim_start_matches('/'));
+    Json(save_note(&state, &config, &payload))
+}
+
+/// Write a note on behalf of an editor, after checking the path, the user's
+/// access, the folder's frontmatter schema, and that nobody changed the file
+/// since the editor loaded `payload.version`.
+fn save_note(state: &AppState, config: &Config, payload: &SaveRequest) -> SaveResponse {
+    let full_path = state.notes_dir.join(payload.path.trim_start_matches('/'));
 
     // Security: ensure path is within notes_dir
     // For new files, check parent directory
@@ -270,10 +2825,12 @@ async fn handle_save(
         match full_path.parent() {
             Some(p) if p.exists() => p.to_path_buf(),
             _ => {
-                return Json(SaveResponse {
+                return SaveResponse {
                     success: false,
                     error: Some("Invalid path".to_string()),
-                });
+                    version: None,
+                    conflict: None,
+                };
             }
         }
     };
@@ -281,65 +2838,571 @@ async fn handle_save(
     let canonical = match check_path.canonicalize() {
         Ok(p) => p,
         Err(_) => {
-            return Json(SaveResponse {
+            return SaveResponse {
                 success: false,
                 error: Some("Invalid path".to_string()),
-            });
+                version: None,
+                conflict: None,
+            };
         }
     };
     let notes_canonical = match state.notes_dir.canonicalize() {
         Ok(p) => p,
         Err(_) => {
-            return Json(SaveResponse {
+            return SaveResponse {
                 success: false,
                 error: Some("Server error".to_string()),
-            });
+                version: None,
+                conflict: None,
+            };
         }
     };
     if !canonical.starts_with(&notes_canonical) {
-        return Json(SaveResponse {
+        return SaveResponse {
             success: false,
             error: Some("Access denied".to_string()),
-        });
+            version: None,
+            conflict: None,
+        };
     }
 
     // Only allow saving .md files
-    if !full_path.extension().is_some_and(|ext| ext == "md") {
-        return Json(SaveResponse {
+    if full_path.extension().is_none_or(|ext| ext != "md") {
+        return SaveResponse {
             success: false,
             error: Some("Only markdown files can be saved".to_string()),
-        });
+            version: None,
+            conflict: None,
+        };
+    }
+
+    let dir = if canonical.is_file() {
+        canonical.parent().unwrap_or(&canonical)
+    } else {
+        canonical.as_path()
+    };
+    let dir_config = config.for_dir(&notes_canonical, dir);
+    if !dir_config.can_write() {
+        return SaveResponse {
+            success: false,
+            error: Some("Access denied".to_string()),
+            version: None,
+            conflict: None,
+        };
+    }
+    let problems = schema::validate(&frontmatter::parse(&payload.content).0, &dir_config.schema);
+    if !problems.is_empty() {
+        return SaveResponse {
+            success: false,
+            error: Some(format!("Frontmatter: {}", problems.join("; ").replace('`', ""))),
+            version: None,
+            conflict: None,
+        };
+    }
+
+    // Refuse to overwrite changes made outside the editor since it loaded
+    if let Some(base) = &payload.version {
+        let current = std::fs::read_to_string(&full_path).unwrap_or_default();
+        if content_version(&current) != *base {
+            return SaveResponse {
+                success: false,
+                error: Some("The note changed on disk since you started editing".to_string()),
+                version: Some(content_version(&current)),
+                conflict: Some(current),
+            };
+        }
     }
 
+    // For new files `canonical` is the folder they're created in
+    let (action, target) = if canonical.is_file() {
+        (Action::Edit, canonical.clone())
+    } else {
+        (Action::Create, canonical.join(full_path.file_name().unwrap_or_default()))
+    };
     match std::fs::write(&full_path, &payload.content) {
-        Ok(_) => Json(SaveResponse {
-            success: true,
-            error: None,
-        }),
+        Ok(_) => {
+            audit(config, &notes_canonical, action, &vault::relative(&notes_canonical, &target), None);
+            SaveResponse {
+                success: true,
+                error: None,
+                version: Some(content_version(&payload.content)),
+                conflict: None,
+            }
+        }
+        Err(e) => SaveResponse {
+            success: false,
+            error: Some(e.to_string()),
+            version: None,
+            conflict: None,
+        },
+    }
+}
+
+/// React to a rename seen by the watcher: rewrite inbound links or offer to,
+/// depending on `links.on_move`.
+fn handle_move(
+    notes_dir: &FsPath,
+    config: &Config,
+    tx: &broadcast::Sender<LiveEvent>,
+    from: &FsPath,
+    to: &FsPath,
+) {
+    let (Ok(from), Ok(to)) = (from.strip_prefix(notes_dir), to.strip_prefix(notes_dir)) else {
+        return;
+    };
+    // Editors save through hidden temp files; those aren't moves
+    let hidden = |p: &FsPath| {
+        p.components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+    };
+    if hidden(from) || hidden(to) {
+        return;
+    }
+    let from = from.to_string_lossy().replace('\\', "/");
+    let to = to.to_string_lossy().replace('\\', "/");
+    let dir_config = config.for_dir(notes_dir, &notes_dir.join(&to));
+    if dir_config.links.on_move == OnMove::Ignore {
+        return;
+    }
+
+    let moves = links::expand_move(notes_dir, config, &from, &to);
+    let apply = dir_config.links.on_move == OnMove::Rewrite;
+    match links::rewrite(notes_dir, config, &moves, apply) {
+        Ok(changed) if changed.is_empty() => {}
+        Ok(changed) if apply => {
+            tracing::info!("{from} moved to {to}; updated links in {} note(s)", changed.len());
+            let detail = format!("from {from}, {} note(s) updated", changed.len());
+            audit(config, notes_dir, Action::Relink, &to, Some(detail));
+        }
+        Ok(changed) => {
+            let _ = tx.send(LiveEvent::Moved {
+                from,
+                to,
+                links: changed.len(),
+            });
+        }
+        Err(e) => tracing::error!("Failed to update links after moving {from}: {e}"),
+    }
+}
+
+async fn handle_relink(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(payload): Json<RelinkRequest>,
+) -> Json<SaveResponse> {
+    let from = payload.from.trim_start_matches('/');
+    let to = payload.to.trim_start_matches('/');
+    let inside = state
+        .notes_dir
+        .join(to)
+        .canonicalize()
+        .ok()
+        .zip(state.notes_dir.canonicalize().ok())
+        .is_some_and(|(path, root)| path.starts_with(root));
+    if !inside || from.split('/').any(|part| part == "..") {
+        return Json(SaveResponse {
+            success: false,
+            error: Some("Invalid path".to_string()),
+            version: None,
+            conflict: None,
+        });
+    }
+    // Only notes the viewer can write are relinked, and only for a move
+    // they could have made themselves
+    let target = state.notes_dir.join(to);
+    if !config
+        .for_dir(&state.notes_dir, target.parent().unwrap_or(&state.notes_dir))
+        .can_write()
+    {
+        return Json(SaveResponse {
+            success: false,
+            error: Some("Access denied".to_string()),
+            version: None,
+            conflict: None,
+        });
+    }
+
+    let moves = links::expand_move(&state.notes_dir, &config, from, to);
+    match links::rewrite(&state.notes_dir, &config, &moves, true) {
+        Ok(changed) => {
+            let detail = format!("from {from}, {} note(s) updated", changed.len());
+            audit(&config, &state.notes_dir, Action::Relink, to, Some(detail));
+            Json(SaveResponse {
+                success: true,
+                error: None,
+                version: None,
+                conflict: None,
+            })
+        }
         Err(e) => Json(SaveResponse {
             success: false,
             error: Some(e.to_string()),
+            version: None,
+            conflict: None,
         }),
     }
 }
 
+/// Tick off or reopen a task in a note, as long as the note hasn't changed
+/// since the page showing it was rendered.
+async fn handle_toggle_task(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(payload): Json<ToggleTaskRequest>,
+) -> (StatusCode, Json<ToggleTaskResponse>) {
+    let failure = |status, error: &str| {
+        (
+            status,
+            Json(ToggleTaskResponse {
+                success: false,
+                error: Some(error.to_string()),
+                done: None,
+                version: None,
+            }),
+        )
+    };
+    let (Ok(canonical), Ok(notes_canonical)) = (
+        state.notes_dir.join(payload.path.trim_start_matches('/')).canonicalize(),
+        state.notes_dir.canonicalize(),
+    ) else {
+        return failure(StatusCode::NOT_FOUND, "No such note");
+    };
+    if !canonical.starts_with(&notes_canonical) || canonical.extension().is_none_or(|ext| ext != "md") {
+        return failure(StatusCode::BAD_REQUEST, "Invalid path");
+    }
+    let dir_config = config.for_dir(&notes_canonical, canonical.parent().unwrap_or(&notes_canonical));
+    if dir_config.private {
+        return failure(StatusCode::NOT_FOUND, "No such note");
+    }
+    if !dir_config.can_write() {
+        return failure(StatusCode::FORBIDDEN, "Access denied");
+    }
+
+    match tasks::toggle(&canonical, payload.line, &payload.version) {
+        Ok(Toggled::Done { done, mtime }) => {
+            let relative = vault::relative(&notes_canonical, &canonical);
+            let detail = format!("task on line {} {}", payload.line, if done { "done" } else { "reopened" });
+            audit(&config, &notes_canonical, Action::Edit, &relative, Some(detail));
+            (
+                StatusCode::OK,
+                Json(ToggleTaskResponse {
+                    success: true,
+                    error: None,
+                    done: Some(done),
+                    version: Some(mtime),
+                }),
+            )
+        }
+        Ok(Toggled::Conflict) => failure(
+            StatusCode::CONFLICT,
+            "The note changed on disk since this page was loaded; reload and try again",
+        ),
+        Ok(Toggled::NotATask) => failure(StatusCode::BAD_REQUEST, "That line isn't a task"),
+        Err(e) => failure(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+/// Move a finished project into the archive, as `para archive` does.
+async fn handle_archive_project(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(payload): Json<ArchiveRequest>,
+) -> Json<ArchiveResponse> {
+    let fail = |error: &str| {
+        Json(ArchiveResponse {
+            success: false,
+            error: Some(error.to_string()),
+            path: None,
+        })
+    };
+    let Ok(notes_canonical) = state.notes_dir.canonicalize() else {
+        return fail("Server error");
+    };
+    let from = payload.path.trim_matches('/');
+    if !archive::is_project(from) || from.split('/').any(|part| part == "..") {
+        return fail("Not a project");
+    }
+    let source = notes_canonical.join(from);
+    if !source.exists() || config.is_private(&notes_canonical, &source) {
+        return fail("No such project");
+    }
+    let today = chrono::Local::now().date_naive();
+    let archiving = match archive::plan(&notes_canonical, from, today) {
+        Ok(archiving) => archiving,
+        Err(e) => return fail(&e.to_string()),
+    };
+    let target = notes_canonical.join(&archiving.to);
+    let writable = |path: &FsPath| {
+        config
+            .for_dir(&notes_canonical, path.parent().unwrap_or(&notes_canonical))
+            .can_write()
+    };
+    if !writable(&source) || !writable(&target) {
+        return fail("Access denied");
+    }
+
+    match archive::apply(&notes_canonical, &config, &archiving, today) {
+        Ok(changed) => {
+            let detail = format!("archived from {from}, {} note(s) updated", changed.len());
+            audit(&config, &notes_canonical, Action::Move, &archiving.to, Some(detail));
+            Json(ArchiveResponse {
+                success: true,
+                error: None,
+                path: Some(archiving.to),
+            })
+        }
+        Err(e) => fail(&format!("{e:#}")),
+    }
+}
+
+/// A button that archives the project a page shows.
+fn render_archive_button(relative: &str) -> String {
+    format!(
+        "<button class=\"archive-project\" data-path=\"{}\">Archive project</button>\n",
+        html_escape(relative)
+    )
+}
+
+/// Rename or move a note or folder, then point links to it at the new
+/// location.
+async fn handle_rename(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Json(payload): Json<RelinkRequest>,
+) -> Json<SaveResponse> {
+    let fail = |error: &str| {
+        Json(SaveResponse {
+            success: false,
+            error: Some(error.to_string()),
+            version: None,
+            conflict: None,
+        })
+    };
+    let Ok(notes_canonical) = state.notes_dir.canonicalize() else {
+        return fail("Server error");
+    };
+
+    // Plain relative paths only; nothing hidden like `.trash` or `.para`
+    let valid = |p: &str| {
+        !p.is_empty()
+            && FsPath::new(p).components().all(|c| {
+                matches!(c, std::path::Component::Normal(name) if !name.to_string_lossy().starts_with('.'))
+            })
+    };
+    let from = payload.from.trim_matches('/');
+    let mut to = payload.to.trim_matches('/').to_string();
+    if !valid(from) || !valid(&to) {
+        return fail("Invalid path");
+    }
+    let source = notes_canonical.join(from);
+    if from.ends_with(".md") && source.is_file() && !to.ends_with(".md") {
+        to.push_str(".md");
+    }
+    let target = notes_canonical.join(&to);
+    if !source.exists() || config.is_private(&notes_canonical, &source) {
+        return fail("No such note");
+    }
+    if target.exists() {
+        return fail(&format!("{to} already exists"));
+    }
+    let writable = |path: &FsPath| {
+        config
+            .for_dir(&notes_canonical, path.parent().unwrap_or(&notes_canonical))
+            .can_write()
+    };
+    if !writable(&source) || !writable(&target) {
+        return fail("Access denied");
+    }
+
+    if let Some(parent) = target.parent()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        return fail(&e.to_string());
+    }
+    if let Err(e) = std::fs::rename(&source, &target) {
+        return fail(&e.to_string());
+    }
+
+    let moves = links::expand_move(&notes_canonical, &config, from, &to);
+    let detail = match links::rewrite(&notes_canonical, &config, &moves, true) {
+        Ok(changed) => format!("from {from}, {} note(s) updated", changed.len()),
+        Err(e) => format!("from {from}, updating links failed: {e}"),
+    };
+    audit(&config, &notes_canonical, Action::Move, &to, Some(detail));
+    Json(SaveResponse {
+        success: true,
+        error: None,
+        version: None,
+        conflict: None,
+    })
+}
+
+/// Save attachments sent as multipart `file` fields into `_attachments/`
+/// beside the note named by the `note` field, and return markdown linking
+/// to them from that note.
+async fn handle_upload(
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    mut multipart: Multipart,
+) -> Json<UploadResponse> {
+    let fail = |error: String| {
+        Json(UploadResponse {
+            success: false,
+            error: Some(error),
+            paths: Vec::new(),
+            markdown: String::new(),
+        })
+    };
+
+    let mut note = None;
+    let mut files = Vec::new();
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return fail(e.body_text()),
+        };
+        match field.name() {
+            Some("note") => note = field.text().await.ok(),
+            Some("file") => {
+                let name = field.file_name().unwrap_or("upload").to_string();
+                match field.bytes().await {
+                    Ok(bytes) => files.push((name, bytes)),
+                    Err(e) => return fail(e.body_text()),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(note) = note else {
+        return fail("Missing note".to_string());
+    };
+    Json(store_uploads(&state, &config, &note, files))
+}
+
+/// Save an image pasted from the clipboard, sent as the request body, under
+/// a timestamped name beside `?note=`, and return its markdown embed.
+async fn handle_paste_image(
+    headers: axum::http::HeaderMap,
+    State(state): State<Arc<AppState>>,
+    Extension(config): Extension<Arc<Config>>,
+    Query(params): Query<PasteParams>,
+    body: Bytes,
+) -> Json<UploadResponse> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let Some(name) = uploads::pasted_name(content_type) else {
+        return Json(UploadResponse {
+            success: false,
+            error: Some("Only PNG, JPEG, GIF, and WebP images can be pasted".to_string()),
+            paths: Vec::new(),
+            markdown: String::new(),
+        });
+    };
+    Json(store_uploads(&state, &config, &params.note, vec![(name, body)]))
+}
+
+/// Store files in the `_attachments/` folder beside `note` (a path relative
+/// to the notes root), checking the user may write there.
+fn store_uploads(
+    state: &AppState,
+    config: &Config,
+    note: &str,
+    files: Vec<(String, Bytes)>,
+) -> UploadResponse {
+    let fail = |error: String| UploadResponse {
+        success: false,
+        error: Some(error),
+        paths: Vec::new(),
+        markdown: String::new(),
+    };
+    let Ok(notes_canonical) = state.notes_dir.canonicalize() else {
+        return fail("Server error".to_string());
+    };
+    let note_dir = match notes_canonical.join(note.trim_start_matches('/')).canonicalize() {
+        Ok(path) if path.is_dir() => path,
+        Ok(path) => path.parent().map(FsPath::to_path_buf).unwrap_or(path),
+        Err(_) => return fail("Invalid note".to_string()),
+    };
+    if !note_dir.starts_with(&notes_canonical) {
+        return fail("Invalid note".to_string());
+    }
+    let dir_config = config.for_dir(&notes_canonical, &note_dir);
+    if !dir_config.can_write() {
+        return fail("Access denied".to_string());
+    }
+    if files.is_empty() {
+        return fail("No file uploaded".to_string());
+    }
+    if let Some(Err(e)) = files
+        .iter()
+        .map(|(name, _)| uploads::check(&dir_config, name))
+        .find(Result::is_err)
+    {
+        return fail(format!("{e:#}"));
+    }
+
+    let mut paths = Vec::new();
+    let mut links = Vec::new();
+    for (name, bytes) in files {
+        match uploads::store(&note_dir, &dir_config, &name, &bytes) {
+            Ok(stored) => {
+                let path = vault::relative(
+                    &notes_canonical,
+                    &note_dir.join(uploads::ATTACHMENTS_DIR).join(&stored),
+                );
+                audit(config, &notes_canonical, Action::Upload, &path, None);
+                links.push(uploads::markdown_link(&stored));
+                paths.push(path);
+            }
+            Err(e) => return fail(format!("{e:#}")),
+        }
+    }
+    UploadResponse {
+        success: true,
+        error: None,
+        paths,
+        markdown: links.join("\n"),
+    }
+}
+
+/// Note a change in the audit log. Failing to log doesn't undo the change.
+fn audit(config: &Config, notes_dir: &FsPath, action: Action, path: &str, detail: Option<String>) {
+    let user = config.viewer.as_ref().map(|u| u.name.as_str());
+    if let Err(e) = audit::record(notes_dir, user, action, path, detail) {
+        tracing::error!("Failed to write audit log: {e:#}");
+    }
+}
+
 async fn handle_websocket(
     State(state): State<Arc<AppState>>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state.reload_tx.subscribe()))
+    ws.on_upgrade(move |socket| handle_socket(socket, state.reload_tx.subscribe(), state.vault.clone()))
 }
 
-async fn handle_socket(socket: WebSocket, mut reload_rx: broadcast::Receiver<String>) {
+async fn handle_socket(socket: WebSocket, mut reload_rx: broadcast::Receiver<LiveEvent>, vault: String) {
     let (mut sender, mut receiver) = socket.split();
+    METRICS.websocket_opened(&vault);
 
     // Spawn task to forward reload events to the client
     let send_task = tokio::spawn(async move {
-        while let Ok(path) = reload_rx.recv().await {
-            let msg = serde_json::json!({
-                "type": "reload",
-                "path": path
-            });
+        while let Ok(event) = reload_rx.recv().await {
+            let msg = match event {
+                LiveEvent::Reload(path) => serde_json::json!({
+                    "type": "reload",
+                    "path": path
+                }),
+                LiveEvent::Moved { from, to, links } => serde_json::json!({
+                    "type": "moved",
+                    "from": from,
+                    "to": to,
+                    "links": links
+                }),
+            };
             if sender
                 .send(Message::Text(msg.to_string().into()))
                 .await
@@ -362,40 +3425,121 @@ async fn handle_socket(socket: WebSocket, mut reload_rx: broadcast::Receiver<Str
         _ = send_task => {},
         _ = recv_task => {},
     }
+    METRICS.websocket_closed(&vault);
 }
 
 async fn serve_path(
-    notes_dir: &PathBuf,
-    path: &PathBuf,
+    state: &AppState,
+    config: &Config,
+    path: &FsPath,
     query: &str,
+    highlight: Option<&Regex>,
     is_htmx: bool,
+    range: Option<&str>,
 ) -> Result<Response, StatusCode> {
     let canonical = path.canonicalize().map_err(|_| StatusCode::NOT_FOUND)?;
-    let notes_canonical = notes_dir
+    let notes_canonical = state
+        .notes_dir
         .canonicalize()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(internal)?;
     if !canonical.starts_with(&notes_canonical) {
         return Err(StatusCode::FORBIDDEN);
     }
 
-    let file_tree = render_file_tree(&notes_canonical, &notes_canonical)?;
-
+    let dir = if canonical.is_dir() {
+        canonical.as_path()
+    } else {
+        canonical.parent().unwrap_or(&notes_canonical)
+    };
+    let dir_config = config.for_dir(&notes_canonical, dir);
+    if dir_config.private {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
     if canonical.is_file() {
         let ext = canonical.extension().and_then(|e| e.to_str()).unwrap_or("");
         if ext == "md" {
             let content =
-                std::fs::read_to_string(&canonical).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            let html = render_markdown(&content);
-            let title = canonical
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("Note");
+                std::fs::read_to_string(&canonical).map_err(internal)?;
+            let index = state.link_indexes.get(config);
+            let from = vault::relative(&notes_canonical, &canonical);
+            let fm = frontmatter::parse(&content).0;
+            let toc = fm.get_bool("toc").unwrap_or(false);
+            let stripped = comments::strip(&content);
+            let toggles = dir_config
+                .can_write()
+                .then(|| Toggles::new(&canonical, &stripped, &markdown_options()))
+                .flatten();
+            let markdown = process_wiki_links(&stripped, &from, &notes_canonical, &index);
+            let html = render_markdown(&markdown, toc, toggles.as_ref());
+            // Diagram commands come from the global config, never a vault's `.para.toml`
+            let mut html = state.diagrams.render(&html, &state.config.diagrams);
+            html = format!("{}{html}", properties::render(&fm));
+            if let Some(date) = journal::date_of(&config.journal, &from) {
+                let dates = journal::dates(&config.journal, state.note_paths.paths().iter());
+                let visible = |day| {
+                    let path = notes_canonical.join(journal::daily_path(&config.journal, day));
+                    !config.is_private(&notes_canonical, &path)
+                };
+                let today = chrono::Local::now().date_naive();
+                html = format!("{}{html}", render_journal_nav(date, today, &dates, visible));
+            }
+            if dir_config.can_write() && archive::is_project(&from) {
+                html = format!("{}{html}", render_archive_button(&from));
+            }
+            if dir_config.links.unfurl {
+                html = state.link_previews.render_cards(&html);
+            }
+            let mut html = process_external_links(&html, &dir_config.links);
+            let problems = schema::validate(&fm, &dir_config.schema);
+            if !problems.is_empty() {
+                html = format!("{}{html}", render_schema_warnings(&problems));
+            }
+            if dir_config.spelling.enabled
+                && let Some(speller) = state.speller()
+            {
+                html = speller.mark_html(&html);
+            }
+            if let Some(re) = highlight {
+                html = mark_search_hits(&html, re);
+            }
+            // Last, so nothing added above can slip markup past the cleaner
+            if state.sanitize {
+                html = sanitize::clean(&html);
+            }
+            let title = note::title(&canonical, &content);
             let edit_path = canonical
                 .strip_prefix(&notes_canonical)
                 .ok()
                 .and_then(|p| p.to_str())
                 .map(|p| format!("/{}", p));
-            Ok(build_response(title, &html, &file_tree, query, is_htmx, edit_path.as_deref()))
+            Ok(build_response(
+                state,
+                config,
+                &title,
+                &html,
+                query,
+                is_htmx,
+                edit_path.as_deref(),
+                dir_config.theme,
+            ))
+        } else if let Some(renderer) = render::for_path(&canonical) {
+            let bytes = std::fs::read(&canonical).map_err(internal)?;
+            let name = canonical
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("File");
+            let href = encode_path(&format!("/raw/{}", vault::relative(&notes_canonical, &canonical)));
+            let body = renderer.render(name, &String::from_utf8_lossy(&bytes));
+            let mut html = format!(
+                "{body}<p class=\"download\"><a href=\"{}\" download hx-boost=\"false\">Download {}</a></p>\n",
+                html_escape(&href),
+                html_escape(name)
+            );
+            if state.sanitize {
+                html = sanitize::clean(&html);
+            }
+            Ok(build_response(state, config, name, &html, query, is_htmx, None, dir_config.theme))
         } else {
             // Serve static files (images, etc.)
             let content_type = match ext {
@@ -407,35 +3551,132 @@ async fn serve_path(
                 "pdf" => "application/pdf",
                 "css" => "text/css",
                 "js" => "application/javascript",
+                "mp3" => "audio/mpeg",
+                "m4a" => "audio/mp4",
+                "wav" => "audio/wav",
+                "ogg" | "oga" | "opus" => "audio/ogg",
+                "flac" => "audio/flac",
+                "mp4" | "m4v" => "video/mp4",
+                "webm" => "video/webm",
+                "ogv" => "video/ogg",
+                "mov" => "video/quicktime",
                 _ => "application/octet-stream",
             };
-            let bytes = std::fs::read(&canonical).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            Ok(Response::builder()
-                .status(StatusCode::OK)
+            let len = canonical
+                .metadata()
+                .map_err(internal)?
+                .len();
+            let mut response = Response::builder()
                 .header(header::CONTENT_TYPE, content_type)
-                .body(Body::from(bytes))
-                .unwrap())
+                .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+                .header(header::ACCEPT_RANGES, "bytes");
+            // An SVG opened on its own could run scripts as the vault's pages
+            if ext == "svg" {
+                response = response.header(
+                    header::CONTENT_SECURITY_POLICY,
+                    "default-src 'none'; style-src 'unsafe-inline'",
+                );
+            }
+            // Audio and video players fetch pieces as they're scrubbed through
+            let (response, first, count) = match range.and_then(|range| parse_range(range, len)) {
+                Some(Some((first, last))) => (
+                    response
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .header(header::CONTENT_RANGE, format!("bytes {first}-{last}/{len}")),
+                    first,
+                    last - first + 1,
+                ),
+                Some(None) => {
+                    return Ok(response
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+                        .body(Body::empty())
+                        .unwrap());
+                }
+                None => (response.status(StatusCode::OK), 0, len),
+            };
+            let body = file_body(&canonical, first, count, config.files.max_buffered_bytes)
+                .await
+                .map_err(internal)?;
+            Ok(response.header(header::CONTENT_LENGTH, count).body(body).unwrap())
         }
     } else if canonical.is_dir() {
-        let html = render_directory(&canonical, notes_dir)?;
+        let mut html = render_directory(&canonical, &notes_canonical, config)?;
+        let relative = vault::relative(&notes_canonical, &canonical);
+        if dir_config.can_write() && archive::is_project(&relative) {
+            html = format!("{}{html}", render_archive_button(&relative));
+        }
+        if canonical == notes_canonical {
+            let activity = Activity::collect(&notes_canonical, config);
+            if activity.has_journal() {
+                let today = chrono::Local::now().date_naive();
+                html = format!("{}{html}", render_heatmap(&activity, today));
+            }
+        }
         let dir_name = canonical
             .file_name()
             .and_then(|s| s.to_str())
             .unwrap_or("Notes");
-        Ok(build_response(dir_name, &html, &file_tree, query, is_htmx, None))
+        Ok(build_response(state, config, dir_name, &html, query, is_htmx, None, dir_config.theme))
     } else {
         Err(StatusCode::NOT_FOUND)
     }
 }
 
-fn build_response(title: &str, content: &str, file_tree: &str, query: &str, is_htmx: bool, edit_path: Option<&str>) -> Response {
-    let edit_attr = edit_path.map(|p| format!(" data-edit-path=\"{}\"", html_escape(p))).unwrap_or_default();
+/// The inclusive byte range a `Range` header asks for in a file of `len`
+/// bytes: `None` to ignore it and send the whole file, as for several ranges
+/// at once, or `Some(None)` when the range is past the end.
+fn parse_range(range: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let spec = range.strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = match (first.trim(), last.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        (first, "") => (first.parse().ok()?, len.saturating_sub(1)),
+        (first, last) => (first.parse().ok()?, last.parse::<u64>().ok()?.min(len.saturating_sub(1))),
+    };
+    Some((first <= last && first < len).then_some((first, last)))
+}
+
+/// `count` bytes of a file from `first`: read into memory when there are no
+/// more than `max_buffered` of them, else streamed from disk.
+async fn file_body(path: &FsPath, first: u64, count: u64, max_buffered: u64) -> std::io::Result<Body> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(first)).await?;
+    let mut file = file.take(count);
+    if count > max_buffered {
+        return Ok(Body::from_stream(ReaderStream::new(file)));
+    }
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).await?;
+    Ok(Body::from(bytes))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_response(
+    state: &AppState,
+    config: &Config,
+    title: &str,
+    content: &str,
+    query: &str,
+    is_htmx: bool,
+    edit_path: Option<&str>,
+    theme: Theme,
+) -> Response {
     if is_htmx {
-        // Return just the main content with a title update, plus edit path for JS
+        // Return just the main content with a title update, plus edit path and theme for JS
         let html = format!(
-            "<title>{title} - para</title><script>document.querySelector('main').dataset.editPath='{edit_path}';</script>{content}",
+            "<title>{title} - para</title><script>document.querySelector('main').dataset.editPath='{edit_path}';document.documentElement.dataset.theme='{theme}';</script>{content}",
             edit_path = edit_path.unwrap_or(""),
-            title = title,
+            theme = theme.as_str(),
+            title = html_escape(title),
             content = content
         );
         Response::builder()
@@ -444,157 +3685,820 @@ fn build_response(title: &str, content: &str, file_tree: &str, query: &str, is_h
             .body(Body::from(html))
             .unwrap()
     } else {
-        Html(wrap_html(title, content, file_tree, query, edit_path)).into_response()
+        let file_tree = if state.mounted {
+            render_mounted_tree(&state.vaults, config)
+        } else {
+            match state.notes_dir.canonicalize() {
+                Ok(root) => render_file_tree(&root, &root, config).unwrap_or_default(),
+                Err(_) => String::new(),
+            }
+        };
+        let head_meta = og_meta(state, title, edit_path);
+        let mermaid = state.config.diagrams.mermaid.is_empty();
+        let vault_switcher = render_vault_switcher(state);
+        let html = wrap_html(
+            title,
+            content,
+            &file_tree,
+            query,
+            edit_path,
+            &head_meta,
+            &vault_switcher,
+            theme,
+            mermaid,
+        );
+        // Hides the editing buttons, which would only be refused
+        let html = if state.config.read_only {
+            html.replacen("<html lang=\"en\"", "<html lang=\"en\" data-read-only", 1)
+        } else {
+            html
+        };
+        Html(html).into_response()
+    }
+}
+
+pub(crate) fn vault_name(notes_dir: &FsPath) -> String {
+    notes_dir
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Notes")
+        .to_string()
+}
+
+/// Short fingerprint of a note's contents, used to detect edits made
+/// elsewhere while the browser editor is open.
+fn content_version(content: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Open Graph tags for shared vaults; only emitted when a public URL is configured.
+fn og_meta(state: &AppState, title: &str, edit_path: Option<&str>) -> String {
+    let Some(public_url) = state.public_url.as_deref() else {
+        return String::new();
+    };
+    let base = public_url.trim_end_matches('/');
+    let site_name = state
+        .notes_dir
+        .canonicalize()
+        .map(|p| vault_name(&p))
+        .unwrap_or_else(|_| "Notes".to_string());
+
+    let mut meta = format!(
+        "\n    <meta property=\"og:title\" content=\"{title}\">\n    <meta property=\"og:site_name\" content=\"{site_name}\">\n    <meta property=\"og:type\" content=\"article\">",
+        title = html_escape(title),
+        site_name = html_escape(&site_name)
+    );
+    if let Some(path) = edit_path {
+        let path = encode_path(path);
+        meta.push_str(&format!(
+            "\n    <meta property=\"og:url\" content=\"{base}{path}\">\n    <meta property=\"og:image\" content=\"{base}/og{path}.png\">\n    <meta name=\"twitter:card\" content=\"summary_large_image\">",
+            base = html_escape(base),
+            path = html_escape(&path)
+        ));
     }
+    meta
 }
 
-fn html_escape(s: &str) -> String {
+/// Percent-encode a URL path, leaving `/` separators intact.
+pub(crate) fn encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+pub(crate) fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
         .replace('"', "&quot;")
 }
 
-fn render_search_results(output: &str, query: &str) -> String {
-    let mut html = format!(
-        "<h1>Search results for \"{}\"</h1>\n",
-        html_escape(query)
-    );
-    let mut current_file: Option<String> = None;
-    let mut lines_buffer: Vec<String> = Vec::new();
+fn search_heading(query: &str) -> String {
+    format!("<h1>Search results for \"{}\"</h1>\n", html_escape(query))
+}
 
-    let flush_file = |html: &mut String, file: &Option<String>, lines: &mut Vec<String>| {
-        if let Some(f) = file {
-            if !lines.is_empty() {
-                html.push_str(&format!(
-                    "<div class=\"search-result\"><a href=\"/{}\">{}</a><pre>{}</pre></div>\n",
-                    f,
-                    f,
-                    lines.join("\n")
-                ));
-                lines.clear();
-            }
-        }
+/// Results linking to their notes with the query carried along, so the note
+/// opens with its matches marked.
+fn render_search_results(
+    results: &[FileMatches],
+    query: &str,
+    mode: Mode,
+    highlight_re: Option<&Regex>,
+) -> String {
+    let mut html = String::new();
+
+    for result in results {
+        let lines: Vec<String> = result
+            .lines
+            .iter()
+            .map(|line| match line {
+                Some(line) => highlight(line, highlight_re),
+                None => "...".to_string(),
+            })
+            .collect();
+        // Notes that only matched on their name have no lines to show.
+        let excerpt = if lines.is_empty() {
+            String::new()
+        } else {
+            format!("<pre>{}</pre>", lines.join("\n"))
+        };
+        html.push_str(&format!(
+            "<div class=\"search-result\"><a href=\"{}?highlight={}&amp;mode={}\">{}</a> <span class=\"search-score\">{:.2}</span>{}</div>\n",
+            html_escape(&encode_path(&format!("/{}", result.path))),
+            encode_path(query),
+            mode.as_str(),
+            html_escape(&result.path),
+            result.score,
+            excerpt
+        ));
+    }
+    html
+}
+
+/// The number of matches and links to the other pages of results.
+fn render_pagination(base: &str, query: &str, mode: Mode, page: usize, results: &Results) -> String {
+    let pages = results.pages();
+    let noun = if results.total == 1 { "note" } else { "notes" };
+    let mut html = format!("<nav class=\"pagination\"><span>{} {noun}", results.total);
+    if pages > 1 {
+        html.push_str(&format!(", page {} of {pages}", page.min(pages)));
+    }
+    html.push_str("</span>");
+    let link = |page: usize, label: &str| {
+        format!(
+            " <a href=\"{base}?q={}&amp;mode={}&amp;page={page}\">{label}</a>",
+            encode_path(query),
+            mode.as_str()
+        )
     };
+    if page > 1 {
+        html.push_str(&link(page.min(pages + 1) - 1, "Previous"));
+    }
+    if page < pages {
+        html.push_str(&link(page + 1, "Next"));
+    }
+    html.push_str("</nav>\n");
+    html
+}
 
-    for line in output.lines() {
-        if let Some((file_part, rest)) = line.split_once(':') {
-            if let Some((_, content)) = rest.split_once(':') {
-                if current_file.as_deref() != Some(file_part) {
-                    flush_file(&mut html, &current_file, &mut lines_buffer);
-                    current_file = Some(file_part.to_string());
-                }
-                let escaped = html_escape(content);
-                let escaped_query = regex::escape(&html_escape(query));
-                let highlight_re = Regex::new(&format!("(?i){}", escaped_query))
-                    .unwrap_or_else(|_| Regex::new("").unwrap());
-                let highlighted = highlight_re
-                    .replace_all(&escaped, |caps: &regex::Captures| {
-                        format!("<mark>{}</mark>", &caps[0])
-                    })
-                    .to_string();
-                lines_buffer.push(highlighted);
+/// Links re-running the query in each search mode, the current one marked.
+/// Semantic search is only offered when it's configured.
+fn render_search_modes(query: &str, current: Mode, semantic: bool) -> String {
+    let links: Vec<String> = Mode::ALL
+        .iter()
+        .filter(|mode| semantic || **mode != Mode::Semantic)
+        .map(|mode| {
+            let class = if *mode == current { " class=\"current\"" } else { "" };
+            format!(
+                "<a href=\"/search?q={}&amp;mode={}\"{class}>{}</a>",
+                encode_path(query),
+                mode.as_str(),
+                mode.as_str()
+            )
+        })
+        .collect();
+    format!("<p class=\"search-modes\">Match: {}</p>\n", links.join(" · "))
+}
+
+fn render_suggestions(paths: &[String]) -> String {
+    if paths.is_empty() {
+        return String::new();
+    }
+    let mut html = String::from("<ul>\n");
+    for path in paths {
+        let folder = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}<span class=\"suggestion-folder\">{}</span></a></li>\n",
+            html_escape(&encode_path(&format!("/{path}"))),
+            html_escape(&search::name(FsPath::new(path))),
+            html_escape(folder)
+        ));
+    }
+    html.push_str("</ul>");
+    html
+}
+
+/// Escape a line, wrapping matches of `re` in `<mark>`.
+fn highlight(line: &str, re: Option<&Regex>) -> String {
+    let Some(re) = re else {
+        return html_escape(line);
+    };
+    let mut out = String::new();
+    let mut last = 0;
+    for m in re.find_iter(line).filter(|m| !m.is_empty()) {
+        out.push_str(&html_escape(&line[last..m.start()]));
+        out.push_str(&format!("<mark>{}</mark>", html_escape(m.as_str())));
+        last = m.end();
+    }
+    out.push_str(&html_escape(&line[last..]));
+    out
+}
+
+fn render_archive(items: &[ArchivedItem]) -> String {
+    if items.is_empty() {
+        return "<p>Nothing archived yet.</p>".to_string();
+    }
+
+    let mut html = String::new();
+    let mut current_year: Option<Option<u32>> = None;
+    let mut current_month: Option<Option<u32>> = None;
+
+    for item in items {
+        if current_year != Some(item.year) {
+            if current_year.is_some() {
+                html.push_str("</ul>\n");
+            }
+            match item.year {
+                Some(year) => html.push_str(&format!("<h2>{year}</h2>\n")),
+                None => html.push_str("<h2>Undated</h2>\n"),
             }
-        } else if line.starts_with("--") {
-            if !lines_buffer.is_empty() {
-                lines_buffer.push("...".to_string());
+            current_year = Some(item.year);
+            current_month = None;
+        } else if current_month != Some(item.month) {
+            html.push_str("</ul>\n");
+        }
+
+        if current_month != Some(item.month) {
+            if let Some(month) = item.month {
+                html.push_str(&format!("<h3>{}</h3>\n", MONTH_NAMES[month as usize - 1]));
             }
+            html.push_str("<ul class=\"file-listing\">\n");
+            current_month = Some(item.month);
         }
+
+        html.push_str(&format!(
+            "  <li><a href=\"/{path}\">{name}</a></li>\n",
+            path = html_escape(&item.path),
+            name = html_escape(&item.name)
+        ));
     }
 
-    flush_file(&mut html, &current_file, &mut lines_buffer);
+    html.push_str("</ul>");
+    html
+}
 
-    if html.contains("search-result") {
-        html
-    } else {
-        format!("<h1>No results for \"{}\"</h1>", html_escape(query))
+fn render_attachments(items: &[Attachment]) -> String {
+    let total: u64 = items.iter().map(|a| a.size).sum();
+    let mut html = format!(
+        "<h1>Attachments</h1>\n<p>{} files, {}</p>\n",
+        items.len(),
+        attachments::format_size(total)
+    );
+    if items.is_empty() {
+        return html;
+    }
+
+    html.push_str("<table class=\"attachments\">\n<tr><th></th><th>File</th><th>Type</th><th>Size</th><th>Referenced by</th></tr>\n");
+    for item in items {
+        let href = encode_path(&format!("/{}", item.path));
+        let kind = attachment_kind(&item.path);
+        let preview = if kind == "image" {
+            format!(
+                "<img class=\"attachment-preview\" src=\"/thumb{href}\" alt=\"\" loading=\"lazy\">",
+                href = html_escape(&href)
+            )
+        } else {
+            format!("<span class=\"attachment {}\"></span>", attachment_class(&item.path))
+        };
+        let referenced_by = if item.referenced_by.is_empty() {
+            "<span class=\"unreferenced\">none</span>".to_string()
+        } else {
+            item.referenced_by
+                .iter()
+                .map(|note| {
+                    format!(
+                        "<a href=\"{}\">{}</a>",
+                        html_escape(&encode_path(&format!("/{note}"))),
+                        html_escape(note)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("<br>")
+        };
+        html.push_str(&format!(
+            "<tr><td>{preview}</td><td><a href=\"{href}\" hx-boost=\"false\">{path}</a></td><td>{kind}</td><td>{size}</td><td>{referenced_by}</td></tr>\n",
+            preview = preview,
+            href = html_escape(&href),
+            path = html_escape(&item.path),
+            kind = kind,
+            size = attachments::format_size(item.size),
+            referenced_by = referenced_by
+        ));
     }
+    html.push_str("</table>");
+    html
 }
 
-fn render_markdown(content: &str) -> String {
+/// Comrak options notes are parsed and rendered with: GitHub's extensions,
+/// footnotes, heading ids and frontmatter, with raw HTML kept.
+fn markdown_options() -> Options<'static> {
     let mut options = Options::default();
     options.extension.strikethrough = true;
     options.extension.table = true;
     options.extension.autolink = true;
     options.extension.tasklist = true;
     options.extension.footnotes = true;
+    options.extension.header_ids = Some(String::new());
+    options.extension.front_matter_delimiter = Some("---".to_string());
     options.render.unsafe_ = true;
+    options
+}
+
+/// Render a note's markdown. `toc` adds an outline of its headings even
+/// without a `[TOC]` marker. With `toggles`, its tasks can be ticked off
+/// from the page.
+pub(crate) fn render_markdown(content: &str, toc: bool, toggles: Option<&Toggles>) -> String {
+    let options = markdown_options();
+    let arena = Arena::new();
+    let root = parse_document(&arena, content, &options);
+    callouts::render(&arena, root);
+    toc::render(&arena, root, toc);
+    if let Some(toggles) = toggles {
+        tasks::render(&arena, root, toggles);
+    }
+    let mut html = Vec::new();
+    format_html(root, &options, &mut html).unwrap_or_default();
+    String::from_utf8(html).unwrap_or_default()
+}
+
+fn render_schema_warnings(problems: &[String]) -> String {
+    let mut html = String::from("<div class=\"schema-warnings\">\n<strong>Frontmatter</strong>\n<ul>\n");
+    for problem in problems {
+        html.push_str(&format!("<li>{}</li>\n", render_inline_code(problem)));
+    }
+    html.push_str("</ul>\n</div>\n");
+    html
+}
+
+/// Escape text, turning `backticked` spans into `<code>`.
+fn render_inline_code(text: &str) -> String {
+    html_escape(text)
+        .split('`')
+        .enumerate()
+        .map(|(i, part)| if i % 2 == 1 { format!("<code>{part}</code>") } else { part.to_string() })
+        .collect()
+}
+
+/// Deepest chain of notes embedded in embedded notes that is inlined.
+const MAX_EMBED_DEPTH: usize = 4;
 
-    markdown_to_html(content, &options)
+static LABEL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\|([^\]]*)\]\]$").unwrap());
+
+/// Turn `[[Note]]` and `[[Note|label]]` into links to the note they resolve
+/// to. Ambiguous names link to a disambiguation page and missing ones are
+/// marked broken. `[[YYYY-MM-DD]]` links to that day's daily note and reads
+/// as the date. `[[Note#Heading]]` links to the heading's anchor, and
+/// `[[#Heading]]` to one in the same note. `![[Note]]` and
+/// `![[Note#Heading]]` inline the note, or the section under that heading.
+/// Code blocks are left alone.
+pub(crate) fn process_wiki_links(content: &str, from: &str, notes_dir: &FsPath, index: &LinkIndex) -> String {
+    embed_wiki_links(content, from, notes_dir, index, &mut vec![from.to_string()])
 }
 
-fn render_file_tree(dir: &PathBuf, notes_root: &PathBuf) -> Result<String, StatusCode> {
-    fn render_tree_recursive(
-        dir: &PathBuf,
-        notes_root: &PathBuf,
-        depth: usize,
-    ) -> Result<String, StatusCode> {
-        if depth > 3 {
-            return Ok(String::new());
+/// `process_wiki_links` for the last note in `embedding`, the chain of
+/// embeds from the page's note down to this one.
+fn embed_wiki_links(
+    content: &str,
+    from: &str,
+    notes_dir: &FsPath,
+    index: &LinkIndex,
+    embedding: &mut Vec<String>,
+) -> String {
+    let mut render = |caps: &regex::Captures| -> String {
+        let whole = &caps[0];
+        if caps[1].trim().is_empty() && (whole.starts_with('!') || caps.get(2).is_none()) {
+            whole.to_string()
+        } else if whole.starts_with('!') {
+            render_embed(caps, from, notes_dir, index, embedding).unwrap_or_else(|| whole.to_string())
+        } else {
+            render_wiki_link(caps, from, index)
         }
+    };
 
-        let mut entries: Vec<_> = std::fs::read_dir(dir)
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .filter_map(|e| e.ok())
-            .collect();
+    let mut out = String::with_capacity(content.len());
+    let mut in_code = false;
+    for line in content.split_inclusive('\n') {
+        if note::is_fence(line) {
+            in_code = !in_code;
+            out.push_str(line);
+        } else if in_code {
+            out.push_str(line);
+        } else {
+            out.push_str(&note::WIKILINK_RE.replace_all(line, &mut render));
+        }
+    }
+    out
+}
+
+fn render_wiki_link(caps: &regex::Captures, from: &str, index: &LinkIndex) -> String {
+    let whole = &caps[0];
+    let target = caps[1].trim();
+    let alias = LABEL_RE.captures(whole).map(|c| c[1].trim().to_string());
+    let date = due::parse_date(target);
+    let label = alias.unwrap_or_else(|| match date {
+        Some(date) => journal::display(date),
+        None => whole
+            .trim_start_matches('!')
+            .trim_start_matches("[[")
+            .trim_end_matches("]]")
+            .to_string(),
+    });
+    let link = Link {
+        target: target.to_string(),
+        kind: LinkKind::Wiki,
+    };
+    let fragment = caps
+        .get(2)
+        .map(|m| format!("#{}", heading_anchor(&m.as_str()[1..])))
+        .unwrap_or_default();
+    let resolve_href = format!("/resolve?name={}", encode_path(target));
+    let (href, class) = match index.lookup(from, &link) {
+        _ if target.is_empty() => (fragment, "wikilink"),
+        // Daily notes that don't exist yet are created when followed
+        Resolution::Found(path) if !index.contains(&path) && date.is_some() => (
+            format!("/journal/{}", encode_path(target)),
+            "wikilink journal missing",
+        ),
+        Resolution::Found(path) => (encode_path(&format!("/{path}")) + &fragment, "wikilink"),
+        Resolution::Ambiguous(_) => (resolve_href, "wikilink ambiguous"),
+        Resolution::Missing => (resolve_href, "wikilink broken"),
+    };
+    format!(
+        "<a href=\"{}\" class=\"{class}\">{}</a>",
+        html_escape(&href),
+        html_escape(&label)
+    )
+}
+
+/// The `id` rendered markdown gives the first heading titled `heading`.
+fn heading_anchor(heading: &str) -> String {
+    Anchorizer::new().anchorize(heading.trim().to_string())
+}
 
-        entries.sort_by_key(|e| e.file_name());
+/// Render what an `![[...]]` embed points at. Notes become an HTML block;
+/// embeds that would repeat a note already being embedded, or nest too deep,
+/// fall back to a link, as do missing notes and headings. Images become an
+/// `<img>` and other attachments a link. Returns `None` for embeds of
+/// attachments that don't exist, leaving them as written.
+fn render_embed(
+    caps: &regex::Captures,
+    from: &str,
+    notes_dir: &FsPath,
+    index: &LinkIndex,
+    embedding: &mut Vec<String>,
+) -> Option<String> {
+    let target = caps[1].trim();
+    let link = Link {
+        target: target.to_string(),
+        kind: LinkKind::Wiki,
+    };
+    let path = match index.lookup(from, &link) {
+        Resolution::Found(path) if index.contains(&path) => path,
+        _ if FsPath::new(target).extension().is_none_or(|e| e == "md") => {
+            return Some(render_wiki_link(caps, from, index));
+        }
+        _ => return index.attachment(from, target).map(|path| render_attachment_embed(caps, &path)),
+    };
+    if embedding.contains(&path) || embedding.len() > MAX_EMBED_DEPTH {
+        return Some(render_wiki_link(caps, from, index));
+    }
 
-        let mut html = String::from("<ul>\n");
+    let content = std::fs::read_to_string(notes_dir.join(&path)).ok()?;
+    let content = comments::strip(&content);
+    let body = frontmatter::split(&content).1;
+    let heading = caps.get(2).map(|m| m.as_str()[1..].trim()).filter(|h| !h.is_empty());
+    let body = match heading {
+        Some(heading) => match section(body, heading) {
+            Some(section) => section,
+            None => return Some(render_wiki_link(caps, from, index)),
+        },
+        None => body.to_string(),
+    };
 
-        for entry in entries {
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
+    embedding.push(path.clone());
+    let html = render_markdown(&embed_wiki_links(&body, &path, notes_dir, index, embedding), false, None);
+    embedding.pop();
 
-            if name_str.starts_with('.') || name_str.starts_with('_') {
-                continue;
-            }
+    let label = LABEL_RE
+        .captures(&caps[0])
+        .map(|c| c[1].trim().to_string())
+        .unwrap_or_else(|| match heading {
+            Some(heading) => format!("{} › {heading}", search::name(FsPath::new(&path))),
+            None => search::name(FsPath::new(&path)),
+        });
+    // A blank line would end the HTML block early
+    let html = html.trim_end().replace("\n\n", "\n&#10;");
+    Some(format!(
+        "\n<div class=\"transclusion\">\n<a class=\"transclusion-source\" href=\"{}\">{}</a>\n{html}\n</div>\n\n",
+        html_escape(&encode_path(&format!("/{path}"))),
+        html_escape(&label)
+    ))
+}
 
-            let file_type = entry
-                .file_type()
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            let entry_path = entry.path();
-            let relative_path = entry_path
-                .strip_prefix(notes_root)
-                .unwrap_or(&entry_path)
-                .to_string_lossy();
+/// An embedded attachment. Images take Obsidian's size hints, `|400` for the
+/// width or `|400x300` for both, after any alt text (`|a diagram|400`).
+fn render_attachment_embed(caps: &regex::Captures, path: &str) -> String {
+    static SIZE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d+)(?:x(\d+))?$").unwrap());
 
-            if file_type.is_dir() {
-                let children = render_tree_recursive(&entry_path, notes_root, depth + 1)?;
-                if children.contains("<li>") || depth < 1 {
-                    html.push_str(&format!(
-                        "<li class=\"dir\"><span class=\"toggle\"></span><a href=\"/{path}/\">{name}</a>{children}</li>\n",
-                        path = relative_path,
-                        name = name_str,
-                        children = children
-                    ));
+    let href = html_escape(&encode_path(&format!("/{path}")));
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let label = LABEL_RE.captures(&caps[0]).map(|c| c[1].trim().to_string());
+    if attachment_kind(name) != "image" {
+        return format!(
+            "<a href=\"{href}\" class=\"embed\">{}</a>",
+            html_escape(label.as_deref().unwrap_or(name))
+        );
+    }
+
+    let (alt, size) = match label.as_deref().map(|l| l.rsplit_once('|').unwrap_or(("", l))) {
+        Some((alt, hint)) if SIZE_RE.is_match(hint.trim()) => (alt.trim(), SIZE_RE.captures(hint.trim())),
+        Some(_) => (label.as_deref().unwrap_or_default(), None),
+        None => ("", None),
+    };
+    let mut attrs = String::new();
+    if let Some(size) = size {
+        attrs.push_str(&format!(" width=\"{}\"", &size[1]));
+        if let Some(height) = size.get(2) {
+            attrs.push_str(&format!(" height=\"{}\"", height.as_str()));
+        }
+    }
+    format!(
+        "<img src=\"{href}\" alt=\"{}\" class=\"embed\"{attrs}>",
+        html_escape(if alt.is_empty() { name } else { alt })
+    )
+}
+
+/// The heading titled `heading` (ignoring case) and the lines under it, up
+/// to the next heading at the same level or above.
+fn section(body: &str, heading: &str) -> Option<String> {
+    let level = |line: &str| line.len() - line.trim_start_matches('#').len();
+    let mut found: Option<(usize, String)> = None;
+    let mut in_code = false;
+    for line in body.split_inclusive('\n') {
+        if note::is_fence(line) {
+            in_code = !in_code;
+        } else if !in_code && search::is_heading(line) {
+            let title = line.trim_start_matches('#').trim().trim_end_matches('#').trim();
+            match &found {
+                Some((open, _)) if level(line) <= *open => break,
+                None if title.to_lowercase() == heading.to_lowercase() => {
+                    found = Some((level(line), String::new()));
                 }
-            } else if name_str.ends_with(".md") && depth > 0 {
+                _ => {}
+            }
+        }
+        if let Some((_, text)) = &mut found {
+            text.push_str(line);
+        }
+    }
+    found.map(|(_, text)| text)
+}
+
+/// Wrap matches of `re` in the text of rendered HTML with
+/// `<mark class="search-hit">`. Tags, entities, and scripts and styles are
+/// left alone.
+fn mark_search_hits(html: &str, re: &Regex) -> String {
+    // Text between tags is already escaped, so matching stops at entities
+    // rather than cutting through them.
+    fn mark_text(text: &str, re: &Regex, out: &mut String) {
+        let mut last = 0;
+        let entities = ENTITY_RE.find_iter(text).map(|m| (m.start(), m.end()));
+        for (start, end) in entities.chain(std::iter::once((text.len(), text.len()))) {
+            let plain = &text[last..start];
+            let mut from = 0;
+            for m in re.find_iter(plain).filter(|m| !m.is_empty()) {
+                out.push_str(&plain[from..m.start()]);
+                out.push_str(&format!("<mark class=\"search-hit\">{}</mark>", m.as_str()));
+                from = m.end();
+            }
+            out.push_str(&plain[from..]);
+            out.push_str(&text[start..end]);
+            last = end;
+        }
+    }
+
+    let mut out = String::with_capacity(html.len());
+    let mut skip_depth = 0usize;
+    let mut last = 0;
+    for tag in TAG_RE.captures_iter(html) {
+        let whole = tag.get(0).unwrap();
+        let text = &html[last..whole.start()];
+        if skip_depth > 0 {
+            out.push_str(text);
+        } else {
+            mark_text(text, re, &mut out);
+        }
+        out.push_str(whole.as_str());
+        last = whole.end();
+
+        if matches!(tag[2].to_lowercase().as_str(), "script" | "style" | "svg") {
+            if &tag[1] == "/" {
+                skip_depth = skip_depth.saturating_sub(1);
+            } else {
+                skip_depth += 1;
+            }
+        }
+    }
+    mark_text(&html[last..], re, &mut out);
+    out
+}
+
+/// Apply the configured class, target, and rel attributes to outbound links.
+pub(crate) fn process_external_links(html: &str, links: &LinkConfig) -> String {
+    if !links.mark_external && !links.external_new_tab && links.external_rel.is_empty() {
+        return html.to_string();
+    }
+
+    let mut rel = links.external_rel.clone();
+    if links.external_new_tab && !rel.iter().any(|r| r == "noopener") {
+        rel.push("noopener".to_string());
+    }
+
+    EXTERNAL_ANCHOR_RE
+        .replace_all(html, |caps: &regex::Captures| {
+            let attrs = &caps[1];
+            let mut extra = String::new();
+            if links.mark_external && !attrs.contains("class=") {
+                extra.push_str(" class=\"external\"");
+            }
+            if links.external_new_tab && !attrs.contains("target=") {
+                extra.push_str(" target=\"_blank\"");
+            }
+            if !rel.is_empty() && !attrs.contains("rel=") {
+                extra.push_str(&format!(" rel=\"{}\"", html_escape(&rel.join(" "))));
+            }
+            format!("<a {attrs}{extra}>")
+        })
+        .to_string()
+}
+
+/// The sidebar's nested list for `dir`, `depth` folders below the notes root.
+fn render_tree(
+    dir: &PathBuf,
+    notes_root: &PathBuf,
+    config: &Config,
+    depth: usize,
+) -> Result<String, StatusCode> {
+    if depth > 3 {
+        return Ok(String::new());
+    }
+
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .map_err(internal)?
+        .filter_map(|e| e.ok())
+        .collect();
+
+    let dir_config = config.for_dir(notes_root, dir);
+    let files = &dir_config.files;
+    order::sort_entries(dir, &mut entries, dir_config.sort);
+
+    let mut html = String::from("<ul>\n");
+
+    for entry in entries {
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+
+        if name_str.starts_with('.') || name_str.starts_with('_') {
+            continue;
+        }
+
+        let file_type = entry
+            .file_type()
+            .map_err(internal)?;
+        let entry_path = entry.path();
+        let relative_path = entry_path
+            .strip_prefix(notes_root)
+            .unwrap_or(&entry_path)
+            .to_string_lossy();
+
+        if file_type.is_dir() {
+            if config.for_dir(notes_root, &entry_path).private {
+                continue;
+            }
+            let children = render_tree(&entry_path, notes_root, config, depth + 1)?;
+            if children.contains("<li") || depth < 1 {
                 html.push_str(&format!(
-                    "<li><a href=\"/{path}\">{name}</a></li>\n",
-                    path = relative_path,
-                    name = name_str
+                    "<li class=\"dir\"><span class=\"toggle\"></span><a href=\"/{path}/\">{name}</a>{children}</li>\n",
+                    path = html_escape(&encode_path(&relative_path)),
+                    name = html_escape(&name_str),
+                    children = children
                 ));
             }
+        } else if name_str.ends_with(".md") && depth > 0 {
+            html.push_str(&format!(
+                "<li{class}><a href=\"/{path}\">{title}</a></li>\n",
+                class = pinned_class(&entry),
+                path = html_escape(&encode_path(&relative_path)),
+                title = html_escape(&note::read_title(&entry_path))
+            ));
+        } else if render::for_path(&entry_path).is_some() && depth > 0 {
+            html.push_str(&format!(
+                "<li{class}><a href=\"/{path}\">{name}</a></li>\n",
+                class = pinned_class(&entry),
+                path = html_escape(&encode_path(&relative_path)),
+                name = html_escape(&name_str)
+            ));
+        } else if depth > 0 && files.shows(&name_str) {
+            html.push_str(&format!(
+                "<li class=\"attachment {kind}\"><a href=\"/{path}\" hx-boost=\"false\">{name}</a></li>\n",
+                kind = attachment_class(&name_str),
+                path = html_escape(&encode_path(&relative_path)),
+                name = html_escape(&name_str)
+            ));
         }
+    }
+
+    html.push_str("</ul>");
+    Ok(html)
+}
 
-        html.push_str("</ul>");
-        Ok(html)
+/// The sidebar for mounted vaults: each one a top-level folder at its
+/// prefix.
+fn render_mounted_tree(vaults: &[Vault], config: &Config) -> String {
+    let mut html = String::from("<nav class=\"file-tree\"><a href=\"/\">Notes</a><ul>\n");
+    for vault in vaults {
+        let Ok(root) = vault.path.canonicalize() else {
+            continue;
+        };
+        let mount = Mount {
+            root: String::new(),
+            base: mount_base(&vault.name),
+            bases: Arc::new(Vec::new()),
+        };
+        let children = render_tree(&root, &root, config, 1).unwrap_or_default();
+        html.push_str(&format!(
+            "<li class=\"dir\"><span class=\"toggle\"></span><a href=\"{}/\">{}</a>{}</li>\n",
+            mount.base,
+            html_escape(&vault.name),
+            mount.prefix_urls(&children)
+        ));
     }
+    html.push_str("</ul></nav>");
+    html
+}
 
+pub(crate) fn render_file_tree(dir: &PathBuf, notes_root: &PathBuf, config: &Config) -> Result<String, StatusCode> {
     let mut html = String::from("<nav class=\"file-tree\"><a href=\"/\">Notes</a>");
-    html.push_str(&render_tree_recursive(dir, notes_root, 0)?);
+    let searches = config.for_dir(notes_root, notes_root).searches;
+    if !searches.is_empty() {
+        html.push_str("<div class=\"saved-searches\">\n");
+        for search in &searches {
+            html.push_str(&format!(
+                "<a href=\"/search?q={}&amp;mode={}\">{}</a>\n",
+                encode_path(&search.query),
+                search.mode.as_str(),
+                html_escape(search.label())
+            ));
+        }
+        html.push_str("</div>");
+    }
+    html.push_str(&render_tree(dir, notes_root, config, 0)?);
     html.push_str("</nav>");
     Ok(html)
 }
 
-fn render_directory(dir: &PathBuf, notes_dir: &PathBuf) -> Result<String, StatusCode> {
+fn pinned_class(entry: &std::fs::DirEntry) -> &'static str {
+    if order::is_pinned(entry) {
+        " class=\"pinned\""
+    } else {
+        ""
+    }
+}
+
+/// Broad type of a non-markdown file, used for icons and labels.
+fn attachment_kind(name: &str) -> &'static str {
+    let ext = name.rsplit_once('.').map(|(_, e)| e.to_lowercase()).unwrap_or_default();
+    match ext.as_str() {
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" => "image",
+        "pdf" => "pdf",
+        "mp3" | "wav" | "ogg" | "m4a" | "flac" => "audio",
+        "mp4" | "webm" | "mov" | "mkv" => "video",
+        _ => "other",
+    }
+}
+
+/// CSS class selecting the type icon for a non-markdown file.
+fn attachment_class(name: &str) -> String {
+    format!("file-{}", attachment_kind(name))
+}
+
+pub(crate) fn render_directory(dir: &PathBuf, notes_dir: &PathBuf, config: &Config) -> Result<String, StatusCode> {
     let mut entries: Vec<_> = std::fs::read_dir(dir)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(internal)?
         .filter_map(|e| e.ok())
         .collect();
 
-    entries.sort_by_key(|e| e.file_name());
+    let dir_config = config.for_dir(notes_dir, dir);
+    order::sort_entries(dir, &mut entries, dir_config.sort);
 
     let mut html = String::from("<ul class=\"file-listing\">\n");
 
@@ -612,17 +4516,48 @@ fn render_directory(dir: &PathBuf, notes_dir: &PathBuf) -> Result<String, Status
 
         let file_type = entry
             .file_type()
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .map_err(internal)?;
 
         if file_type.is_dir() {
+            if config.for_dir(notes_dir, &entry.path()).private {
+                continue;
+            }
             html.push_str(&format!(
-                "  <li><a href=\"{name}/\">{name}/</a></li>\n",
-                name = name_str
+                "  <li{class}><a href=\"{href}/\">{name}/</a></li>\n",
+                class = pinned_class(&entry),
+                href = html_escape(&encode_path(&name_str)),
+                name = html_escape(&name_str)
             ));
         } else if name_str.ends_with(".md") {
             html.push_str(&format!(
-                "  <li><a href=\"{name}\">{name}</a></li>\n",
-                name = name_str
+                "  <li{class}><a href=\"{href}\">{title}</a></li>\n",
+                class = pinned_class(&entry),
+                href = html_escape(&encode_path(&name_str)),
+                title = html_escape(&note::read_title(&entry.path()))
+            ));
+        } else if render::for_path(&entry.path()).is_some() {
+            html.push_str(&format!(
+                "  <li{class}><a href=\"{href}\">{name}</a></li>\n",
+                class = pinned_class(&entry),
+                href = html_escape(&encode_path(&name_str)),
+                name = html_escape(&name_str)
+            ));
+        } else if dir_config.files.shows(&name_str) {
+            let thumb = if attachment_kind(&name_str) == "image" {
+                let relative = vault::relative(notes_dir, &entry.path());
+                format!(
+                    "<img class=\"listing-thumb\" src=\"{}\" alt=\"\" loading=\"lazy\">",
+                    html_escape(&encode_path(&format!("/thumb/{relative}")))
+                )
+            } else {
+                String::new()
+            };
+            html.push_str(&format!(
+                "  <li class=\"attachment {kind}\"><a href=\"{href}\" hx-boost=\"false\">{thumb}{name}</a></li>\n",
+                kind = attachment_class(&name_str),
+                href = html_escape(&encode_path(&name_str)),
+                thumb = thumb,
+                name = html_escape(&name_str)
             ));
         }
     }
@@ -631,18 +4566,78 @@ fn render_directory(dir: &PathBuf, notes_dir: &PathBuf) -> Result<String, Status
     Ok(html)
 }
 
-fn wrap_html(title: &str, content: &str, file_tree: &str, search_query: &str, edit_path: Option<&str>) -> String {
+/// A file embedded in the binary that pages load from `/assets/`.
+pub(crate) struct Asset {
+    /// Its name with a hash of its contents, e.g. `para-0123456789abcdef.css`.
+    pub file: String,
+    content_type: &'static str,
+    pub body: &'static str,
+}
+
+impl Asset {
+    fn new(name: &str, extension: &str, body: &'static str) -> Asset {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        Asset {
+            file: format!("{name}-{:016x}.{extension}", hasher.finish()),
+            content_type: if extension == "css" {
+                "text/css; charset=utf-8"
+            } else {
+                "text/javascript; charset=utf-8"
+            },
+            body,
+        }
+    }
+
+    fn url(&self) -> String {
+        format!("/assets/{}", self.file)
+    }
+}
+
+pub(crate) struct Assets {
+    css: Asset,
+    htmx: Asset,
+    mermaid: Asset,
+    graph: Asset,
+    main: Asset,
+    editor: Asset,
+}
+
+impl Assets {
+    pub fn all(&self) -> [&Asset; 6] {
+        [&self.css, &self.htmx, &self.mermaid, &self.graph, &self.main, &self.editor]
+    }
+}
+
+/// `mermaid` includes mermaid.js, for drawing diagrams in the browser.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn wrap_html(
+    title: &str,
+    content: &str,
+    file_tree: &str,
+    search_query: &str,
+    edit_path: Option<&str>,
+    head_meta: &str,
+    vault_switcher: &str,
+    theme: Theme,
+    mermaid: bool,
+) -> String {
     let edit_attr = edit_path.map(|p| format!(" data-edit-path=\"{}\"", p)).unwrap_or_default();
+    let mermaid_script = if mermaid {
+        format!("\n    <script src=\"{}\"></script>", ASSETS.mermaid.url())
+    } else {
+        String::new()
+    };
     format!(
         r#"<!DOCTYPE html>
-<html lang="en">
+<html lang="en" data-theme="{theme}">
 <head>
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>{title} - para</title>
-    <style>{para_css}</style>
-    <script>{htmx_js}</script>
-    <script>{mermaid_js}</script>
+    <title>{title} - para</title>{head_meta}
+    <link rel="stylesheet" href="{para_css}">
+    <script src="{htmx_js}"></script>{mermaid_script}
+    <script src="{graph_js}"></script>
     <link rel="stylesheet" href="https://esm.sh/@milkdown/crepe@7/theme/common/style.css">
     <link rel="stylesheet" href="https://esm.sh/@milkdown/crepe@7/theme/frame.css">
     <script>
@@ -655,11 +4650,17 @@ fn wrap_html(title: &str, content: &str, file_tree: &str, search_query: &str, ed
 <body>
     <nav class="navbar">
         <form class="search-form" action="/search" method="get" hx-get="/search" hx-target="main" hx-push-url="true">
-            <input type="text" name="q" placeholder="Search notes..." value="{search_query}" />
+            <input type="text" name="q" placeholder="Search notes..." value="{search_query}" autocomplete="off"
+                hx-get="/search/suggest" hx-trigger="input changed delay:150ms" hx-target="next .search-suggestions" hx-push-url="false" />
             <button type="submit">Search</button>
-        </form>
+            <div class="search-suggestions" hx-boost="true" hx-target="main" hx-push-url="true"></div>
+        </form>{vault_switcher}
+        <a class="random-note" href="/random" title="Open a random note">🎲</a>
         <span class="current-path"></span>
         <button id="edit-toggle" class="edit-toggle">Edit</button>
+        <a id="edit-source" class="edit-toggle edit-source">Source</a>
+        <button id="rename-note" class="edit-toggle edit-rename">Rename</button>
+        <button id="delete-note" class="edit-toggle edit-delete">Delete</button>
     </nav>
     <div class="content-wrapper">
         <div class="sidebar" hx-boost="true" hx-target="main" hx-push-url="true">
@@ -683,19 +4684,56 @@ fn wrap_html(title: &str, content: &str, file_tree: &str, search_query: &str, ed
             <div id="milkdown-editor"></div>
         </main>
     </div>
-    <script>{para_js}</script>
-    <script type="module">{editor_js}</script>
+    <dialog class="quick-switcher" hx-boost="true" hx-target="main" hx-push-url="true">
+        <input type="text" placeholder="Go to note..." autocomplete="off" />
+        <ul></ul>
+    </dialog>
+    <script src="{para_js}"></script>
+    <script type="module" src="{editor_js}"></script>
 </body>
 </html>"#,
-        title = title,
+        title = html_escape(title),
         content = content,
         file_tree = file_tree,
         search_query = html_escape(search_query),
-        para_css = PARA_CSS,
-        htmx_js = HTMX_JS,
-        mermaid_js = MERMAID_JS,
-        para_js = PARA_JS,
-        editor_js = EDITOR_JS,
-        edit_attr = edit_attr
+        para_css = ASSETS.css.url(),
+        htmx_js = ASSETS.htmx.url(),
+        mermaid_script = mermaid_script,
+        graph_js = ASSETS.graph.url(),
+        para_js = ASSETS.main.url(),
+        editor_js = ASSETS.editor.url(),
+        edit_attr = edit_attr,
+        head_meta = head_meta,
+        vault_switcher = vault_switcher,
+        theme = theme.as_str()
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_reads_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Some((0, 99))));
+        assert_eq!(parse_range("bytes=500-", 1000), Some(Some((500, 999))));
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Some((900, 999))));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some(Some((0, 999))));
+        assert_eq!(parse_range("bytes=990-5000", 1000), Some(Some((990, 999))));
+    }
+
+    #[test]
+    fn parse_range_flags_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(None));
+        assert_eq!(parse_range("bytes=5-2", 1000), Some(None));
+        assert_eq!(parse_range("bytes=0-0", 0), Some(None));
+    }
+
+    #[test]
+    fn parse_range_ignores_what_it_cannot_serve() {
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=abc", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::attachments::percent_decode;
+use crate::comments;
+use crate::config::{Config, Theme};
+use crate::frontmatter;
+use crate::links::{self, LinkIndex};
+use crate::note;
+use crate::properties;
+use crate::sanitize;
+use crate::serve::{self, encode_path, html_escape, vault_name};
+use crate::templates::TEMPLATES_DIR;
+use crate::vault;
+
+/// URLs in `href`, `src` and `action` attributes.
+static URL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(\s(?:href|src|action)=")([^"]*)""#).unwrap());
+/// Sidebar and listing entries whose link went nowhere in the export, like
+/// empty folders or the export's own.
+static DEAD_ITEM_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\s*<li[^>]*>(?:<span class="toggle"></span>)?<a>[^<]*</a>(?:<ul>\s*</ul>)?</li>"#).unwrap()
+});
+/// The search bar and quick switcher, which need the server to answer them.
+static SERVER_CHROME_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)\s*(?:<nav class="navbar">.*?</nav>|<dialog class="quick-switcher".*?</dialog>)"#).unwrap()
+});
+
+/// Write the vault to `out` as a static site: a page per note, an
+/// `index.html` per folder, and attachments, fonts, scripts and styles
+/// copied alongside.
+/// Links are relative, so the site works from any folder of a static host,
+/// or straight off the disk. Returns how many notes were written.
+pub fn run(notes_dir: &Path, config: &Config, out: &Path) -> Result<usize> {
+    let notes_dir = notes_dir
+        .canonicalize()
+        .with_context(|| format!("{} not found", notes_dir.display()))?;
+    std::fs::create_dir_all(out).with_context(|| format!("failed to create {}", out.display()))?;
+    let out = out.canonicalize()?;
+
+    // Leave out templates, and the export itself when it lands in the vault
+    let files: Vec<PathBuf> = vault::files(&notes_dir, config)
+        .into_iter()
+        .filter(|path| !path.starts_with(&out))
+        .filter(|path| !vault::relative(&notes_dir, path).starts_with(&format!("{TEMPLATES_DIR}/")))
+        .collect();
+    let site = Site::new(&notes_dir, config, &out, &files);
+
+    let mut notes = 0;
+    for path in &files {
+        let relative = vault::relative(&notes_dir, path);
+        if relative.ends_with(".md") {
+            site.write_note(path, &relative)?;
+            notes += 1;
+        } else {
+            let target = out.join(&relative);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(path, &target).with_context(|| format!("failed to copy {relative}"))?;
+        }
+    }
+    for dir in &site.dirs {
+        site.write_dir(dir)?;
+    }
+
+    std::fs::create_dir_all(out.join("fonts"))?;
+    for (name, bytes) in serve::FONTS {
+        std::fs::write(out.join("fonts").join(name), bytes)?;
+    }
+    std::fs::create_dir_all(out.join("assets"))?;
+    for asset in serve::ASSETS.all() {
+        std::fs::write(out.join("assets").join(&asset.file), asset.body)?;
+    }
+    // GitHub Pages would otherwise skip `_attachments` and other `_` folders
+    std::fs::write(out.join(".nojekyll"), "")?;
+    Ok(notes)
+}
+
+struct Site<'a> {
+    notes_dir: &'a Path,
+    config: &'a Config,
+    out: &'a Path,
+    index: LinkIndex,
+    /// The sidebar, rendered once for every page.
+    tree: String,
+    /// Every exported file, relative to the notes root.
+    files: BTreeSet<String>,
+    /// Every folder holding an exported file, with a trailing `/`, and `""`
+    /// for the root.
+    dirs: BTreeSet<String>,
+}
+
+impl<'a> Site<'a> {
+    fn new(notes_dir: &'a Path, config: &'a Config, out: &'a Path, paths: &[PathBuf]) -> Site<'a> {
+        let files: BTreeSet<String> = paths.iter().map(|p| vault::relative(notes_dir, p)).collect();
+        let mut dirs = BTreeSet::from([String::new()]);
+        for file in &files {
+            let mut end = 0;
+            while let Some(slash) = file[end..].find('/') {
+                end += slash + 1;
+                dirs.insert(file[..end].to_string());
+            }
+        }
+        let root = notes_dir.to_path_buf();
+        Site {
+            notes_dir,
+            config,
+            out,
+            index: links::index(notes_dir, config),
+            tree: serve::render_file_tree(&root, &root, config).unwrap_or_default(),
+            files,
+            dirs,
+        }
+    }
+
+    /// Render a note the way `para serve` shows it to a reader, without
+    /// the parts that need the server.
+    fn write_note(&self, path: &Path, relative: &str) -> Result<()> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("failed to read {relative}"))?;
+        let dir_config = self
+            .config
+            .for_dir(self.notes_dir, path.parent().unwrap_or(self.notes_dir));
+        let fm = frontmatter::parse(&content).0;
+        let toc = fm.get_bool("toc").unwrap_or(false);
+        let markdown = serve::process_wiki_links(&comments::strip(&content), relative, self.notes_dir, &self.index);
+        let html = sanitize::clean(&serve::render_markdown(&markdown, toc, None));
+        let html = format!("{}{html}", properties::render(&fm));
+        let html = serve::process_external_links(&html, &dir_config.links);
+        let title = note::title(path, &content);
+        let file = format!("{}.html", relative.trim_end_matches(".md"));
+        self.write_page(relative, &file, &title, &html, dir_config.theme)
+    }
+
+    /// A folder's listing, unless the folder has an `index.md` of its own.
+    fn write_dir(&self, dir: &str) -> Result<()> {
+        if self.files.contains(&format!("{dir}index.md")) {
+            return Ok(());
+        }
+        let path = self.notes_dir.join(dir);
+        let html = serve::render_directory(&path, &self.notes_dir.to_path_buf(), self.config).unwrap_or_default();
+        let title = match dir.trim_end_matches('/').rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => vault_name(self.notes_dir),
+        };
+        let theme = self.config.for_dir(self.notes_dir, &path).theme;
+        self.write_page(dir, &format!("{dir}index.html"), &title, &html, theme)
+    }
+
+    /// Wrap `content` in the page layout and write it to `file` in the
+    /// site, with its links made relative to `page`.
+    fn write_page(&self, page: &str, file: &str, title: &str, content: &str, theme: Theme) -> Result<()> {
+        let content = DEAD_ITEM_RE.replace_all(&self.relink(page, content), "").into_owned();
+        let tree = DEAD_ITEM_RE.replace_all(&self.relink(page, &self.tree), "").into_owned();
+        let html = serve::wrap_html(title, &content, &tree, "", None, "", "", theme, true);
+        let html = SERVER_CHROME_RE
+            .replace_all(&html, "")
+            .replacen("<html lang=\"en\"", "<html lang=\"en\" data-static", 1)
+            .replace("hx-boost=\"true\"", "hx-boost=\"false\"")
+            .replace("=\"/assets/", &format!("=\"{}assets/", up(page)));
+
+        let target = self.out.join(file);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&target, html).with_context(|| format!("failed to write {file}"))
+    }
+
+    /// Point the links on `page` at the exported files. Links to pages only
+    /// the server can answer, like search, tags or an ambiguous wikilink,
+    /// lose their `href`.
+    fn relink(&self, page: &str, html: &str) -> String {
+        URL_RE
+            .replace_all(html, |caps: &regex::Captures| {
+                match self.local_url(page, &caps[2].replace("&amp;", "&")) {
+                    Some(url) => format!("{}{}\"", &caps[1], html_escape(&url)),
+                    None => String::new(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Where `url`, as written on `page`, leads in the exported site, as a
+    /// path from that page. External URLs and same-page anchors are kept.
+    fn local_url(&self, page: &str, url: &str) -> Option<String> {
+        if url.is_empty() || url.starts_with(['#', '?']) || url.starts_with("//") || has_scheme(url) {
+            return Some(url.to_string());
+        }
+        let (url, fragment) = match url.split_once('#') {
+            Some((url, fragment)) => (url, format!("#{fragment}")),
+            None => (url, String::new()),
+        };
+        let url = url.split('?').next().unwrap_or(url);
+
+        let base = folder(page);
+        let joined = match url.strip_prefix('/') {
+            Some(url) => url.to_string(),
+            None => format!("{base}{url}"),
+        };
+        let mut parts: Vec<&str> = Vec::new();
+        for part in joined.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                part => parts.push(part),
+            }
+        }
+        let path = percent_decode(&parts.join("/"));
+        // Served through the thumbnailer or as source, exported as is
+        let path = ["raw/", "thumb/"]
+            .iter()
+            .find_map(|route| path.strip_prefix(route).filter(|p| self.files.contains(*p)))
+            .unwrap_or(&path);
+
+        let target = if self.dirs.contains(&format!("{path}/")) || path.is_empty() {
+            let dir = if path.is_empty() { String::new() } else { format!("{path}/") };
+            format!("{dir}index.html")
+        } else if self.files.contains(path) {
+            match path.strip_suffix(".md") {
+                Some(stem) => format!("{stem}.html"),
+                None => path.to_string(),
+            }
+        } else {
+            return None;
+        };
+        Some(format!("{}{}{fragment}", up(page), encode_path(&target)))
+    }
+}
+
+/// The folder `page` is in, with a trailing `/`, or `""` at the root.
+fn folder(page: &str) -> &str {
+    &page[..page.rfind('/').map_or(0, |i| i + 1)]
+}
+
+/// The way from `page` back up to the root of the site.
+fn up(page: &str) -> String {
+    "../".repeat(folder(page).matches('/').count())
+}
+
+/// `https:`, `mailto:` and the like.
+fn has_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::render::Renderer;
+use crate::serve::html_escape;
+
+/// Files bigger than this are shown without highlighting.
+const MAX_HIGHLIGHT: usize = 512 * 1024;
+
+/// Text files shown unhighlighted, along with config formats there's no
+/// syntax for.
+const PLAIN_TEXT: [&str; 8] = ["txt", "log", "text", "toml", "ini", "cfg", "conf", "env"];
+
+/// Extensions syntect knows but that are served as files instead.
+const SERVED: [&str; 3] = ["svg", "html", "htm"];
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// Plain-text and source files, highlighted by their extension and shown
+/// with line numbers.
+pub struct Source;
+
+impl Source {
+    /// Whether a file is one this renders.
+    pub fn handles(path: &Path) -> bool {
+        let Some(ext) = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase) else {
+            return false;
+        };
+        PLAIN_TEXT.contains(&ext.as_str())
+            || (!SERVED.contains(&ext.as_str()) && SYNTAXES.find_syntax_by_extension(&ext).is_some())
+    }
+}
+
+impl Renderer for Source {
+    fn render(&self, name: &str, content: &str) -> String {
+        let syntax = Path::new(name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|ext| SYNTAXES.find_syntax_by_extension(&ext.to_lowercase()))
+            .filter(|_| content.len() <= MAX_HIGHLIGHT);
+        let code = match syntax {
+            Some(syntax) => highlight(syntax, content).unwrap_or_else(|| html_escape(content)),
+            None => html_escape(content),
+        };
+        let gutter: String = (1..=content.lines().count().max(1))
+            .map(|n| format!("<a id=\"L{n}\" href=\"#L{n}\">{n}</a>\n"))
+            .collect();
+        format!(
+            "<h1>{}</h1>\n<div class=\"source\"><pre class=\"line-numbers\">{gutter}</pre><pre><code>{code}</code></pre></div>\n",
+            html_escape(name)
+        )
+    }
+}
+
+/// HTML for `content` with `hl-`-prefixed classes on each token, styled by
+/// para.css.
+fn highlight(syntax: &SyntaxReference, content: &str) -> Option<String> {
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(
+        syntax,
+        &SYNTAXES,
+        ClassStyle::SpacedPrefixed { prefix: "hl-" },
+    );
+    for line in LinesWithEndings::from(content) {
+        generator.parse_html_for_line_which_includes_newline(line).ok()?;
+    }
+    Some(generator.finalize())
+}
@@ -0,0 +1,171 @@
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use spellbook::Dictionary;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::config::SpellingConfig;
+use crate::frontmatter;
+use crate::note;
+
+/// Words the vault accepts on top of the dictionary, one per line.
+const CUSTOM_DICTIONARY: &str = ".para/dictionary.txt";
+
+static WORD_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&#?\w+;|[\p{L}\p{N}]+(?:['’][\p{L}]+)*").unwrap());
+/// Link destinations, wikilink targets, autolinks, and inline HTML tags,
+/// none of which are prose.
+static NON_PROSE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\]\([^)]*\)|\[\[[^\]]*\]\]|<[^>]*>|https?://\S+|\S+@\S+\.\w+").unwrap()
+});
+static TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(/?)([a-zA-Z][a-zA-Z0-9]*)[^>]*>").unwrap());
+
+pub struct Speller {
+    dictionary: Dictionary,
+}
+
+pub struct Misspelling {
+    /// 1-based line number in the file, frontmatter included.
+    pub line: usize,
+    pub word: String,
+}
+
+impl Speller {
+    /// Load the configured Hunspell dictionary plus the vault's own word list.
+    pub fn load(notes_dir: &Path, config: &SpellingConfig) -> Result<Speller> {
+        let base = find_dictionary(&config.dictionary)
+            .with_context(|| format!("no Hunspell dictionary found for {}", config.dictionary))?;
+        let aff = std::fs::read_to_string(with_suffix(&base, "aff"))
+            .with_context(|| format!("failed to read {}.aff", base.display()))?;
+        let dic = std::fs::read_to_string(with_suffix(&base, "dic"))
+            .with_context(|| format!("failed to read {}.dic", base.display()))?;
+        let mut dictionary = match Dictionary::new(&aff, &dic) {
+            Ok(dictionary) => dictionary,
+            Err(e) => bail!("invalid dictionary {}: {e}", base.display()),
+        };
+
+        if let Ok(words) = std::fs::read_to_string(notes_dir.join(CUSTOM_DICTIONARY)) {
+            for word in words.lines().map(str::trim).filter(|w| !w.is_empty()) {
+                let _ = dictionary.add(word);
+            }
+        }
+        Ok(Speller { dictionary })
+    }
+
+    /// Whether a word should be flagged. Single letters, numbers, acronyms,
+    /// and mixed-case identifiers are never flagged.
+    fn is_misspelled(&self, word: &str) -> bool {
+        if word.chars().count() < 2 || word.starts_with('&') || word.chars().any(|c| c.is_numeric())
+        {
+            return false;
+        }
+        if word.chars().skip(1).any(|c| c.is_uppercase()) {
+            return false;
+        }
+        !self.dictionary.check(word)
+    }
+
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let mut suggestions = Vec::new();
+        self.dictionary.suggest(word, &mut suggestions);
+        suggestions.truncate(3);
+        suggestions
+    }
+
+    /// Misspelled words in a note's prose, skipping frontmatter, code, link
+    /// targets, and URLs.
+    pub fn check_note(&self, content: &str) -> Vec<Misspelling> {
+        let (_, body) = frontmatter::split(content);
+        let offset = content[..content.len() - body.len()].lines().count();
+
+        let mut found = Vec::new();
+        for (i, line) in note::strip_code(body).lines().enumerate() {
+            let prose = NON_PROSE_RE.replace_all(line, " ");
+            for word in WORD_RE.find_iter(&prose) {
+                if self.is_misspelled(word.as_str()) {
+                    found.push(Misspelling {
+                        line: offset + i + 1,
+                        word: word.as_str().to_string(),
+                    });
+                }
+            }
+        }
+        found
+    }
+
+    /// Wrap misspelled words in rendered HTML with `<span class="misspelled">`.
+    /// Text inside code, preformatted blocks, and scripts is left alone.
+    pub fn mark_html(&self, html: &str) -> String {
+        let mut out = String::with_capacity(html.len());
+        let mut skip_depth = 0usize;
+        let mut last = 0;
+        for tag in TAG_RE.captures_iter(html) {
+            let whole = tag.get(0).unwrap();
+            self.mark_text(&html[last..whole.start()], skip_depth > 0, &mut out);
+            out.push_str(whole.as_str());
+            last = whole.end();
+
+            let name = tag[2].to_lowercase();
+            if matches!(name.as_str(), "code" | "pre" | "script" | "style" | "svg") {
+                if &tag[1] == "/" {
+                    skip_depth = skip_depth.saturating_sub(1);
+                } else {
+                    skip_depth += 1;
+                }
+            }
+        }
+        self.mark_text(&html[last..], skip_depth > 0, &mut out);
+        out
+    }
+
+    fn mark_text(&self, text: &str, skip: bool, out: &mut String) {
+        if skip {
+            out.push_str(text);
+            return;
+        }
+        let mut last = 0;
+        for word in WORD_RE.find_iter(text) {
+            if self.is_misspelled(word.as_str()) {
+                out.push_str(&text[last..word.start()]);
+                out.push_str("<span class=\"misspelled\">");
+                out.push_str(word.as_str());
+                out.push_str("</span>");
+                last = word.end();
+            }
+        }
+        out.push_str(&text[last..]);
+    }
+}
+
+/// Resolve a dictionary name or path to the shared base path of its
+/// `.aff`/`.dic` files.
+fn find_dictionary(name: &str) -> Option<PathBuf> {
+    let exists = |base: &Path| with_suffix(base, "dic").exists();
+
+    if name.contains('/') {
+        let base = PathBuf::from(name.trim_end_matches(".dic").trim_end_matches(".aff"));
+        return exists(&base).then_some(base);
+    }
+
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/hunspell"),
+        PathBuf::from("/usr/share/myspell"),
+        PathBuf::from("/usr/share/myspell/dicts"),
+        PathBuf::from("/usr/local/share/hunspell"),
+        PathBuf::from("/opt/homebrew/share/hunspell"),
+        PathBuf::from("/Library/Spelling"),
+    ];
+    if let Ok(home) = std::env::var("HOME") {
+        let home = PathBuf::from(home);
+        dirs.push(home.join("Library").join("Spelling"));
+        dirs.push(home.join(".local").join("share").join("hunspell"));
+    }
+    dirs.into_iter()
+        .map(|dir| dir.join(name))
+        .find(|base| exists(base))
+}
+
+fn with_suffix(base: &Path, ext: &str) -> PathBuf {
+    PathBuf::from(format!("{}.{ext}", base.display()))
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::comments;
+use crate::config::Config;
+use crate::due;
+use crate::links;
+use crate::note::Note;
+use crate::para::Category;
+use crate::vault;
+
+/// How many of the largest and smallest notes to list.
+const EXTREMES: usize = 5;
+
+#[derive(Serialize)]
+pub struct Stats {
+    pub notes: usize,
+    /// Notes per PARA category, plus `Other` for the rest of the vault.
+    pub categories: BTreeMap<String, usize>,
+    pub words: usize,
+    /// Most words first.
+    pub largest: Vec<NoteSize>,
+    /// Fewest words first.
+    pub smallest: Vec<NoteSize>,
+    /// Notes created per `YYYY-MM`.
+    pub created: BTreeMap<String, usize>,
+    pub links: LinkCounts,
+}
+
+#[derive(Serialize)]
+pub struct NoteSize {
+    pub path: String,
+    pub words: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct LinkCounts {
+    /// Wikilinks and markdown links to notes.
+    pub total: usize,
+    /// Links that don't lead to exactly one note.
+    pub broken: usize,
+}
+
+/// Count up the vault. A note's creation date is its `created:` field if it
+/// has one, else when the file was created (or last modified, where the
+/// file system doesn't record creation).
+pub fn collect(notes_dir: &Path, config: &Config) -> Stats {
+    let index = links::index(notes_dir, config);
+    let mut categories: BTreeMap<String, usize> = Category::ALL
+        .iter()
+        .map(|category| (category.name().to_string(), 0))
+        .collect();
+    let mut sizes = Vec::new();
+    let mut created: BTreeMap<String, usize> = BTreeMap::new();
+    let mut link_counts = LinkCounts::default();
+
+    for path in vault::files(notes_dir, config) {
+        if path.extension().is_none_or(|e| e != "md") {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let note = Note::parse(vault::relative(notes_dir, &path), &comments::strip(&content));
+
+        let category = Category::of(&note.path).map_or("Other", |c| c.name());
+        *categories.entry(category.to_string()).or_default() += 1;
+
+        let date = note
+            .frontmatter
+            .get_str("created")
+            .and_then(|d| due::parse_date(d.get(..10).unwrap_or(&d)))
+            .or_else(|| {
+                let metadata = path.metadata().ok()?;
+                let time = metadata.created().or_else(|_| metadata.modified()).ok()?;
+                Some(DateTime::<Local>::from(time).date_naive())
+            });
+        if let Some(date) = date {
+            *created.entry(date.format("%Y-%m").to_string()).or_default() += 1;
+        }
+
+        for link in &note.links {
+            link_counts.total += 1;
+            if index.resolve(&note.path, link).is_none() {
+                link_counts.broken += 1;
+            }
+        }
+        sizes.push(NoteSize {
+            words: note.body.split_whitespace().count(),
+            path: note.path,
+        });
+    }
+
+    sizes.sort_by(|a, b| b.words.cmp(&a.words).then_with(|| a.path.cmp(&b.path)));
+    let words = sizes.iter().map(|s| s.words).sum();
+    let notes = sizes.len();
+    let smallest = sizes
+        .iter()
+        .rev()
+        .take(EXTREMES)
+        .map(|s| NoteSize {
+            path: s.path.clone(),
+            words: s.words,
+        })
+        .collect();
+    sizes.truncate(EXTREMES);
+    Stats {
+        notes,
+        categories,
+        words,
+        largest: sizes,
+        smallest,
+        created,
+        links: link_counts,
+    }
+}
+
+/// Print the vault's statistics as a table, or as JSON.
+pub fn run(notes_dir: &Path, config: &Config, json: bool) -> Result<()> {
+    let stats = collect(notes_dir, config);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!("{:<16}{:>8}", "Notes", stats.notes);
+    let other = stats.categories.get("Other").copied().unwrap_or(0);
+    for category in Category::ALL {
+        let count = stats.categories.get(category.name()).copied().unwrap_or(0);
+        println!("  {:<14}{count:>8}", category.name());
+    }
+    println!("  {:<14}{other:>8}", "Other");
+    println!("{:<16}{:>8}", "Words", stats.words);
+    println!("{:<16}{:>8}", "Links", stats.links.total);
+    println!("  {:<14}{:>8}", "Broken", stats.links.broken);
+
+    for (heading, notes) in [("Largest notes", &stats.largest), ("Smallest notes", &stats.smallest)] {
+        if notes.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{heading}");
+        for note in notes {
+            println!("  {:>8}  {}", note.words, note.path);
+        }
+    }
+
+    if !stats.created.is_empty() {
+        println!();
+        println!("Created per month");
+        for (month, count) in &stats.created {
+            println!("  {month:<14}{count:>8}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use crate::render::Renderer;
+use crate::serve::html_escape;
+
+/// CSV files, shown as a table that sorts by a column when its header is
+/// clicked.
+pub struct Csv;
+
+impl Renderer for Csv {
+    fn render(&self, name: &str, content: &str) -> String {
+        let table = render_csv(content).unwrap_or_else(|e| {
+            format!("<p>This file couldn't be read as CSV: {}</p>\n", html_escape(&e.to_string()))
+        });
+        format!("<h1>{}</h1>\n{table}", html_escape(name))
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/csv; charset=utf-8"
+    }
+}
+
+/// Render a CSV file as an HTML table, taking the first row as the header.
+/// Rows may have differing numbers of fields; short rows are padded.
+fn render_csv(content: &str) -> Result<String, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let rows = reader.records().collect::<Result<Vec<_>, _>>()?;
+    let Some((header, body)) = rows.split_first() else {
+        return Ok(String::new());
+    };
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+    let mut html = String::from("<table class=\"csv sortable\">\n<thead>\n<tr>");
+    for i in 0..columns {
+        html.push_str(&format!("<th>{}</th>", html_escape(header.get(i).unwrap_or(""))));
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in body {
+        html.push_str("<tr>");
+        for i in 0..columns {
+            let cell = row.get(i).unwrap_or("");
+            let class = if is_number(cell) { " class=\"num\"" } else { "" };
+            html.push_str(&format!("<td{class}>{}</td>", html_escape(cell)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+    Ok(html)
+}
+
+/// Whether a cell holds a number, so it can be aligned and sorted as one.
+fn is_number(cell: &str) -> bool {
+    let cell = cell.trim();
+    !cell.is_empty() && cell.replace(',', "").parse::<f64>().is_ok()
+}
@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use crate::config::Config;
+use crate::note::Note;
+use crate::para::Category;
+use crate::vault;
+
+/// Whether `tag` is `wanted` or nested under it (`area/work` under `area`),
+/// ignoring case.
+pub fn matches(tag: &str, wanted: &str) -> bool {
+    let tag = tag.to_lowercase();
+    let wanted = wanted.trim_start_matches('#').trim_end_matches('/').to_lowercase();
+    tag == wanted || tag.strip_prefix(&wanted).is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Notes carrying `tag`, grouped by PARA category in PARA order. Notes
+/// outside the four category folders come last, under `None`.
+pub fn by_category(notes_dir: &Path, config: &Config, tag: &str) -> Vec<(Option<Category>, Vec<String>)> {
+    let tagged: Vec<String> = vault::files(notes_dir, config)
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "md"))
+        .filter_map(|p| Note::load(notes_dir, p))
+        .filter(|note| note.tags.iter().any(|t| matches(t, tag)))
+        .map(|note| note.path)
+        .collect();
+
+    Category::ALL
+        .into_iter()
+        .map(Some)
+        .chain([None])
+        .map(|category| {
+            let paths: Vec<String> = tagged
+                .iter()
+                .filter(|path| Category::of(path) == category)
+                .cloned()
+                .collect();
+            (category, paths)
+        })
+        .filter(|(_, paths)| !paths.is_empty())
+        .collect()
+}
@@ -0,0 +1,269 @@
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, Options, parse_document};
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::UNIX_EPOCH;
+
+use crate::config::Config;
+use crate::due;
+use crate::note::{Note, Task};
+use crate::para::{self, Category};
+use crate::tags;
+use crate::vault;
+
+/// A task's checkbox in the source, inside any blockquotes: `- [ ] `,
+/// `* [x] `, `1. [ ] ` and so on.
+static TASK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?:>\s*)*(?:[-*+]|\d+[.)])\s+\[([ xX])\]").unwrap());
+
+/// What a page needs to tick tasks off in the note it shows.
+pub struct Toggles {
+    /// Line of each task in the file, in document order.
+    pub lines: Vec<usize>,
+    /// The file's version when the page was rendered.
+    pub mtime: String,
+}
+
+impl Toggles {
+    /// Toggles for the note at `path`, whose text with comments stripped is
+    /// `content`, parsed with the options it's rendered with.
+    pub fn new(path: &Path, content: &str, options: &Options) -> Option<Self> {
+        let arena = Arena::new();
+        let root = parse_document(&arena, content, options);
+        let lines = root
+            .descendants()
+            .filter(|node| matches!(node.data.borrow().value, NodeValue::TaskItem(_)))
+            .map(|node| node.data.borrow().sourcepos.start.line)
+            .collect();
+        Some(Self {
+            lines,
+            mtime: mtime(path)?,
+        })
+    }
+}
+
+/// Give task list items checkboxes that tick the task off in the file. If
+/// the note doesn't have the tasks `toggles` was made for (which happens
+/// when it changed in between) they're left as they are.
+pub fn render<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>, toggles: &Toggles) {
+    let items: Vec<_> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::TaskItem(_)))
+        .collect();
+    if items.len() != toggles.lines.len() {
+        return;
+    }
+
+    for (item, line) in items.into_iter().zip(&toggles.lines) {
+        let list = match item.parent().map(|list| list.data.borrow().value.clone()) {
+            Some(NodeValue::List(list)) => list,
+            _ => continue,
+        };
+        let done = {
+            let mut data = item.data.borrow_mut();
+            let NodeValue::TaskItem(symbol) = data.value else {
+                continue;
+            };
+            data.value = NodeValue::Item(list);
+            symbol.is_some()
+        };
+        let checkbox = arena.alloc(
+            NodeValue::HtmlInline(format!(
+                "<input type=\"checkbox\" class=\"task-toggle\" data-line=\"{line}\" data-version=\"{}\"{}> ",
+                toggles.mtime,
+                if done { " checked" } else { "" }
+            ))
+            .into(),
+        );
+        // In the task's first paragraph, so it sits on the same line
+        match item.first_child() {
+            Some(first) if matches!(first.data.borrow().value, NodeValue::Paragraph) => {
+                first.prepend(checkbox)
+            }
+            _ => item.prepend(checkbox),
+        }
+    }
+}
+
+/// The version of a file that a toggle must match: its modification time.
+pub fn mtime(path: &Path) -> Option<String> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos().to_string())
+}
+
+/// What came of ticking or unticking a task.
+pub enum Toggled {
+    /// The task is now `done`, and the file's new version is `mtime`.
+    Done { done: bool, mtime: String },
+    /// The file changed since the page was rendered.
+    Conflict,
+    /// The line isn't a task.
+    NotATask,
+}
+
+/// Flip the task on `line` (1-based) of the note at `path`, if the file is
+/// still at version `mtime`. The note is replaced in one step, so a crash
+/// midway can't leave it half written.
+pub fn toggle(path: &Path, line: usize, mtime: &str) -> std::io::Result<Toggled> {
+    if self::mtime(path).as_deref() != Some(mtime) {
+        return Ok(Toggled::Conflict);
+    }
+    let content = std::fs::read_to_string(path)?;
+    let mut lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let Some(text) = line.checked_sub(1).and_then(|i| lines.get(i)) else {
+        return Ok(Toggled::NotATask);
+    };
+    let Some(mark) = TASK_RE.captures(text).and_then(|caps| caps.get(1)) else {
+        return Ok(Toggled::NotATask);
+    };
+    let done = mark.as_str() == " ";
+    let flipped = format!(
+        "{}{}{}",
+        &text[..mark.start()],
+        if done { "x" } else { " " },
+        &text[mark.end()..]
+    );
+    lines[line - 1] = &flipped;
+    let updated = lines.concat();
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{name}.tmp"));
+    std::fs::write(&temp, &updated)?;
+    std::fs::rename(&temp, path)?;
+    Ok(Toggled::Done {
+        done,
+        mtime: self::mtime(path).unwrap_or_default(),
+    })
+}
+
+/// Which due dates [`collect`] keeps; tasks without one never pass.
+#[derive(Clone, Copy)]
+pub enum Due {
+    /// Due before today.
+    Overdue,
+    /// Due today or earlier.
+    Today,
+    /// Due within the next week, or earlier.
+    Week,
+    /// Due on or before the date.
+    By(NaiveDate),
+}
+
+impl Due {
+    /// `overdue`, `today`, `week` or a `YYYY-MM-DD` date.
+    pub fn parse(s: &str) -> Option<Due> {
+        match s.trim().to_lowercase().as_str() {
+            "overdue" => Some(Due::Overdue),
+            "today" => Some(Due::Today),
+            "week" => Some(Due::Week),
+            date => due::parse_date(date).map(Due::By),
+        }
+    }
+
+    fn keeps(&self, due: NaiveDate, today: NaiveDate) -> bool {
+        match self {
+            Due::Overdue => due < today,
+            Due::Today => due <= today,
+            Due::Week => today.checked_add_days(Days::new(7)).is_none_or(|end| due <= end),
+            Due::By(date) => due <= *date,
+        }
+    }
+}
+
+/// Which open tasks [`collect`] keeps. Every condition given must hold.
+pub struct Filter<'a> {
+    /// Tags the task must all carry, in its own text or its note's
+    /// frontmatter, nested tags included.
+    pub tags: &'a [String],
+    pub due: Option<Due>,
+}
+
+/// A note's open tasks.
+#[derive(Serialize)]
+pub struct NoteTasks {
+    /// Relative to the notes root.
+    pub path: String,
+    /// The project the note belongs to, if it's under the projects category.
+    pub project: Option<String>,
+    pub tasks: Vec<Task>,
+}
+
+/// Open tasks that pass `filter`, grouped by note and sorted by path, so a
+/// project's notes sit together. The archive is left out.
+pub fn collect(notes_dir: &Path, config: &Config, filter: &Filter, today: NaiveDate) -> Vec<NoteTasks> {
+    let archive = para::category_dir(notes_dir, Category::Archive);
+    let projects = para::category_dir(notes_dir, Category::Projects);
+    let mut notes = Vec::new();
+    for path in vault::files(notes_dir, config) {
+        if path.extension().is_none_or(|e| e != "md") || archive.as_ref().is_some_and(|a| path.starts_with(a)) {
+            continue;
+        }
+        let Some(note) = Note::load(notes_dir, &path) else {
+            continue;
+        };
+        let note_tags = note.frontmatter.get_list("tags");
+        let tasks: Vec<Task> = note
+            .tasks
+            .into_iter()
+            .filter(|task| !task.done)
+            .filter(|task| {
+                let task_tags = task.tags();
+                filter.tags.iter().all(|wanted| {
+                    note_tags
+                        .iter()
+                        .chain(&task_tags)
+                        .any(|tag| tags::matches(tag.trim_start_matches('#'), wanted))
+                })
+            })
+            .filter(|task| {
+                filter.due.is_none_or(|wanted| {
+                    task.due
+                        .as_deref()
+                        .and_then(due::parse_date)
+                        .is_some_and(|date| wanted.keeps(date, today))
+                })
+            })
+            .collect();
+        if tasks.is_empty() {
+            continue;
+        }
+
+        let project = projects
+            .as_ref()
+            .and_then(|dir| path.strip_prefix(dir).ok())
+            .and_then(|inside| inside.components().next())
+            .map(|first| first.as_os_str().to_string_lossy().trim_end_matches(".md").to_string());
+        notes.push(NoteTasks {
+            path: note.path,
+            project,
+            tasks,
+        });
+    }
+    notes.sort_by(|a, b| a.path.cmp(&b.path));
+    notes
+}
+
+/// Print open tasks under the note they're in, each with its line number,
+/// or as JSON.
+pub fn run(notes_dir: &Path, config: &Config, filter: &Filter, today: NaiveDate, json: bool) -> Result<()> {
+    let notes = collect(notes_dir, config, filter, today);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&notes)?);
+        return Ok(());
+    }
+
+    for (i, note) in notes.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", note.path);
+        for task in &note.tasks {
+            println!("  {:>4}  {}", task.line, task.text);
+        }
+    }
+    Ok(())
+}
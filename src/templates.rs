@@ -0,0 +1,141 @@
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDateTime;
+use regex::Regex;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::para::{self, Category};
+use crate::vault;
+
+/// Folder in the notes root that holds note templates, one `.md` per name.
+pub const TEMPLATES_DIR: &str = "_templates";
+
+/// `{{title}}`, `{{date}}`, `{{time}}`, or `{{date:%A}}` with a strftime
+/// pattern.
+static VAR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*(title|date|time)(?::([^}]*))?\s*\}\}").unwrap());
+
+/// Values for the placeholders in a template.
+pub struct Vars<'a> {
+    pub title: &'a str,
+    pub now: NaiveDateTime,
+}
+
+/// The template called `name`, or `None` if `_templates/{name}.md` doesn't
+/// exist.
+pub fn load(notes_dir: &Path, name: &str) -> Result<Option<String>> {
+    let path = notes_dir.join(TEMPLATES_DIR).join(format!("{name}.md"));
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(Some(content))
+}
+
+/// Fill in a template's placeholders. Unknown `{{...}}` are left as they are.
+pub fn fill(template: &str, vars: &Vars) -> String {
+    VAR_RE
+        .replace_all(template, |caps: &regex::Captures| {
+            let format = caps.get(2).map(|m| m.as_str().trim()).filter(|f| !f.is_empty());
+            match (&caps[1], format) {
+                ("title", _) => vars.title.to_string(),
+                ("date", None) => vars.now.format("%Y-%m-%d").to_string(),
+                ("time", None) => vars.now.format("%H:%M").to_string(),
+                (_, Some(format)) => {
+                    let mut out = String::new();
+                    // A bad pattern leaves the placeholder in place
+                    match write!(out, "{}", vars.now.format(format)) {
+                        Ok(()) => out,
+                        Err(_) => caps[0].to_string(),
+                    }
+                }
+                _ => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// What to create with [`create`].
+pub struct NewNote<'a> {
+    pub title: &'a str,
+    /// Folder relative to the notes root. The first part may name a PARA
+    /// category, so `projects/clientx` lands in `1 Projects/clientx`.
+    pub dir: &'a str,
+    pub template: Option<&'a str>,
+}
+
+/// Create a note titled `note.title`, filled from its template (or just a
+/// heading), and return its path relative to the notes root.
+pub fn create(notes_dir: &Path, note: &NewNote, now: NaiveDateTime) -> Result<String> {
+    let name = file_name(note.title);
+    if name.is_empty() {
+        bail!("the note needs a title");
+    }
+    let dir = folder(notes_dir, note.dir)?;
+    let path = notes_dir.join(&dir).join(format!("{name}.md"));
+    let relative = vault::relative(notes_dir, &path);
+    if path.exists() {
+        bail!("{relative} already exists");
+    }
+
+    let vars = Vars {
+        title: note.title.trim(),
+        now,
+    };
+    let content = match note.template {
+        Some(template) => {
+            let source = load(notes_dir, template)?
+                .with_context(|| format!("no template {TEMPLATES_DIR}/{template}.md"))?;
+            fill(&source, &vars)
+        }
+        None => format!("# {}\n", vars.title),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("failed to create {dir}"))?;
+    }
+    std::fs::write(&path, content).with_context(|| format!("failed to write {relative}"))?;
+    Ok(relative)
+}
+
+/// The folder a note goes in, with a leading PARA category name swapped for
+/// that category's folder.
+fn folder(notes_dir: &Path, dir: &str) -> Result<String> {
+    let parts: Vec<&str> = dir
+        .split('/')
+        .map(str::trim)
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    if parts.contains(&"..") {
+        bail!("{dir} is outside the notes directory");
+    }
+    let Some((first, rest)) = parts.split_first() else {
+        return Ok(String::new());
+    };
+    let top = match Category::from_dir_name(first) {
+        Some(category) if !notes_dir.join(first).exists() => para::category_dir(notes_dir, category)
+            .map(|path| vault::relative(notes_dir, &path))
+            .unwrap_or_else(|| first.to_string()),
+        _ => first.to_string(),
+    };
+    Ok(std::iter::once(top.as_str())
+        .chain(rest.iter().copied())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// A title made safe to use as a file name.
+fn file_name(title: &str) -> String {
+    title
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_matches('.')
+        .trim()
+        .to_string()
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use image::ImageFormat;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use std::path::Path;
+
+/// Longest edge of a generated thumbnail, in pixels.
+const MAX_EDGE: u32 = 320;
+
+/// Raster formats the thumbnailer can decode.
+pub fn supports(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "gif" | "webp"
+            )
+        })
+}
+
+/// Return a cached thumbnail for `source` as `(bytes, content_type)`,
+/// generating it on first use. The cache key covers the path, size, and
+/// modification time, so edited images get a fresh thumbnail.
+pub fn thumbnail(notes_dir: &Path, source: &Path) -> Result<(Vec<u8>, &'static str)> {
+    let cache_dir = notes_dir.join(".para").join("cache").join("thumbs");
+    let key = cache_key(source)?;
+
+    for (ext, content_type) in [("jpg", "image/jpeg"), ("png", "image/png")] {
+        if let Ok(bytes) = std::fs::read(cache_dir.join(format!("{key}.{ext}"))) {
+            return Ok((bytes, content_type));
+        }
+    }
+
+    let image = image::open(source)?.thumbnail(MAX_EDGE, MAX_EDGE);
+
+    // Keep transparency where it exists; everything else is smaller as JPEG
+    let (format, ext, content_type) = if image.color().has_alpha() {
+        (ImageFormat::Png, "png", "image/png")
+    } else {
+        (ImageFormat::Jpeg, "jpg", "image/jpeg")
+    };
+    let mut bytes = Vec::new();
+    if format == ImageFormat::Jpeg {
+        image.to_rgb8().write_to(&mut Cursor::new(&mut bytes), format)?;
+    } else {
+        image.write_to(&mut Cursor::new(&mut bytes), format)?;
+    }
+
+    std::fs::create_dir_all(&cache_dir)?;
+    std::fs::write(cache_dir.join(format!("{key}.{ext}")), &bytes)?;
+    Ok((bytes, content_type))
+}
+
+fn cache_key(source: &Path) -> Result<String> {
+    let meta = source.metadata()?;
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    meta.len().hash(&mut hasher);
+    meta.modified()?.hash(&mut hasher);
+    Ok(format!("{:016x}", hasher.finish()))
+}
@@ -0,0 +1,114 @@
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Anchorizer, Arena};
+
+use crate::callouts::html_block;
+use crate::serve::html_escape;
+
+/// Paragraphs consisting of just this are replaced by the outline.
+const MARKER: &str = "[TOC]";
+
+struct Heading {
+    level: u8,
+    text: String,
+    id: String,
+}
+
+/// Replace `[TOC]` paragraphs with an outline of the note's headings, or
+/// when `always` is set and there is no marker, put one at the top (after
+/// the note's title, if it opens with one).
+pub fn render<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>, always: bool) {
+    let markers: Vec<_> = root.children().filter(|node| is_marker(node)).collect();
+    if markers.is_empty() && !always {
+        return;
+    }
+
+    // Ids are given out in document order, the same way the HTML renderer
+    // gives them to the headings themselves.
+    let mut anchorizer = Anchorizer::new();
+    let headings: Vec<Heading> = root
+        .descendants()
+        .filter_map(|node| match node.data.borrow().value {
+            NodeValue::Heading(ref heading) => Some(heading.level),
+            _ => None,
+        }
+        .map(|level| {
+            let text = text_of(node);
+            Heading {
+                level,
+                id: anchorizer.anchorize(text.clone()),
+                text,
+            }
+        }))
+        .collect();
+    if headings.is_empty() {
+        for marker in markers {
+            marker.detach();
+        }
+        return;
+    }
+
+    let html = outline(&headings);
+    if markers.is_empty() {
+        let toc = html_block(arena, html);
+        let first = root
+            .children()
+            .find(|node| !matches!(node.data.borrow().value, NodeValue::FrontMatter(_)));
+        match first {
+            Some(first) if matches!(first.data.borrow().value, NodeValue::Heading(ref h) if h.level == 1) => {
+                first.insert_after(toc)
+            }
+            _ => root.prepend(toc),
+        }
+    } else {
+        for marker in markers {
+            marker.insert_before(html_block(arena, html.clone()));
+            marker.detach();
+        }
+    }
+}
+
+fn is_marker<'a>(node: &'a AstNode<'a>) -> bool {
+    matches!(node.data.borrow().value, NodeValue::Paragraph) && text_of(node).trim() == MARKER
+}
+
+/// A collapsible list of links to the headings, nested by level.
+fn outline(headings: &[Heading]) -> String {
+    let mut html = String::from("<details class=\"toc\" open>\n<summary>Contents</summary>\n");
+    let mut open: Vec<u8> = Vec::new();
+    for heading in headings {
+        while open.last().is_some_and(|&level| level > heading.level) {
+            open.pop();
+            html.push_str("</li>\n</ul>\n");
+        }
+        if open.last().is_some_and(|&level| level == heading.level) {
+            html.push_str("</li>\n");
+        } else {
+            open.push(heading.level);
+            html.push_str("<ul>\n");
+        }
+        html.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            html_escape(&heading.id),
+            html_escape(&heading.text)
+        ));
+    }
+    for _ in open {
+        html.push_str("</li>\n</ul>\n");
+    }
+    html.push_str("</details>\n");
+    html
+}
+
+/// A node's text as the HTML renderer collects it for heading ids.
+fn text_of<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for child in node.descendants() {
+        match &child.data.borrow().value {
+            NodeValue::Text(literal) => text.push_str(literal),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            NodeValue::LineBreak | NodeValue::SoftBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+
+use crate::vault;
+
+/// Folder under the notes root that deleted notes are moved into. Hidden, so
+/// the rest of para never sees its contents.
+pub const TRASH_DIR: &str = ".trash";
+
+/// Records where a trashed item came from, inside its slot.
+const ORIGIN_FILE: &str = ".origin";
+
+pub struct Item {
+    /// Slot name under `.trash/`, which is when the item was deleted.
+    pub id: String,
+    /// Where the item lived, relative to the notes root.
+    pub path: String,
+    pub is_dir: bool,
+}
+
+impl Item {
+    /// The deletion time as `YYYY-MM-DD HH:MM:SS`.
+    pub fn deleted(&self) -> String {
+        let stamp = self.id.split('-').take(2).collect::<Vec<_>>().join("-");
+        chrono::NaiveDateTime::parse_from_str(&stamp, "%Y%m%d-%H%M%S")
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or(stamp)
+    }
+}
+
+/// Move a file or folder (relative to the notes root) into its own slot in
+/// the trash, returning the slot id. Hidden paths, like the trash itself,
+/// `.para/` or `.git/`, are refused.
+pub fn trash(notes_dir: &Path, relative: &str) -> Result<String> {
+    if vault::is_hidden(relative) {
+        bail!("{relative} is hidden and can't be deleted");
+    }
+    let source = notes_dir.join(relative);
+    let name = source.file_name().context("Nothing to delete")?.to_owned();
+
+    let trash_dir = notes_dir.join(TRASH_DIR);
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut id = stamp.clone();
+    let mut n = 1;
+    while trash_dir.join(&id).exists() {
+        n += 1;
+        id = format!("{stamp}-{n}");
+    }
+
+    let slot = trash_dir.join(&id);
+    std::fs::create_dir_all(&slot)?;
+    std::fs::write(slot.join(ORIGIN_FILE), relative)?;
+    std::fs::rename(&source, slot.join(name))
+        .with_context(|| format!("Failed to move {relative} to the trash"))?;
+    Ok(id)
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn list(notes_dir: &Path) -> Vec<Item> {
+    let Ok(entries) = std::fs::read_dir(notes_dir.join(TRASH_DIR)) else {
+        return Vec::new();
+    };
+    let mut items: Vec<Item> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let id = entry.file_name().to_string_lossy().into_owned();
+            let path = std::fs::read_to_string(entry.path().join(ORIGIN_FILE)).ok()?;
+            let stored = stored_path(&entry.path(), &path)?;
+            Some(Item {
+                is_dir: stored.is_dir(),
+                id,
+                path,
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| b.id.cmp(&a.id));
+    items
+}
+
+/// Put an item back where it was, returning its path. Refuses to overwrite
+/// anything created there since.
+pub fn restore(notes_dir: &Path, id: &str) -> Result<String> {
+    let slot = slot(notes_dir, id)?;
+    let path = std::fs::read_to_string(slot.join(ORIGIN_FILE))?;
+    let stored = stored_path(&slot, &path).context("Trashed item is missing")?;
+    if Path::new(&path).components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+        bail!("Invalid original path {path}");
+    }
+    let target = notes_dir.join(&path);
+    if target.exists() {
+        bail!("{path} already exists");
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&stored, &target)?;
+    std::fs::remove_dir_all(&slot)?;
+    Ok(path)
+}
+
+/// Delete an item for good, returning the path it was deleted from.
+pub fn purge(notes_dir: &Path, id: &str) -> Result<String> {
+    let slot = slot(notes_dir, id)?;
+    let path = std::fs::read_to_string(slot.join(ORIGIN_FILE)).unwrap_or_default();
+    std::fs::remove_dir_all(&slot)?;
+    Ok(path)
+}
+
+fn slot(notes_dir: &Path, id: &str) -> Result<PathBuf> {
+    if id.is_empty() || id.contains(['/', '\\']) || id.starts_with('.') {
+        bail!("Invalid trash item");
+    }
+    let slot = notes_dir.join(TRASH_DIR).join(id);
+    if !slot.is_dir() {
+        bail!("No such trash item");
+    }
+    Ok(slot)
+}
+
+fn stored_path(slot: &Path, origin: &str) -> Option<PathBuf> {
+    let name = Path::new(origin).file_name()?;
+    let stored = slot.join(name);
+    stored.exists().then_some(stored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_refuses_ids_outside_the_trash() {
+        let notes = std::env::temp_dir().join(format!("para-trash-{}", std::process::id()));
+        std::fs::create_dir_all(notes.join(TRASH_DIR).join("20260101-120000")).unwrap();
+        for id in ["", "..", ".origin", "../x", "a/b", "a\\b", "/etc"] {
+            let error = slot(&notes, id).unwrap_err().to_string();
+            assert_eq!(error, "Invalid trash item", "{id:?}");
+        }
+        assert_eq!(slot(&notes, "20260101-120001").unwrap_err().to_string(), "No such trash item");
+        assert_eq!(
+            slot(&notes, "20260101-120000").unwrap(),
+            notes.join(TRASH_DIR).join("20260101-120000")
+        );
+        std::fs::remove_dir_all(&notes).unwrap();
+    }
+
+    #[test]
+    fn trash_refuses_hidden_paths() {
+        let notes = std::env::temp_dir().join(format!("para-trash-hidden-{}", std::process::id()));
+        for path in [".para/audit.log", ".git", "notes/.trash"] {
+            assert!(trash(&notes, path).is_err(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use ureq::unversioned::resolver::{DefaultResolver, ResolvedSocketAddrs, Resolver};
+use ureq::unversioned::transport::{DefaultConnector, NextTimeout};
+
+use crate::serve::html_escape;
+
+const MAX_BODY_BYTES: u64 = 512 * 1024;
+
+/// Previews fetched at once. Links past this render plainly and are tried
+/// again on a later render.
+const MAX_FETCHES: usize = 4;
+
+/// A paragraph containing nothing but an external link, as comrak renders it.
+static STANDALONE_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^<p><a href="(https?://[^"]+)">([^<]*)</a></p>$"#).unwrap()
+});
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Preview {
+    title: Option<String>,
+    description: Option<String>,
+    favicon: Option<String>,
+}
+
+/// Fetches and caches metadata for external links. Lookups never block on the
+/// network: unknown URLs render as plain anchors while a background fetch
+/// fills the cache for the next render.
+pub struct LinkPreviews {
+    cache_path: PathBuf,
+    cache: Mutex<HashMap<String, Preview>>,
+    pending: Mutex<HashSet<String>>,
+}
+
+impl LinkPreviews {
+    pub fn new(notes_dir: &std::path::Path) -> Arc<Self> {
+        let cache_path = notes_dir.join(".para").join("cache").join("link-previews.json");
+        let cache = std::fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        Arc::new(Self {
+            cache_path,
+            cache: Mutex::new(cache),
+            pending: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Replace standalone external links in rendered HTML with link cards.
+    pub fn render_cards(self: &Arc<Self>, html: &str) -> String {
+        STANDALONE_LINK_RE
+            .replace_all(html, |caps: &regex::Captures| {
+                let url = decode_entities(&caps[1]);
+                let cached = self.cache.lock().unwrap().get(&url).cloned();
+                match cached {
+                    Some(preview) if preview.title.is_some() => render_card(&url, &preview),
+                    Some(_) => caps[0].to_string(),
+                    None => {
+                        self.fetch_in_background(&url);
+                        caps[0].to_string()
+                    }
+                }
+            })
+            .to_string()
+    }
+
+    fn fetch_in_background(self: &Arc<Self>, url: &str) {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.len() >= MAX_FETCHES || !pending.insert(url.to_string()) {
+                return;
+            }
+        }
+
+        let previews = Arc::clone(self);
+        let url = url.to_string();
+        tokio::task::spawn_blocking(move || {
+            // Failed fetches are cached too so offline use doesn't retry on every render
+            let preview = fetch_preview(&url).unwrap_or(Preview {
+                title: None,
+                description: None,
+                favicon: None,
+            });
+            previews.cache.lock().unwrap().insert(url.clone(), preview);
+            previews.pending.lock().unwrap().remove(&url);
+            previews.save();
+        });
+    }
+
+    fn save(&self) {
+        let cache = self.cache.lock().unwrap();
+        // Only successful previews are persisted; failures are retried next run
+        let persisted: HashMap<_, _> = cache.iter().filter(|(_, p)| p.title.is_some()).collect();
+        if let Some(parent) = self.cache_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Ok(json) = serde_json::to_string_pretty(&persisted) {
+            let _ = std::fs::write(&self.cache_path, json);
+        }
+    }
+}
+
+/// Resolves hosts like ureq normally does, but only to public addresses, so
+/// a link in a note can't make the server read its own machine or network,
+/// through a redirect or a name that resolves differently the second time.
+#[derive(Debug)]
+struct PublicOnly;
+
+impl Resolver for PublicOnly {
+    fn resolve(
+        &self,
+        uri: &ureq::http::Uri,
+        config: &ureq::config::Config,
+        timeout: NextTimeout,
+    ) -> Result<ResolvedSocketAddrs, ureq::Error> {
+        let resolved = DefaultResolver::default().resolve(uri, config, timeout)?;
+        let mut public = self.empty();
+        for addr in resolved.iter().filter(|addr| is_public(addr.ip())) {
+            public.push(*addr);
+        }
+        if public.is_empty() {
+            return Err(ureq::Error::HostNotFound);
+        }
+        Ok(public)
+    }
+}
+
+/// Whether `ip` is on the internet, rather than this machine, a private or
+/// carrier-grade NAT network, or link-local like cloud metadata services.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+fn fetch_preview(url: &str) -> Option<Preview> {
+    let config = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(5)))
+        .build();
+    let agent = ureq::Agent::with_parts(config, DefaultConnector::default(), PublicOnly);
+    let mut response = agent.get(url).call().ok()?;
+    let body = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_BODY_BYTES)
+        .read_to_string()
+        .ok()?;
+
+    let title = meta_content(&body, "og:title").or_else(|| {
+        Regex::new(r"(?is)<title[^>]*>(.*?)</title>")
+            .unwrap()
+            .captures(&body)
+            .map(|c| decode_entities(c[1].trim()))
+    })?;
+    let description =
+        meta_content(&body, "og:description").or_else(|| meta_content(&body, "description"));
+
+    let origin = Regex::new(r"^(https?://[^/]+)").unwrap().captures(url)?[1].to_string();
+    let favicon = Regex::new(r#"(?is)<link[^>]+rel=["'](?:shortcut )?icon["'][^>]*>"#)
+        .unwrap()
+        .find(&body)
+        .and_then(|tag| attr(tag.as_str(), "href"))
+        .map(|href| resolve_url(&origin, &href))
+        .unwrap_or_else(|| format!("{origin}/favicon.ico"));
+
+    Some(Preview {
+        title: Some(title),
+        description,
+        favicon: Some(favicon),
+    })
+}
+
+fn meta_content(html: &str, name: &str) -> Option<String> {
+    let pattern = format!(
+        r#"(?is)<meta[^>]+(?:property|name)=["']{}["'][^>]*>"#,
+        regex::escape(name)
+    );
+    let tag = Regex::new(&pattern).ok()?.find(html)?;
+    attr(tag.as_str(), "content").filter(|c| !c.is_empty())
+}
+
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!(r#"(?is)\b{}=["']([^"']*)["']"#, regex::escape(name));
+    Regex::new(&pattern)
+        .ok()?
+        .captures(tag)
+        .map(|c| decode_entities(c[1].trim()))
+}
+
+fn resolve_url(origin: &str, href: &str) -> String {
+    if href.starts_with("http://") || href.starts_with("https://") {
+        href.to_string()
+    } else if let Some(rest) = href.strip_prefix("//") {
+        let scheme = origin.split("://").next().unwrap_or("https");
+        format!("{scheme}://{rest}")
+    } else {
+        format!("{}/{}", origin, href.trim_start_matches('/'))
+    }
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn render_card(url: &str, preview: &Preview) -> String {
+    let host = url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .unwrap_or(url);
+    let icon = preview
+        .favicon
+        .as_deref()
+        .map(|src| {
+            format!(
+                "<img class=\"link-card-icon\" src=\"{}\" alt=\"\" loading=\"lazy\">",
+                html_escape(src)
+            )
+        })
+        .unwrap_or_default();
+    let description = preview
+        .description
+        .as_deref()
+        .map(|d| format!("<span class=\"link-card-description\">{}</span>", html_escape(d)))
+        .unwrap_or_default();
+
+    format!(
+        "<a class=\"link-card\" href=\"{url}\">{icon}<span class=\"link-card-body\"><span class=\"link-card-title\">{title}</span>{description}<span class=\"link-card-host\">{host}</span></span></a>",
+        url = html_escape(url),
+        icon = icon,
+        title = html_escape(preview.title.as_deref().unwrap_or(url)),
+        description = description,
+        host = html_escape(host)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_public_refuses_local_and_private_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.127.255.255",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn is_public_allows_internet_addresses() {
+        for ip in ["8.8.8.8", "1.1.1.1", "100.128.0.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{Result, bail};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::exif;
+
+/// Folder, next to the note, that uploads are saved into.
+pub const ATTACHMENTS_DIR: &str = "_attachments";
+
+/// Save an uploaded file into the `_attachments/` folder beside `note_dir`,
+/// returning the file name it was stored under. Names are cleaned up and
+/// numbered rather than overwriting an existing file, and image metadata is
+/// stripped when `uploads.strip_metadata` is on.
+pub fn store(note_dir: &Path, config: &Config, name: &str, bytes: &[u8]) -> Result<String> {
+    check(config, name)?;
+    let name = clean_name(name);
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((&name, ""));
+
+    let dir = note_dir.join(ATTACHMENTS_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let mut stored = name.clone();
+    let mut n = 1;
+    while dir.join(&stored).exists() {
+        n += 1;
+        stored = format!("{stem}-{n}.{ext}");
+    }
+
+    if config.uploads.strip_metadata {
+        std::fs::write(dir.join(&stored), exif::strip_metadata(ext, bytes))?;
+    } else {
+        std::fs::write(dir.join(&stored), bytes)?;
+    }
+    Ok(stored)
+}
+
+/// File name for an image pasted from the clipboard, or `None` if the
+/// content type isn't an image we keep.
+pub fn pasted_name(content_type: &str) -> Option<String> {
+    let ext = match content_type.split(';').next()?.trim() {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => return None,
+    };
+    Some(format!("pasted-{}.{ext}", chrono::Local::now().format("%Y%m%d-%H%M%S")))
+}
+
+/// Refuse file types that wouldn't show up in the vault's listings, and SVG,
+/// which can carry scripts that would run as the vault's own pages.
+pub fn check(config: &Config, name: &str) -> Result<()> {
+    let name = clean_name(name);
+    if !config.files.shows(&name) || name.to_lowercase().ends_with(".svg") {
+        bail!("{name}: file type not allowed");
+    }
+    Ok(())
+}
+
+/// Markdown linking to a stored upload from its note: an image embed for
+/// images, a plain link otherwise.
+pub fn markdown_link(stored: &str) -> String {
+    let href = format!("{ATTACHMENTS_DIR}/{}", stored.replace(' ', "%20"));
+    let ext = stored.rsplit_once('.').map(|(_, e)| e.to_lowercase()).unwrap_or_default();
+    if matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp") {
+        let alt = stored.rsplit_once('.').map_or(stored, |(stem, _)| stem);
+        format!("![{alt}]({href})")
+    } else {
+        format!("[{stored}]({href})")
+    }
+}
+
+/// Keep just the file name, without path separators, control characters,
+/// characters that break markdown links, or quotes and ampersands that would
+/// need escaping wherever the name ends up in HTML.
+fn clean_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if matches!(c, '(' | ')' | '[' | ']' | '<' | '>' | '#' | '?' | '%' | '"' | '\'' | '&' | '`') { '-' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').to_string();
+    if cleaned.is_empty() { "upload".to_string() } else { cleaned }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_name_drops_directories() {
+        assert_eq!(clean_name("../../etc/passwd"), "passwd");
+        assert_eq!(clean_name("C:\\Users\\me\\photo.png"), "photo.png");
+        assert_eq!(clean_name("a/b\\c.pdf"), "c.pdf");
+    }
+
+    #[test]
+    fn clean_name_replaces_markup_characters() {
+        assert_eq!(clean_name("x\" onmouseover=\"alert(1).png"), "x- onmouseover=-alert-1-.png");
+        assert_eq!(clean_name("it's `a` & <b>.jpg"), "it-s -a- - -b-.jpg");
+        assert_eq!(clean_name("[link](x).gif"), "-link--x-.gif");
+    }
+
+    #[test]
+    fn clean_name_strips_leading_dots_and_control_characters() {
+        assert_eq!(clean_name(".htaccess"), "htaccess");
+        assert_eq!(clean_name("...hidden.png"), "hidden.png");
+        assert_eq!(clean_name("a\nb\u{7}.png"), "ab.png");
+        assert_eq!(clean_name("  spaced.png  "), "spaced.png");
+    }
+
+    #[test]
+    fn clean_name_falls_back_when_nothing_is_left() {
+        assert_eq!(clean_name(""), "upload");
+        assert_eq!(clean_name("..."), "upload");
+        assert_eq!(clean_name("dir/"), "upload");
+        assert_eq!(clean_name("\u{0}\u{1}"), "upload");
+    }
+
+    #[test]
+    fn check_allows_listed_types_but_not_svg() {
+        let config = Config::default();
+        assert!(check(&config, "photo.png").is_ok());
+        assert!(check(&config, "Report.PDF").is_ok());
+        assert!(check(&config, "drawing.svg").is_err());
+        assert!(check(&config, "drawing.SVG").is_err());
+        assert!(check(&config, "script.html").is_err());
+        assert!(check(&config, "no-extension").is_err());
+    }
+}
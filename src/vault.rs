@@ -0,0 +1,70 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// Every file in the vault, sorted by path. Hidden entries (`.name`) and
+/// private subtrees are skipped; `_`-prefixed folders are included.
+pub fn files(notes_dir: &Path, config: &Config) -> Vec<PathBuf> {
+    fn walk(notes_dir: &Path, config: &Config, dir: &Path, found: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() && !config.for_dir(notes_dir, &path).private => {
+                    walk(notes_dir, config, &path, found);
+                }
+                Ok(t) if t.is_file() => found.push(path),
+                _ => {}
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    if !config.for_dir(notes_dir, notes_dir).private {
+        walk(notes_dir, config, notes_dir, &mut found);
+    }
+    found.sort();
+    found
+}
+
+/// A path relative to the notes root, using `/` separators.
+pub fn relative(notes_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(notes_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Whether any part of a path relative to the notes root starts with a dot,
+/// hiding it from para.
+pub fn is_hidden(relative: &str) -> bool {
+    relative.split('/').any(|part| part.starts_with('.'))
+}
+
+/// Compile a path glob (`*` within a segment, `**` across segments, `?` one
+/// character) into a regex over relative paths. A pattern naming a folder
+/// also matches everything inside it.
+pub fn glob(pattern: &str) -> Regex {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    let mut re = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str(".*");
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push_str("(/.*)?$");
+    Regex::new(&re).expect("escaped glob is a valid regex")
+}